@@ -1,7 +1,7 @@
 use crate::error::ContractError;
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Coin, Uint128};
-use cw20::Cw20Coin;
+use cosmwasm_std::{to_json_binary, Addr, BankMsg, Coin, CosmosMsg, StdResult, Uint128, WasmMsg};
+use cw20::{Cw20Coin, Cw20ExecuteMsg};
 use std::convert::TryInto;
 
 #[cw_serde]
@@ -12,7 +12,6 @@ pub enum Amount {
 }
 
 impl Amount {
-    // TODO: write test for this
     pub fn from_parts(denom: String, amount: Uint128) -> Self {
         if denom.starts_with("cw20:") {
             let address = denom.get(5..).unwrap().into();
@@ -54,10 +53,10 @@ impl Amount {
 
     /// convert the amount into u64
     pub fn u64_amount(&self) -> Result<u64, ContractError> {
-        Ok(self.amount().u128().try_into().map_err(|_| ContractError::StdErr {
+        self.amount().u128().try_into().map_err(|_| ContractError::StdErr {
             kind: "ConversionError".to_string(),
             detail: "Amount too large to convert to u64".to_string(),
-        })?)
+        })
     }
 
     pub fn is_empty(&self) -> bool {
@@ -67,3 +66,46 @@ impl Amount {
         }
     }
 }
+
+impl Amount {
+    /// Message sending this amount from the contract to `recipient`,
+    /// a bank send for native coins and a cw20 Transfer for tokens
+    pub fn transfer_msg(&self, recipient: &Addr) -> StdResult<CosmosMsg> {
+        match self {
+            Amount::Native(coin) => Ok(BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![coin.clone()],
+            }
+            .into()),
+            Amount::Cw20(coin) => Ok(WasmMsg::Execute {
+                contract_addr: coin.address.clone(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount: coin.amount,
+                })?,
+                funds: vec![],
+            }
+            .into()),
+        }
+    }
+
+    /// Message pulling this amount from `owner` to `recipient` against an allowance,
+    /// native coins can't be pulled and have to be attached to the message instead
+    pub fn transfer_from_msg(&self, owner: &Addr, recipient: &Addr) -> Result<CosmosMsg, ContractError> {
+        match self {
+            Amount::Native(coin) => Err(ContractError::InvalidFunds {
+                denom: coin.denom.clone(),
+            }),
+            Amount::Cw20(coin) => Ok(WasmMsg::Execute {
+                contract_addr: coin.address.clone(),
+                msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: owner.to_string(),
+                    recipient: recipient.to_string(),
+                    amount: coin.amount,
+                })?,
+                funds: vec![],
+            }
+            .into()),
+        }
+    }
+}
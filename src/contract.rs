@@ -1,46 +1,264 @@
 use cosmwasm_std::{
-    entry_point, from_json, Addr, Binary, Deps, DepsMut, Env, MessageInfo, QueryRequest, Response, SubMsg, Timestamp, Uint128, WasmMsg, WasmQuery
+    entry_point, from_json, Addr, Api, Binary, Coin, Decimal, Deps, DepsMut, Env, MessageInfo, Order, QuerierWrapper, QueryRequest, Reply, Response, StdResult, Storage, SubMsg, Timestamp, Uint128, WasmMsg, WasmQuery
 };
-use cw20::{AllowanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg};
+use cw20::{AllowanceResponse, BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg, MinterResponse, TokenInfoResponse};
+use cw_storage_plus::Bound;
 use crate::error::{ContractError, ContractResult};
-use crate::msg::{DepositMsg, ExecuteMsg, InstantiateMsg, LoanMsg, QueryMsg, RepayMsg, TransactMsg, WithdrawMsg};
+use crate::external::query_price;
+use crate::migrations::{run_migrations, LEGACY_VERSION};
+use crate::msg::{
+    AdminMsg, AdminResponse, BorrowerPositionResponse, Cw721ReceiveMsg, Cw721ReceiverExecuteMsg, DepositMsg, DepositQuoteResponse, ExecuteMsg, HealthFactorResponse,
+    BorrowerInfo, InstantiateMsg, DefaultedLoanInfo, InvariantCheck, InvariantsResponse, LenderInfo, ListDefaultedLoansResponse, LenderPositionResponse, ListBorrowersResponse, ListLendersResponse, ListLoansResponse, LoanInfo, LoanMsg, LoanQuoteResponse, MigrateMsg, NftContractInfoResponse, NftInfoResponse, NumTokensResponse, OwnerOfResponse, PauseStateResponse, PoolDetailsResponse,
+    PoolStateResponse, QueryMsg, TokensResponse, ReceiveMsg, RepayMsg, RepayQuoteResponse, RolloverSourceResponse, PendingLoanTransferResponse, TransactMsg, UpdateConfigMsg, UserDetailsResponse, WithdrawMsg, WithdrawalQueueResponse,
+};
 use crate::state::{
-    ADMIN, ASSET_CONFIG, COLLATERAL_CONFIG, COLLATERAL_SUBMITTED, INTEREST_EARNED,
-    INTEREST_TO_REPAY, NANOSECONDS_IN_YEAR, POOL_CONFIG, PRINCIPLE_DEPLOYED, PRINCIPLE_TO_REPAY,
-    TOTAL_ASSET_AVAILABLE, TOTAL_COLLATERAL_AVAILABLE
+    loans, ADMIN, ASSET_CONFIG, COLLATERAL_CONFIG, DEFAULTED_LOANS, FEE_CONFIG, GUARDIAN, LENDERS, LIQUIDATION_CONFIG, LOAN_COUNT,
+    ORACLE, PAUSE_STATE, PENDING_ADMIN, PENDING_LOAN_TRANSFERS, PENDING_ROLLOVER, POOL_CONFIG, ROLLOVER_SOURCES, POOL_STATE, RECEIPT_TOKEN, SECONDS_IN_YEAR, SETTLEMENT,
+    TOTAL_ASSET_AVAILABLE, TOTAL_COLLATERAL_AVAILABLE, TOTAL_PROTOCOL_EARNINGS, TOTAL_QUEUED_WITHDRAWALS, WITHDRAWAL_QUEUE
 };
-use crate::types::{CoinConfig, PoolConfig};
+use crate::types::{CoinConfig, DefaultedLoan, FeeConfig, LenderPosition, LiquidationConfig, Loan, Operation, PendingRollover, PoolConfig, PoolState, QueuedWithdrawal, Settlement};
 use cosmwasm_std::to_json_binary;
-use cw_utils::nonpayable;
+use cw_utils::{must_pay, nonpayable, parse_reply_instantiate_data};
+use cw2::{get_contract_version, set_contract_version, ContractVersion};
+use semver::Version;
+
+const CONTRACT_NAME: &str = concat!("crates.io:", env!("CARGO_PKG_NAME"));
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const RECEIPT_TOKEN_REPLY_ID: u64 = 1;
+const ROLLOVER_REPLY_ID: u64 = 2;
 
 #[entry_point]
 pub fn instantiate(
-    deps: DepsMut,
-    _env: Env,
+    mut deps: DepsMut,
+    env: Env,
     _info: MessageInfo,
     msg: InstantiateMsg,
 ) -> ContractResult<Response> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    if msg.config.maturationdate <= env.block.time.seconds() {
+        return Err(ContractError::InvalidConfig { reason: "maturity must be in the future".to_string() });
+    }
+    validate_pool_config(&msg.config)?;
+
     let admin_addr = deps.api.addr_validate(&msg.admin)?;
-    POOL_CONFIG.save(deps.storage, &msg.config)?;
-    ADMIN.save(deps.storage, &admin_addr)?;
+    let oracle_addr = deps.api.addr_validate(&msg.oracle)?;
+
+    let mut config = msg.config;
+    config.asset = validate_denom(deps.api, &config.asset).map_err(|_| ContractError::InvalidAsset {})?;
+    config.collateral = validate_denom(deps.api, &config.collateral).map_err(|_| ContractError::InvalidCollateral {})?;
+    if config.asset == config.collateral {
+        return Err(ContractError::InvalidCollateral {});
+    }
+
+    validate_liquidation_config(&msg.liquidation)?;
+    let fees = msg.fees.unwrap_or_default();
+    validate_fee_config(&fees, &config)?;
+
+    POOL_CONFIG.save(deps.storage, &config)?;
+    LIQUIDATION_CONFIG.save(deps.storage, &msg.liquidation)?;
+    FEE_CONFIG.save(deps.storage, &fees)?;
+    ADMIN.set(deps.branch(), Some(admin_addr))?;
+    ORACLE.save(deps.storage, &oracle_addr)?;
 
     // Initialize asset and collateral configurations
     let asset_config = CoinConfig {
-        denom: msg.config.asset,
+        denom: config.asset,
         decimals: 6,  
     };
     ASSET_CONFIG.save(deps.storage, &asset_config)?;
 
     let collateral_config = CoinConfig {
-        denom: msg.config.collateral,
+        denom: config.collateral,
         decimals: 6,  
     };
     COLLATERAL_CONFIG.save(deps.storage, &collateral_config)?;
 
     TOTAL_ASSET_AVAILABLE.save(deps.storage, &Uint128::zero())?;
     TOTAL_COLLATERAL_AVAILABLE.save(deps.storage, &Uint128::zero())?;
+    TOTAL_PROTOCOL_EARNINGS.save(deps.storage, &Uint128::zero())?;
+    POOL_STATE.save(deps.storage, &PoolState::new(env.block.time.seconds(), config.debtinterestrate))?;
+
+    // the receipt token is named after the pool and only the pool mints it,
+    // its address is stored once it's instantiated, in `reply`
+    let mut response = Response::default();
+    if let Some(code_id) = msg.receipt_token_code_id {
+        let token_msg = cw20_base::msg::InstantiateMsg {
+            name: config.name.clone(),
+            symbol: config.symbol.clone(),
+            decimals: u8::try_from(asset_config.decimals).map_err(|_| ContractError::Overflow {})?,
+            initial_balances: vec![],
+            mint: Some(MinterResponse { minter: env.contract.address.to_string(), cap: None }),
+            marketing: None,
+        };
+        let instantiate_msg = WasmMsg::Instantiate {
+            admin: Some(msg.admin),
+            code_id,
+            msg: to_json_binary(&token_msg)?,
+            funds: vec![],
+            label: format!("{} receipt", config.name),
+        };
+        response = response.add_submessage(SubMsg::reply_on_success(instantiate_msg, RECEIPT_TOKEN_REPLY_ID));
+    }
+
+    Ok(response)
+}
+
+// Docs:
+// Stores the address of the receipt token instantiated along with the pool
+#[entry_point]
+pub fn reply(
+    deps: DepsMut,
+    _env: Env,
+    msg: Reply,
+) -> ContractResult<Response> {
+    match msg.id {
+        RECEIPT_TOKEN_REPLY_ID => {
+            let res = parse_reply_instantiate_data(msg)?;
+            let receipt_token = deps.api.addr_validate(&res.contract_address)?;
+            RECEIPT_TOKEN.save(deps.storage, &receipt_token)?;
+
+            Ok(Response::new()
+            .add_attribute("action", "receipt_token")
+            .add_attribute("receipt_token", receipt_token))
+        },
+        ROLLOVER_REPLY_ID => {
+            // the successor pool has paid the asset back with RepayRollover by now
+            let rollover = PENDING_ROLLOVER.load(deps.storage)?;
+            PENDING_ROLLOVER.remove(deps.storage);
+
+            let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+            let received = rollover.repaid;
+            if received < rollover.amount_due {
+                return Err(ContractError::InsufficientFunds {});
+            }
+
+            let (queue_paid, queue_msgs) = pay_queued_withdrawals(deps.storage, &asset_config)?;
+
+            Ok(Response::new()
+            .add_attribute("action", "rollover_repaid")
+            .add_attribute("loan_id", rollover.loan_id.to_string())
+            .add_attribute("target_pool", rollover.target_pool)
+            .add_attribute("queue_paid", queue_paid)
+            .add_submessages(payout_msgs(&asset_config, &rollover.holder, received.checked_sub(rollover.amount_due)?)?)
+            .add_submessages(queue_msgs))
+        },
+        id => Err(ContractError::UnknownReply { id }),
+    }
+}
+
+// Docs:
+// Denoms are either a native denom or `cw20:<address>`,
+// the cw20 address is validated and stored in its canonical form
+fn validate_denom(api: &dyn Api, denom: &str) -> ContractResult<String> {
+    match denom.strip_prefix("cw20:") {
+        Some(address) => Ok(format!("cw20:{}", api.addr_validate(address)?)),
+        None if denom.is_empty() => Err(ContractError::InvalidFunds { denom: denom.to_string() }),
+        None => Ok(denom.to_string()),
+    }
+}
+
+// Docs:
+// Rates are per year and capped at 100%, lenders can't be promised more than borrowers pay,
+// loans are at least fully collateralized and the lock in is a share of the time to maturity
+fn validate_pool_config(config: &PoolConfig) -> ContractResult<()> {
+    if config.debtinterestrate > Decimal::one() {
+        return Err(ContractError::InvalidConfig { reason: "debt interest rate must be at most 1".to_string() });
+    }
+    if config.lendinterestrate > config.debtinterestrate {
+        return Err(ContractError::InvalidConfig { reason: "lend interest rate above debt interest rate".to_string() });
+    }
+    if config.overcollateralizationfactor < Decimal::one() {
+        return Err(ContractError::InsufficientOCF {});
+    }
+    if config.strikeprice.is_zero() {
+        return Err(ContractError::InvalidConfig { reason: "strike price must be above 0".to_string() });
+    }
+    if config.lock_in_period > Uint128::new(100) {
+        return Err(ContractError::InvalidConfig { reason: "lock in period must be at most 100".to_string() });
+    }
+    Ok(())
+}
+
+// Docs:
+// A position has to be liquidatable before it is underwater, and the keeper's bonus
+// has to be paid out of the collateral left above the debt
+pub fn validate_liquidation_config(config: &LiquidationConfig) -> ContractResult<()> {
+    if config.threshold < Decimal::one() {
+        return Err(ContractError::InvalidConfig { reason: "liquidation threshold must be at least 1".to_string() });
+    }
+    if config.bonus >= Decimal::one() {
+        return Err(ContractError::InvalidConfig { reason: "liquidation bonus must be below 1".to_string() });
+    }
+    if config.close_factor.is_zero() || config.close_factor > Decimal::one() {
+        return Err(ContractError::InvalidConfig { reason: "close factor must be above 0 and at most 1".to_string() });
+    }
+    Ok(())
+}
+
+// Docs:
+// The reserve factor comes out of the spread, what is left of the debt interest rate
+// still covers the lend interest rate, and a loan can't be eaten up by its fee
+fn validate_fee_config(fees: &FeeConfig, pool_config: &PoolConfig) -> ContractResult<()> {
+    if fees.reserve_factor > Decimal::one() {
+        return Err(ContractError::InvalidConfig { reason: "reserve factor must be at most 1".to_string() });
+    }
+    if fees.origination_fee >= Decimal::one() {
+        return Err(ContractError::InvalidConfig { reason: "origination fee must be below 1".to_string() });
+    }
+    let lenders_share = pool_config.debtinterestrate.checked_mul(Decimal::one() - fees.reserve_factor)?;
+    if lenders_share < pool_config.lendinterestrate {
+        return Err(ContractError::InvalidConfig { reason: "reserve factor above the interest rate spread".to_string() });
+    }
+    Ok(())
+}
+
+// Pools instantiated before fees existed charge none
+fn fee_config(storage: &dyn Storage) -> ContractResult<FeeConfig> {
+    Ok(FEE_CONFIG.may_load(storage)?.unwrap_or_default())
+}
+
+// Holds `amount` of the asset in the pool for the protocol
+fn book_protocol_earnings(storage: &mut dyn Storage, amount: Uint128) -> ContractResult<()> {
+    let earnings = TOTAL_PROTOCOL_EARNINGS.may_load(storage)?.unwrap_or_default();
+    TOTAL_PROTOCOL_EARNINGS.save(storage, &earnings.checked_add(amount)?)?;
+    Ok(())
+}
+
+// Docs:
+// Upgrades a deployed pool to this code, converting its storage along the way.
+// Only pools of this contract can migrate, and never to an older version.
+// Pools deployed before cw2 versions were recorded count as the legacy version.
+#[entry_point]
+pub fn migrate(
+    mut deps: DepsMut,
+    env: Env,
+    msg: MigrateMsg,
+) -> ContractResult<Response> {
+    let stored = get_contract_version(deps.storage).unwrap_or_else(|_| ContractVersion {
+        contract: CONTRACT_NAME.to_string(),
+        version: LEGACY_VERSION.to_string(),
+    });
 
-    Ok(Response::default())
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidMigration { contract: stored.contract, version: stored.version });
+    }
+
+    let from = Version::parse(&stored.version)?;
+    let to = Version::parse(CONTRACT_VERSION)?;
+    if from > to {
+        return Err(ContractError::InvalidMigration { contract: stored.contract, version: stored.version });
+    }
+
+    let applied = run_migrations(deps.branch(), &env, &from, &to, &msg)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "migrate")
+    .add_attribute("from_version", from.to_string())
+    .add_attribute("to_version", to.to_string())
+    .add_attribute("migrations", applied.join(",")))
 }
 
 #[entry_point]
@@ -50,30 +268,349 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> ContractResult<Response> {
+    assert_no_pending_rollover(deps.storage, &msg)?;
+
     match msg {
-        ExecuteMsg::Transact(transact_msg) => match transact_msg {
-            TransactMsg::Receive(msg) => execute_receive(deps, env, info, msg),
+        ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, msg),
 
+        ExecuteMsg::Transact(transact_msg) => match transact_msg {
             TransactMsg::Deposit (msg) => execute_deposit(deps, env, info, msg),
             TransactMsg::Withdraw (msg) => execute_withdraw(deps, env, info, msg),
             TransactMsg::WithdrawInterest {} => execute_withdraw_interest(deps, env, info),
             TransactMsg::Loan (msg) => execute_loan(deps, env, info, msg),
             TransactMsg::Repay (msg) => execute_repay(deps, env, info, msg),
+            TransactMsg::RepayAmount { loan_id, amount } => execute_repay_amount(deps, env, info, loan_id, amount),
+            TransactMsg::Settle { limit } => execute_settle(deps, env, info, limit),
+            TransactMsg::Redeem {} => execute_redeem(deps, env, info),
+            TransactMsg::Liquidate { loan_id, repay_amount } => execute_liquidate(deps, env, info, loan_id, repay_amount),
+            TransactMsg::ProcessQueue { limit } => execute_process_queue(deps, info, limit),
+            TransactMsg::TopUpCollateral { loan_id, amount } => execute_top_up_collateral(deps, env, info, loan_id, amount),
+            TransactMsg::WithdrawCollateral { loan_id, amount } => execute_withdraw_collateral(deps, env, info, loan_id, amount),
+            TransactMsg::Rollover { loan_id, target_pool } => execute_rollover(deps, env, info, loan_id, target_pool),
+            TransactMsg::SyncLender { lender } => execute_sync_lender(deps, env, info, lender),
+            TransactMsg::AllowRollover { from_pool } => execute_allow_rollover(deps, info, from_pool),
+            TransactMsg::AcceptRollover { borrower, asset_amount } => execute_accept_rollover(deps, env, info, borrower, asset_amount),
+            TransactMsg::RepayRollover {} => execute_repay_rollover(deps, info),
+            TransactMsg::AcceptLoan { loan_id } => execute_accept_loan(deps, info, loan_id),
+        },
+
+        ExecuteMsg::Admin(admin_msg) => match admin_msg {
+            AdminMsg::UpdateConfig (msg) => execute_update_config(deps, env, info, *msg),
+            AdminMsg::ProposeAdmin { admin } => execute_propose_admin(deps, info, admin),
+            AdminMsg::AcceptAdmin {} => execute_accept_admin(deps, info),
+            AdminMsg::SetGuardian { guardian } => execute_set_guardian(deps, info, guardian),
+            AdminMsg::UpdatePause { operations, paused } => execute_update_pause(deps, info, operations, paused),
+            AdminMsg::CollectProtocolFees { recipient } => execute_collect_protocol_fees(deps, info, recipient),
         },
+
+        ExecuteMsg::TransferNft { recipient, token_id } => execute_transfer_loan(deps, info, recipient, token_id, None),
+        ExecuteMsg::SendNft { contract, token_id, msg } => execute_transfer_loan(deps, info, contract, token_id, Some(msg)),
     }
 }
 
 // Docs :
-// This function is used to receive the mesaage that the funds have been transferred
-// This function will not accept any coins, only a Cw20ReceiveMsg
-// This function does nothing else
+// This function is used to receive the message that cw20 funds have been sent to the pool
+// info.sender is the token contract, wrapper.sender is the user who sent the funds
+// and wrapper.msg is the ReceiveMsg telling us what the funds are for.
+// The tokens are already held by the contract, so there's no TransferFrom here,
+// the funds are routed into the same accounting as the allowance based handlers.
 fn execute_receive(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    msg: Cw20ReceiveMsg,
+    wrapper: Cw20ReceiveMsg,
+) -> ContractResult<Response> {
+    // This function will not accept any coins, only a Cw20ReceiveMsg
+    nonpayable(&info)?;
+
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    let token = info.sender;
+    let amount = wrapper.amount;
+
+    match from_json(&wrapper.msg)? {
+        ReceiveMsg::Deposit {} => receive_deposit(deps, env, token, sender, amount),
+        ReceiveMsg::Repay { loan_id, asset_principle } => receive_repay(deps, env, token, sender, amount, loan_id, asset_principle),
+        ReceiveMsg::RepayAmount { loan_id } => receive_repay_amount(deps, env, token, sender, amount, loan_id),
+        ReceiveMsg::PostCollateral { asset_amount } => receive_post_collateral(deps, env, token, sender, amount, asset_amount),
+        ReceiveMsg::Liquidate { loan_id } => receive_liquidate(deps, env, token, sender, amount, loan_id),
+        ReceiveMsg::TopUpCollateral { loan_id } => receive_top_up_collateral(deps, env, token, sender, amount, loan_id),
+        ReceiveMsg::AcceptRollover { borrower, asset_amount } => receive_accept_rollover(deps, env, token, sender, amount, borrower, asset_amount),
+        ReceiveMsg::RepayRollover {} => receive_repay_rollover(deps, token, sender, amount),
+    }
+}
+
+// Docs:
+// While a rollover waits for the successor pool to pay the debt back, within the rollover's transaction,
+// the repayment is the only message the pool takes so nothing else can move its books or its balance in between
+fn assert_no_pending_rollover(storage: &dyn Storage, msg: &ExecuteMsg) -> ContractResult<()> {
+    if PENDING_ROLLOVER.may_load(storage)?.is_none() {
+        return Ok(());
+    }
+    let is_repayment = match msg {
+        ExecuteMsg::Transact(TransactMsg::RepayRollover {}) => true,
+        ExecuteMsg::Receive(wrapper) => matches!(from_json(&wrapper.msg), Ok(ReceiveMsg::RepayRollover {})),
+        _ => false,
+    };
+    if !is_repayment {
+        return Err(ContractError::RolloverPending {});
+    }
+    Ok(())
+}
+
+fn receive_deposit(
+    deps: DepsMut,
+    env: Env,
+    token: Addr,
+    sender: Addr,
+    amount: Uint128,
+) -> ContractResult<Response> {
+    assert_not_paused(deps.storage, Operation::Deposit)?;
+
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    if asset_config.cw20_address() != Some(token) {
+        return Err(ContractError::InvalidAsset {});
+    }
+
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let receipt_msgs = book_deposit(deps.storage, &pool_config, env.block.time.seconds(), &sender, amount)?;
+    let (queue_paid, queue_msgs) = pay_queued_withdrawals(deps.storage, &asset_config)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "deposit")
+    .add_attribute("queue_paid", queue_paid)
+    .add_submessages(receipt_msgs)
+    .add_submessages(queue_msgs))
+}
+
+fn receive_repay(
+    deps: DepsMut,
+    env: Env,
+    token: Addr,
+    sender: Addr,
+    amount: Uint128,
+    loan_id: u64,
+    asset_principle: Uint128,
+) -> ContractResult<Response> {
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    if asset_config.cw20_address() != Some(token) {
+        return Err(ContractError::InvalidAsset {});
+    }
+
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let (amount_due, collateral_to_unlock) = book_repay(deps.storage, &pool_config, env.block.time.seconds(), &sender, loan_id, asset_principle)?;
+
+    if amount < amount_due {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+    let (queue_paid, queue_msgs) = pay_queued_withdrawals(deps.storage, &asset_config)?;
+
+    // Interest keeps accruing every block, so the user can't know the exact amount due
+    // when sending, whatever is sent above it goes back
+    Ok(Response::new()
+    .add_attribute("action", "repay")
+    .add_attribute("loan_id", loan_id.to_string())
+    .add_attribute("queue_paid", queue_paid)
+    .add_submessages(payout_msgs(&collateral_config, &sender, collateral_to_unlock)?)
+    .add_submessages(payout_msgs(&asset_config, &sender, amount.checked_sub(amount_due)?)?)
+    .add_submessages(queue_msgs))
+}
+
+fn receive_repay_amount(
+    deps: DepsMut,
+    env: Env,
+    token: Addr,
+    sender: Addr,
+    amount: Uint128,
+    loan_id: u64,
+) -> ContractResult<Response> {
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    if asset_config.cw20_address() != Some(token) {
+        return Err(ContractError::InvalidAsset {});
+    }
+
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let repayment = book_repay_amount(deps.storage, &pool_config, env.block.time.seconds(), &sender, loan_id, amount)?;
+
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+    let (queue_paid, queue_msgs) = pay_queued_withdrawals(deps.storage, &asset_config)?;
+
+    Ok(repayment.response(loan_id)?
+    .add_attribute("queue_paid", queue_paid)
+    .add_submessages(payout_msgs(&collateral_config, &sender, repayment.collateral)?)
+    .add_submessages(payout_msgs(&asset_config, &sender, amount.checked_sub(repayment.paid()?)?)?)
+    .add_submessages(queue_msgs))
+}
+
+fn receive_liquidate(
+    mut deps: DepsMut,
+    env: Env,
+    token: Addr,
+    sender: Addr,
+    amount: Uint128,
+    loan_id: u64,
+) -> ContractResult<Response> {
+    assert_not_paused(deps.storage, Operation::Liquidate)?;
+
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    if asset_config.cw20_address() != Some(token) {
+        return Err(ContractError::InvalidAsset {});
+    }
+
+    let liquidation = book_liquidation(deps.branch(), &env, loan_id, amount)?;
+
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+    let (queue_paid, queue_msgs) = pay_queued_withdrawals(deps.storage, &asset_config)?;
+
+    Ok(liquidation.response(loan_id, &collateral_config, &sender)?
+    .add_attribute("queue_paid", queue_paid)
+    .add_submessages(payout_msgs(&asset_config, &sender, amount.checked_sub(liquidation.repaid)?)?)
+    .add_submessages(queue_msgs))
+}
+
+fn receive_post_collateral(
+    deps: DepsMut,
+    env: Env,
+    token: Addr,
+    sender: Addr,
+    amount: Uint128,
+    asset_amount: Uint128,
+) -> ContractResult<Response> {
+    assert_not_paused(deps.storage, Operation::Loan)?;
+
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+    if collateral_config.cw20_address() != Some(token) {
+        return Err(ContractError::InvalidCollateral {});
+    }
+
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let collateral_needed = calculate_collateral_amount(asset_amount, pool_config.strikeprice, pool_config.overcollateralizationfactor)?;
+    if amount < collateral_needed {
+        return Err(ContractError::InsufficientCollateral {});
+    }
+
+    let (loan_id, fee) = book_loan(deps.storage, &pool_config, env.block.time.seconds(), &sender, asset_amount, collateral_needed)?;
+    assert_healthy(deps.as_ref(), &env, loan_id)?;
+
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "loan")
+    .add_attribute("loan_id", loan_id.to_string())
+    .add_attribute("fee", fee)
+    .add_submessages(payout_msgs(&asset_config, &sender, asset_amount.checked_sub(fee)?)?)
+    .add_submessages(payout_msgs(&collateral_config, &sender, amount.checked_sub(collateral_needed)?)?))
+}
+
+fn receive_top_up_collateral(
+    deps: DepsMut,
+    env: Env,
+    token: Addr,
+    sender: Addr,
+    amount: Uint128,
+    loan_id: u64,
 ) -> ContractResult<Response> {
-    Ok(Response::default())
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+    if collateral_config.cw20_address() != Some(token) {
+        return Err(ContractError::InvalidCollateral {});
+    }
+
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    book_top_up_collateral(deps.storage, &pool_config, env.block.time.seconds(), &sender, loan_id, amount)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "top_up_collateral")
+    .add_attribute("loan_id", loan_id.to_string())
+    .add_attribute("amount", amount))
+}
+
+fn receive_accept_rollover(
+    deps: DepsMut,
+    env: Env,
+    token: Addr,
+    sender: Addr,
+    amount: Uint128,
+    borrower: String,
+    asset_amount: Uint128,
+) -> ContractResult<Response> {
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+    if collateral_config.cw20_address() != Some(token) {
+        return Err(ContractError::InvalidCollateral {});
+    }
+
+    accept_rollover(deps, env, sender, borrower, asset_amount, amount)
+}
+
+fn receive_repay_rollover(
+    deps: DepsMut,
+    token: Addr,
+    sender: Addr,
+    amount: Uint128,
+) -> ContractResult<Response> {
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    if asset_config.cw20_address() != Some(token) {
+        return Err(ContractError::InvalidAsset {});
+    }
+
+    book_rollover_repayment(deps.storage, &sender, amount)
+}
+
+// Docs:
+// These helpers are the only place funds move in and out of the pool,
+// every coin goes through Amount so native and cw20 coins are handled alike
+
+// Pulls `amount` of `coin` from the sender into the pool.
+// Native coins have to be attached to the message and anything attached above `amount` is refunded,
+// cw20 tokens need an allowance for the contract and are pulled with TransferFrom.
+fn collect_funds(
+    deps: Deps,
+    env: &Env,
+    info: &MessageInfo,
+    coin: &CoinConfig,
+    amount: Uint128,
+) -> ContractResult<Vec<SubMsg>> {
+    match coin.cw20_address() {
+        None => {
+            let paid = must_pay(info, &coin.denom)?;
+            if paid < amount {
+                return Err(ContractError::InsufficientFunds {});
+            }
+            payout_msgs(coin, &info.sender, paid.checked_sub(amount)?)
+        }
+        Some(cw20_addr) => {
+            nonpayable(info)?;
+
+            // check with the cw20 if user has given allowance to this contract to transfer funds
+            let allowance_and_expiry: AllowanceResponse = fetch_allowance(
+                deps,
+                env.contract.address.clone(),
+                info.sender.clone(),
+                cw20_addr,
+            )?;
+
+            // if the allowance is lesser than the amount to transfer,
+            // or the allowance has expired, then return an error
+            if allowance_and_expiry.allowance < amount {
+                return Err(ContractError::InsufficientAllowance {});
+            }
+            else if allowance_and_expiry.expires.is_expired(&env.block) {
+                return Err(ContractError::AllowanceExpired {});
+            }
+
+            let transfer_msg = coin.amount(amount).transfer_from_msg(&info.sender, &env.contract.address)?;
+            Ok(vec![SubMsg::new(transfer_msg)])
+        }
+    }
+}
+
+// Sends `amount` of `coin` from the pool to `recipient`,
+// nothing is sent for a zero amount since neither bank nor cw20 accept empty transfers
+fn payout_msgs(coin: &CoinConfig, recipient: &Addr, amount: Uint128) -> ContractResult<Vec<SubMsg>> {
+    if amount.is_zero() {
+        return Ok(vec![]);
+    }
+    Ok(vec![SubMsg::new(coin.amount(amount).transfer_msg(recipient)?)])
 }
 
 // Docs: 
@@ -101,129 +638,561 @@ pub fn fetch_allowance(
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> ContractResult<Binary> {
     match msg {
         QueryMsg::AllDetails {} => {
-            let pool_config = POOL_CONFIG.load(deps.storage)?;
-            let admin = ADMIN.load(deps.storage)?;
-            let total_asset_available = TOTAL_ASSET_AVAILABLE.load(deps.storage)?;
-            let total_collateral_available = TOTAL_COLLATERAL_AVAILABLE.load(deps.storage)?;
-            let all_details = (
-                pool_config, admin, total_asset_available,
-                total_collateral_available,
-            );
-           
+            let all_details = PoolDetailsResponse {
+                config: POOL_CONFIG.load(deps.storage)?,
+                admin: ADMIN.get(deps)?,
+                total_asset_available: TOTAL_ASSET_AVAILABLE.load(deps.storage)?,
+                total_collateral_available: TOTAL_COLLATERAL_AVAILABLE.load(deps.storage)?,
+                fees: fee_config(deps.storage)?,
+                total_protocol_earnings: TOTAL_PROTOCOL_EARNINGS.may_load(deps.storage)?.unwrap_or_default(),
+                receipt_token: RECEIPT_TOKEN.may_load(deps.storage)?,
+            };
+
             Ok(to_json_binary(&all_details)?)
         },
 
         QueryMsg::UserDetails {user} => {
             let user_clone = user.clone();
-            let withdrawable_positions = get_withdrawable_positions(deps, user_clone)?;
+            let withdrawable_positions = get_withdrawable_positions(deps, _env, user_clone)?;
             let repayable_positions = get_repayable_positions(deps, user)?;
-            let all_details = (
-                withdrawable_positions,
-                repayable_positions
-            );
+            let all_details = UserDetailsResponse {
+                lender: withdrawable_positions,
+                borrower: repayable_positions,
+            };
             Ok(to_json_binary(&all_details)?)
         }
 
         QueryMsg::GetDepositQuote { user, amount } => {
-            let quote = quote_deposit(deps, user,  amount)?;
+            let quote = quote_deposit(deps, _env, user,  amount)?;
             Ok(to_json_binary(&quote)?)
         },
 
-        QueryMsg::GetRepayQuote{ user} => {
-            let quote = quote_repay(deps, _env, user)?;
+        QueryMsg::GetRepayQuote { loan_id } => {
+            let quote = quote_repay(deps, _env, loan_id)?;
             Ok(to_json_binary(&quote)?)
         },
 
         QueryMsg::GetLoanQuote { amount} => {
-            let quote: (Uint128, Uint128, Uint128) = quote_loan(deps, _env, amount)?;
+            let quote = quote_loan(deps, _env, amount)?;
             Ok(to_json_binary(&quote)?)
         },
 
         QueryMsg::GetWithdrawableAndRepayablePositions { user,} => {
             let user_clone = user.clone();
-            let quote_withdraw = get_withdrawable_positions(deps, user_clone)?;
+            let quote_withdraw = get_withdrawable_positions(deps, _env, user_clone)?;
             let quote_repay = get_repayable_positions(deps, user)?;
-            Ok(to_json_binary(&(quote_withdraw, quote_repay))?)
+            Ok(to_json_binary(&UserDetailsResponse { lender: quote_withdraw, borrower: quote_repay })?)
         },
-    }
-}
 
-fn quote_repay(    
-    deps: Deps,
-    env: Env,
-    user: Addr,
-) -> ContractResult<(Uint128, Uint128, Uint128)> {
-    // This nonpayable function ensures that no coins are sent to the contract
+        QueryMsg::Settlement {} => {
+            let settlement = SETTLEMENT.may_load(deps.storage)?;
+            Ok(to_json_binary(&settlement)?)
+        },
 
-    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
-    let now = env.block.time.seconds();
+        QueryMsg::HealthFactor { loan_id } => {
+            let health = health_factor(deps, _env, loan_id)?;
+            Ok(to_json_binary(&health)?)
+        },
 
-    if now > pool_config.maturationdate {
-        return Err(ContractError::CollateralForfeited {});
-    }
+        QueryMsg::Balance { address } => {
+            let address = deps.api.addr_validate(&address)?;
+            let balance = debt_balance(deps, _env, address)?;
+            Ok(to_json_binary(&BalanceResponse { balance })?)
+        },
 
-    if pool_config.overcollateralizationfactor < Uint128::new(1) {
-        return Err(ContractError::InsufficientOCF {});
-    }
- 
-    let (overall_collateral_submitted_by_user, last_collateral_time) = COLLATERAL_SUBMITTED.may_load(deps.storage, &user)?.unwrap_or((Uint128::zero(), Timestamp::from_seconds(0)));
-    let (overall_principle_to_repay_by_user, last_principle_time) = PRINCIPLE_TO_REPAY.may_load(deps.storage, &user)?.unwrap_or((Uint128::zero(), Timestamp::from_seconds(0)));
-    let interest_to_repay_by_user_yet = INTEREST_TO_REPAY.may_load(deps.storage, &user)?.unwrap_or(Uint128::zero());
+        QueryMsg::TokenInfo {} => {
+            let token_info = debt_token_info(deps, _env)?;
+            Ok(to_json_binary(&token_info)?)
+        },
 
-    // TODO: what if somehow - (define) some collateral is left
-    if interest_to_repay_by_user_yet == Uint128::zero() && overall_principle_to_repay_by_user == Uint128::zero() {
-        return Err(ContractError::PositionNotAvailable {});
-    }
+        QueryMsg::OwnerOf { token_id, .. } => {
+            let loan = load_loan(deps.storage, parse_loan_id(&token_id)?)?;
+            Ok(to_json_binary(&OwnerOfResponse { owner: loan.borrower.to_string(), approvals: vec![] })?)
+        },
 
-    if last_collateral_time != last_principle_time {
-        return Err(ContractError::InvalidState {});
-    }
+        QueryMsg::NftInfo { token_id } => {
+            let pool_config = POOL_CONFIG.load(deps.storage)?;
+            let pool_state = pool_state_at(deps.storage, &pool_config, _env.block.time.seconds())?;
+            let loan = load_loan(deps.storage, parse_loan_id(&token_id)?)?;
+            Ok(to_json_binary(&NftInfoResponse { token_uri: None, extension: loan_info(loan, &pool_state)? })?)
+        },
 
-    let current_time_period = get_time_period(Timestamp::from_seconds(now), last_principle_time);
-    let interest_on_current_principle = calculate_simple_interest(overall_principle_to_repay_by_user, pool_config.debtinterestrate, current_time_period);
+        QueryMsg::NumTokens {} => {
+            let count = loans().keys(deps.storage, None, None, Order::Ascending).count() as u64;
+            Ok(to_json_binary(&NumTokensResponse { count })?)
+        },
 
-    let total_interest_to_pay = interest_to_repay_by_user_yet + interest_on_current_principle;
-    
-    let ret = (overall_principle_to_repay_by_user, total_interest_to_pay, overall_collateral_submitted_by_user);
+        QueryMsg::Tokens { owner, start_after, limit } => {
+            let owner = deps.api.addr_validate(&owner)?;
+            let tokens = loan_tokens(deps, _env, Some(owner), start_after, limit)?;
+            Ok(to_json_binary(&tokens)?)
+        },
 
-    Ok(ret)
+        QueryMsg::AllTokens { start_after, limit } => {
+            let tokens = loan_tokens(deps, _env, None, start_after, limit)?;
+            Ok(to_json_binary(&tokens)?)
+        },
 
-}
-  
-// fn quoteDeosit() 
-// This functions is used to calculate the 
-// amount of interest the user will earn till maturity
-// 1) given the value they have currently entered
-// 2) given the total position that they have in the pool
+        QueryMsg::ContractInfo {} => {
+            let pool_config = POOL_CONFIG.load(deps.storage)?;
+            Ok(to_json_binary(&NftContractInfoResponse {
+                name: format!("{} loans", pool_config.name),
+                symbol: format!("l{}", pool_config.symbol),
+            })?)
+        },
 
-fn quote_deposit(
-    deps: Deps,
-    user: Addr,
-    amount: Uint128,
-  ) -> ContractResult<( (Uint128, Uint128),  (Uint128, Uint128))> {
-    let pool_config = POOL_CONFIG.load(deps.storage)?;
-    let asset_config = ASSET_CONFIG.load(deps.storage)?;
-  
-    let interest_earned_by_user = INTEREST_EARNED.may_load(deps.storage, &user)?.unwrap_or(Uint128::zero());
-  
-    let (principle_already_deployed, last_deposit_time) = PRINCIPLE_DEPLOYED.may_load(deps.storage, &user)?.unwrap_or((Uint128::zero(), Timestamp::from_seconds(0)));
-  
-    let time_period = get_time_period(Timestamp::from_seconds(pool_config.maturationdate), last_deposit_time);
-  
-  
-    // without the current position
-    // at maturity
-  
-    let interest = calculate_simple_interest(principle_already_deployed, pool_config.lendinterestrate, time_period);
-    let user_position_without_new_amount = (principle_already_deployed, interest_earned_by_user + interest);
-  
-    // with the current position
-    // at maturity
+        QueryMsg::PendingLoanTransfer { loan_id } => {
+            let recipient = PENDING_LOAN_TRANSFERS.may_load(deps.storage, loan_id)?;
+            Ok(to_json_binary(&PendingLoanTransferResponse { recipient })?)
+        },
+
+        QueryMsg::Loan { loan_id } => {
+            let pool_config = POOL_CONFIG.load(deps.storage)?;
+            let pool_state = pool_state_at(deps.storage, &pool_config, _env.block.time.seconds())?;
+            let loan = load_loan(deps.storage, loan_id)?;
+            Ok(to_json_binary(&loan_info(loan, &pool_state)?)?)
+        },
+
+        QueryMsg::Admin {} => {
+            let admin = ADMIN.get(deps)?;
+            let pending_admin = PENDING_ADMIN.may_load(deps.storage)?;
+            Ok(to_json_binary(&AdminResponse { admin, pending_admin })?)
+        },
+
+        QueryMsg::PoolState {} => {
+            let pool_state = query_pool_state(deps, _env)?;
+            Ok(to_json_binary(&pool_state)?)
+        },
+
+        QueryMsg::PauseState {} => {
+            let pause_state = PAUSE_STATE.may_load(deps.storage)?.unwrap_or_default();
+            let guardian = GUARDIAN.may_load(deps.storage)?;
+            Ok(to_json_binary(&PauseStateResponse { pause_state, guardian })?)
+        },
+
+        QueryMsg::RolloverSource { borrower } => {
+            let from_pool = ROLLOVER_SOURCES.may_load(deps.storage, &borrower)?;
+            Ok(to_json_binary(&RolloverSourceResponse { from_pool })?)
+        },
+
+        QueryMsg::Invariants {} => {
+            let invariants = query_invariants(deps, _env)?;
+            Ok(to_json_binary(&invariants)?)
+        },
+
+        QueryMsg::ListBorrowers { start_after, limit } => {
+            let borrowers = list_borrowers(deps, _env, start_after, limit)?;
+            Ok(to_json_binary(&borrowers)?)
+        },
+
+        QueryMsg::ListLenders { start_after, limit } => {
+            let lenders = list_lenders(deps, _env, start_after, limit)?;
+            Ok(to_json_binary(&lenders)?)
+        },
+
+        QueryMsg::ListLoans { start_after, limit } => {
+            let loans = list_loans(deps, _env, None, start_after, limit)?;
+            Ok(to_json_binary(&loans)?)
+        },
+
+        QueryMsg::LoansByBorrower { borrower, start_after, limit } => {
+            let loans = list_loans(deps, _env, Some(borrower), start_after, limit)?;
+            Ok(to_json_binary(&loans)?)
+        },
+
+        QueryMsg::ListDefaultedLoans { start_after, limit } => {
+            let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+            let loans = DEFAULTED_LOANS
+                .range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+                .take(limit)
+                .map(|item| {
+                    let (loan_id, loan) = item?;
+                    Ok(DefaultedLoanInfo { loan_id, borrower: loan.borrower, debt: loan.debt, collateral: loan.collateral })
+                })
+                .collect::<StdResult<Vec<_>>>()?;
+            Ok(to_json_binary(&ListDefaultedLoansResponse { loans })?)
+        },
+
+        QueryMsg::WithdrawalQueue { limit } => {
+            let queue = query_withdrawal_queue(deps, limit)?;
+            Ok(to_json_binary(&queue)?)
+        },
+    }
+}
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+// fn listLenders()
+// A page of lenders in address order, starting after `start_after`,
+// with the interest accrued on each position until now
+fn list_lenders(
+    deps: Deps,
+    env: Env,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> ContractResult<ListLendersResponse> {
+    let pool_config = POOL_CONFIG.load(deps.storage)?;
+    let pool_state = pool_state_at(deps.storage, &pool_config, env.block.time.seconds())?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let lenders = LENDERS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (lender, _) = item?;
+            let position = load_lender(deps.storage, &deps.querier, &pool_state, env.block.time.seconds(), &lender)?;
+            Ok(LenderInfo {
+                lender,
+                principal: position.principal,
+                interest: position.balance(&pool_state)?.saturating_sub(position.principal),
+                deposited_at: position.deposited_at,
+            })
+        })
+        .collect::<ContractResult<Vec<_>>>()?;
+
+    Ok(ListLendersResponse { lenders })
+}
+
+// fn listBorrowers()
+// A page of borrowers in the order of the borrower index, starting after `start_after`, each with all of their loans
+// added up and the interest owed on them until now. The loans are walked through the borrower index,
+// the page ends before the first loan of the borrower past the limit
+fn list_borrowers(
+    deps: Deps,
+    env: Env,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> ContractResult<ListBorrowersResponse> {
+    let pool_config = POOL_CONFIG.load(deps.storage)?;
+    let pool_state = pool_state_at(deps.storage, &pool_config, env.block.time.seconds())?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    // past every loan id of `start_after`
+    let start = start_after.map(|borrower| Bound::exclusive((borrower, u64::MAX)));
+
+    let mut borrowers: Vec<BorrowerInfo> = vec![];
+    for item in loans().idx.borrower.range(deps.storage, start, None, Order::Ascending) {
+        let (_, loan) = item?;
+        let interest = loan.debt(&pool_state)?.saturating_sub(loan.principal);
+        let page_full = borrowers.len() == limit;
+        match borrowers.last_mut() {
+            Some(info) if info.borrower == loan.borrower => {
+                info.principal = info.principal.checked_add(loan.principal)?;
+                info.interest = info.interest.checked_add(interest)?;
+                info.collateral = info.collateral.checked_add(loan.collateral)?;
+                info.loans += 1;
+            },
+            _ if page_full => break,
+            _ => borrowers.push(BorrowerInfo {
+                borrower: loan.borrower,
+                principal: loan.principal,
+                interest,
+                collateral: loan.collateral,
+                loans: 1,
+            }),
+        }
+    }
+
+    Ok(ListBorrowersResponse { borrowers })
+}
+
+// fn debtBalance()
+// What the borrower owes until now over all their loans, the balance of the debt token.
+// There is no debt token to mint or burn, loans add to the balance and repayments take from it
+fn debt_balance(
+    deps: Deps,
+    env: Env,
+    borrower: Addr,
+) -> ContractResult<Uint128> {
+    let pool_config = POOL_CONFIG.load(deps.storage)?;
+    let pool_state = pool_state_at(deps.storage, &pool_config, env.block.time.seconds())?;
+
+    let mut balance = Uint128::zero();
+    for item in loans().idx.borrower.prefix(borrower).range(deps.storage, None, None, Order::Ascending) {
+        let (_, loan) = item?;
+        balance = balance.checked_add(loan.debt(&pool_state)?)?;
+    }
+    Ok(balance)
+}
+
+fn debt_token_info(
+    deps: Deps,
+    env: Env,
+) -> ContractResult<TokenInfoResponse> {
+    let pool_config = POOL_CONFIG.load(deps.storage)?;
+    let asset_config = ASSET_CONFIG.load(deps.storage)?;
+    let pool_state = pool_state_at(deps.storage, &pool_config, env.block.time.seconds())?;
+
+    Ok(TokenInfoResponse {
+        name: format!("{} debt", pool_config.name),
+        symbol: format!("d{}", pool_config.symbol),
+        decimals: u8::try_from(asset_config.decimals).map_err(|_| ContractError::Overflow {})?,
+        total_supply: pool_state.total_debt()?,
+    })
+}
+
+// fn listLoans()
+// A page of open loans in id order, starting after `start_after`,
+// of one borrower through the borrower index or of everyone,
+// with the interest accrued on each loan until now
+fn list_loans(
+    deps: Deps,
+    env: Env,
+    borrower: Option<Addr>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> ContractResult<ListLoansResponse> {
+    let pool_config = POOL_CONFIG.load(deps.storage)?;
+    let pool_state = pool_state_at(deps.storage, &pool_config, env.block.time.seconds())?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let range = match borrower {
+        Some(borrower) => loans().idx.borrower.prefix(borrower).range(deps.storage, start, None, Order::Ascending),
+        None => loans().range(deps.storage, start, None, Order::Ascending),
+    };
+    let loans = range
+        .take(limit)
+        .map(|item| loan_info(item?.1, &pool_state))
+        .collect::<ContractResult<Vec<_>>>()?;
+
+    Ok(ListLoansResponse { loans })
+}
+
+// The ids of a page of loans as cw721 token ids
+fn loan_tokens(
+    deps: Deps,
+    env: Env,
+    owner: Option<Addr>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> ContractResult<TokensResponse> {
+    let start_after = start_after.as_deref().map(parse_loan_id).transpose()?;
+    let loans = list_loans(deps, env, owner, start_after, limit)?.loans;
+
+    Ok(TokensResponse { tokens: loans.iter().map(|loan| loan.id.to_string()).collect() })
+}
+
+// cw721 token ids are the loan ids
+fn parse_loan_id(token_id: &str) -> ContractResult<u64> {
+    token_id.parse().map_err(|_| ContractError::PositionNotAvailable {})
+}
+
+fn loan_info(loan: Loan, pool_state: &PoolState) -> ContractResult<LoanInfo> {
+    Ok(LoanInfo {
+        interest: loan.debt(pool_state)?.saturating_sub(loan.principal),
+        id: loan.id,
+        borrower: loan.borrower,
+        principal: loan.principal,
+        collateral: loan.collateral,
+        opened_at: loan.opened_at,
+        rate: loan.rate,
+    })
+}
+
+// fn queryWithdrawalQueue()
+// The withdrawals at the front of the queue, the next one to be paid first
+fn query_withdrawal_queue(
+    deps: Deps,
+    limit: Option<u32>,
+) -> ContractResult<WithdrawalQueueResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let withdrawals = WITHDRAWAL_QUEUE
+        .iter(deps.storage)?
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(WithdrawalQueueResponse {
+        total_queued: TOTAL_QUEUED_WITHDRAWALS.may_load(deps.storage)?.unwrap_or_default(),
+        length: WITHDRAWAL_QUEUE.len(deps.storage)?,
+        withdrawals,
+    })
+}
+
+// fn queryInvariants()
+// Checks the books of the pool: the available totals against the coins it holds,
+// the pool wide totals against the sums over every position,
+// and whether the borrowers' interest covers the lenders' by maturity
+fn query_invariants(
+    deps: Deps,
+    env: Env,
+) -> ContractResult<InvariantsResponse> {
+    let pool_config = POOL_CONFIG.load(deps.storage)?;
+    let asset_config = ASSET_CONFIG.load(deps.storage)?;
+    let collateral_config = COLLATERAL_CONFIG.load(deps.storage)?;
+    let pool_state = POOL_STATE.load(deps.storage)?;
+    let total_asset_available = TOTAL_ASSET_AVAILABLE.load(deps.storage)?;
+    let total_collateral_available = TOTAL_COLLATERAL_AVAILABLE.load(deps.storage)?;
+    let total_protocol_earnings = TOTAL_PROTOCOL_EARNINGS.may_load(deps.storage)?.unwrap_or_default();
+    let total_queued = TOTAL_QUEUED_WITHDRAWALS.may_load(deps.storage)?.unwrap_or_default();
+
+    let asset_balance = InvariantCheck {
+        recorded: total_asset_available.checked_add(total_protocol_earnings)?,
+        actual: asset_config.balance_of(&deps.querier, &env.contract.address)?,
+    };
+    let collateral_balance = InvariantCheck {
+        recorded: total_collateral_available,
+        actual: collateral_config.balance_of(&deps.querier, &env.contract.address)?,
+    };
+
+    let (mut supplied, mut scaled_supply) = (Uint128::zero(), Uint128::zero());
+    for item in LENDERS.range(deps.storage, None, None, Order::Ascending) {
+        let (_, position) = item?;
+        supplied = supplied.checked_add(position.principal)?;
+        scaled_supply = scaled_supply.checked_add(position.scaled)?;
+    }
+    // the shares of a receipt token are wherever the token went
+    if let Some(receipt_token) = RECEIPT_TOKEN.may_load(deps.storage)? {
+        let token_info: TokenInfoResponse = deps.querier.query_wasm_smart(receipt_token, &Cw20QueryMsg::TokenInfo {})?;
+        scaled_supply = token_info.total_supply;
+    }
+
+    let (mut borrowed, mut scaled_debt, mut collateral) = (Uint128::zero(), Uint128::zero(), Uint128::zero());
+    for item in loans().range(deps.storage, None, None, Order::Ascending) {
+        let (_, loan) = item?;
+        borrowed = borrowed.checked_add(loan.principal)?;
+        scaled_debt = scaled_debt.checked_add(loan.scaled_debt)?;
+        collateral = collateral.checked_add(loan.collateral)?;
+    }
+    // halfway through settlement the collateral of the loans defaulted so far is still held
+    for item in DEFAULTED_LOANS.range(deps.storage, None, None, Order::Ascending) {
+        collateral = collateral.checked_add(item?.1.collateral)?;
+    }
+
+    let mut queued = Uint128::zero();
+    for item in WITHDRAWAL_QUEUE.iter(deps.storage)? {
+        queued = queued.checked_add(item?.amount)?;
+    }
+
+    // once settled the collateral left is the forfeited collateral being redeemed
+    let borrower_collateral = InvariantCheck {
+        recorded: total_collateral_available,
+        actual: match SETTLEMENT.may_load(deps.storage)? {
+            Some(_) => total_collateral_available,
+            None => collateral,
+        },
+    };
+
+    // everything the pool has and is owed by maturity against everything it owes,
+    // the principal cancels out and what is left is the interest of both sides,
+    // the protocol takes its share of the interest still to be repaid and the queue is owed as it is
+    let at_maturity = accrue_interest(pool_state.clone(), &pool_config, pool_config.maturationdate)?;
+    let total_debt = at_maturity.total_debt()?;
+    let reserve = total_debt
+        .saturating_sub(at_maturity.total_borrowed)
+        .checked_mul_floor(fee_config(deps.storage)?.reserve_factor)?;
+    let assets = total_asset_available.checked_add(total_debt)?.checked_sub(reserve)?;
+    let obligations = at_maturity.total_supply()?.checked_add(total_queued)?;
+
+    let mut response = InvariantsResponse {
+        asset_balance,
+        collateral_balance,
+        total_supplied: InvariantCheck { recorded: pool_state.total_supplied, actual: supplied },
+        total_scaled_supply: InvariantCheck { recorded: pool_state.total_scaled_supply, actual: scaled_supply },
+        total_borrowed: InvariantCheck { recorded: pool_state.total_borrowed, actual: borrowed },
+        total_scaled_debt: InvariantCheck { recorded: pool_state.total_scaled_debt()?, actual: scaled_debt },
+        borrower_collateral,
+        queued_withdrawals: InvariantCheck { recorded: total_queued, actual: queued },
+        interest_surplus: assets.saturating_sub(obligations),
+        interest_shortfall: obligations.saturating_sub(assets),
+        discrepancies: vec![],
+    };
+
+    let checks = [
+        ("asset_balance", &response.asset_balance),
+        ("collateral_balance", &response.collateral_balance),
+        ("total_supplied", &response.total_supplied),
+        ("total_scaled_supply", &response.total_scaled_supply),
+        ("total_borrowed", &response.total_borrowed),
+        ("total_scaled_debt", &response.total_scaled_debt),
+        ("borrower_collateral", &response.borrower_collateral),
+        ("queued_withdrawals", &response.queued_withdrawals),
+    ];
+    response.discrepancies = checks
+        .iter()
+        .filter(|(_, check)| !check.holds())
+        .map(|(name, _)| name.to_string())
+        .collect();
+
+    Ok(response)
+}
+
+fn quote_repay(    
+    deps: Deps,
+    env: Env,
+    loan_id: u64,
+) -> ContractResult<RepayQuoteResponse> {
+    // This nonpayable function ensures that no coins are sent to the contract
+
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    if now > pool_config.maturationdate {
+        return Err(ContractError::CollateralForfeited {});
+    }
+
+    if pool_config.overcollateralizationfactor < Decimal::one() {
+        return Err(ContractError::InsufficientOCF {});
+    }
+ 
+    let pool_state = pool_state_at(deps.storage, &pool_config, now)?;
+    let loan = load_loan(deps.storage, loan_id)?;
+    let debt = loan.debt(&pool_state)?;
+
+    // TODO: what if somehow - (define) some collateral is left
+    if debt.is_zero() {
+        return Err(ContractError::PositionNotAvailable {});
+    }
+
+    Ok(RepayQuoteResponse {
+        principal: loan.principal,
+        interest: debt.saturating_sub(loan.principal),
+        collateral: loan.collateral,
+    })
+
+}
+  
+// fn quoteDeosit() 
+// This functions is used to calculate the 
+// amount of interest the user will earn till maturity
+// 1) given the value they have currently entered
+// 2) given the total position that they have in the pool
+
+fn quote_deposit(
+    deps: Deps,
+    env: Env,
+    user: Addr,
+    amount: Uint128,
+  ) -> ContractResult<DepositQuoteResponse> {
+    let pool_config = POOL_CONFIG.load(deps.storage)?;
+  
+    let pool_state = pool_state_at(deps.storage, &pool_config, env.block.time.seconds())?;
+    let at_maturity = accrue_interest(pool_state.clone(), &pool_config, pool_config.maturationdate)?;
+    let mut position = load_lender(deps.storage, &deps.querier, &pool_state, env.block.time.seconds(), &user)?;
+  
+    // without the current position
+    // at maturity
+  
+    let user_position_without_new_amount = LenderPositionResponse {
+      principal: position.principal,
+      interest: position.balance(&at_maturity)?.saturating_sub(position.principal),
+    };
   
-    let interest = calculate_simple_interest(principle_already_deployed + amount, pool_config.lendinterestrate, time_period);
-    let user_position_with_new_amount = (principle_already_deployed + amount, interest_earned_by_user + interest);
+    // with the current position
+    // at maturity
+  
+    position.principal = position.principal.checked_add(amount)?;
+    position.scaled = position.scaled.checked_add(amount.checked_div_floor(pool_state.supply_index)?)?;
+    let user_position_with_new_amount = LenderPositionResponse {
+      principal: position.principal,
+      interest: position.balance(&at_maturity)?.saturating_sub(position.principal),
+    };
   
-    Ok((user_position_without_new_amount, user_position_with_new_amount))
+    Ok(DepositQuoteResponse {
+      current: user_position_without_new_amount,
+      with_deposit: user_position_with_new_amount,
+    })
   
   }
   
@@ -239,7 +1208,7 @@ fn quote_deposit(
     deps: Deps,
     env: Env,
     amount: Uint128,
-  ) -> ContractResult<(Uint128, Uint128, Uint128)> {
+  ) -> ContractResult<LoanQuoteResponse> {
     let pool_config = POOL_CONFIG.load(deps.storage)?;
   
     // Don't quote if the pool has expired
@@ -248,16 +1217,95 @@ fn quote_deposit(
       return Err(ContractError::PoolMatured {});
     }
     // without the previous position
-    // at maturity
+    // at maturity, booked against the borrow index like the loan would be,
+    // accruals in between compound it a little more
     
-    let quotation_to_maturity_time_period = get_time_period(Timestamp::from_seconds(pool_config.maturationdate), Timestamp::from_seconds(now));
-    let interest = calculate_simple_interest(amount, pool_config.debtinterestrate, quotation_to_maturity_time_period);
-    let collateral_for_given_position = calculate_collateral_amount(amount, pool_config.strikeprice, pool_config.overcollateralizationfactor);
-    let user_position_for_new_amount = (amount, interest, collateral_for_given_position);
+    let pool_state = pool_state_at(deps.storage, &pool_config, now)?;
+    let at_maturity = accrue_interest(pool_state.clone(), &pool_config, pool_config.maturationdate)?;
+    let rate = pool_config.debtinterestrate;
+    let scaled_debt = amount.checked_div_ceil(pool_state.borrow_index(rate))?;
+    let interest = scaled_debt.checked_mul_ceil(at_maturity.borrow_index(rate))?.saturating_sub(amount);
+    let collateral_for_given_position = calculate_collateral_amount(amount, pool_config.strikeprice, pool_config.overcollateralizationfactor)?;
+    let user_position_for_new_amount = LoanQuoteResponse {
+      amount,
+      interest,
+      collateral: collateral_for_given_position,
+      fee: amount.checked_mul_floor(fee_config(deps.storage)?.origination_fee)?,
+    };
   
     Ok(user_position_for_new_amount)
   }
   
+  // fn healthFactor()
+  // This function is used to monitor a loan between origination and maturity
+  // it values the user's collateral at the oracle price of collateral in asset
+  // and compares it to the debt with the interest accrued till now
+  // returns the collateral value, the debt and collateral value / debt
+
+  fn health_factor(
+    deps: Deps,
+    env: Env,
+    loan_id: u64,
+  ) -> ContractResult<HealthFactorResponse> {
+    let loan = load_loan(deps.storage, loan_id)?;
+    let collateral_value = collateral_value(deps, &loan)?;
+    let debt = current_debt(deps.storage, &POOL_CONFIG.load(deps.storage)?, env.block.time.seconds(), &loan)?;
+
+    if debt.is_zero() {
+      return Err(ContractError::PositionNotAvailable {});
+    }
+
+    Ok(HealthFactorResponse {
+      collateral_value,
+      debt,
+      health_factor: Decimal::checked_from_ratio(collateral_value, debt)?,
+    })
+  }
+
+  // The loan's collateral valued in the asset at the current oracle price
+  fn collateral_value(
+    deps: Deps,
+    loan: &Loan,
+  ) -> ContractResult<Uint128> {
+    let oracle = ORACLE.load(deps.storage)?;
+    let asset_config = ASSET_CONFIG.load(deps.storage)?;
+    let collateral_config = COLLATERAL_CONFIG.load(deps.storage)?;
+
+    let price = query_price(&deps.querier, &oracle, &collateral_config.denom, &asset_config.denom)?;
+
+    Ok(loan.collateral.checked_mul_floor(price)?)
+  }
+
+  // Principal plus the interest accrued on it till now, interest stops accruing at maturity
+  fn current_debt(
+    storage: &dyn Storage,
+    pool_config: &PoolConfig,
+    now: u64,
+    loan: &Loan,
+  ) -> ContractResult<Uint128> {
+    let pool_state = pool_state_at(storage, pool_config, now)?;
+
+    Ok(loan.debt(&pool_state)?)
+  }
+
+  // An open loan, repaid and settled loans are gone
+  fn load_loan(
+    storage: &dyn Storage,
+    loan_id: u64,
+  ) -> ContractResult<Loan> {
+    loans().may_load(storage, loan_id)?.ok_or(ContractError::PositionNotAvailable {})
+  }
+
+  // Closes a loan, along with a transfer of it still waiting to be accepted
+  fn remove_loan(
+    storage: &mut dyn Storage,
+    loan_id: u64,
+  ) -> ContractResult<()> {
+    loans().remove(storage, loan_id)?;
+    PENDING_LOAN_TRANSFERS.remove(storage, loan_id);
+    Ok(())
+  }
+  
   // fn getWithdrawablePositions()
   // This function is used to calculate the total amount of
   // principal the user can withdraw from the pool
@@ -266,17 +1314,20 @@ fn quote_deposit(
   
   fn get_withdrawable_positions(
     deps: Deps,
+    env: Env,
     user: Addr,
-  ) -> ContractResult<(Uint128, Uint128)> {
+  ) -> ContractResult<LenderPositionResponse> {
     let pool_config = POOL_CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
   
-    let interest_earned_by_user = INTEREST_EARNED.may_load(deps.storage, &user)?.unwrap_or(Uint128::zero());
-    let (principle_already_deployed, last_deposit_time) = PRINCIPLE_DEPLOYED.may_load(deps.storage, &user)?.unwrap_or((Uint128::zero(), Timestamp::from_seconds(0)));
-  
-    let time_period = get_time_period(Timestamp::from_seconds(pool_config.maturationdate), last_deposit_time);
+    let pool_state = pool_state_at(deps.storage, &pool_config, now)?;
+    let at_maturity = pool_state_at(deps.storage, &pool_config, pool_config.maturationdate)?;
+    let position = load_lender(deps.storage, &deps.querier, &pool_state, now, &user)?;
   
-    let interest = calculate_simple_interest(principle_already_deployed, pool_config.lendinterestrate, time_period);
-    let user_position = (principle_already_deployed, interest_earned_by_user + interest);
+    let user_position = LenderPositionResponse {
+      principal: position.principal,
+      interest: position.balance(&at_maturity)?.saturating_sub(position.principal),
+    };
   
     Ok(user_position)
   }
@@ -291,34 +1342,111 @@ fn quote_deposit(
   fn get_repayable_positions(
     deps: Deps,
     user: Addr,
-  ) -> ContractResult<(Uint128, Uint128, Uint128)> {
+  ) -> ContractResult<BorrowerPositionResponse> {
     let pool_config = POOL_CONFIG.load(deps.storage)?;
   
-    let interest_to_repay_by_user = INTEREST_TO_REPAY.may_load(deps.storage, &user)?.unwrap_or(Uint128::zero());
-    let principle_to_repay_by_user = PRINCIPLE_TO_REPAY.may_load(deps.storage, &user)?.unwrap_or((Uint128::zero(), Timestamp::from_seconds(0)));
-    let collateral_submitted_by_user = COLLATERAL_SUBMITTED.may_load(deps.storage, &user)?.unwrap_or((Uint128::zero(), Timestamp::from_seconds(0)));
-  
-    let time_period = get_time_period(Timestamp::from_seconds(pool_config.maturationdate), principle_to_repay_by_user.1);
+    let at_maturity = pool_state_at(deps.storage, &pool_config, pool_config.maturationdate)?;
+
+    // every loan of the user added up
+    let (mut principal, mut debt, mut collateral) = (Uint128::zero(), Uint128::zero(), Uint128::zero());
+    for item in loans().idx.borrower.prefix(user).range(deps.storage, None, None, Order::Ascending) {
+      let (_, loan) = item?;
+      principal = principal.checked_add(loan.principal)?;
+      debt = debt.checked_add(loan.debt(&at_maturity)?)?;
+      collateral = collateral.checked_add(loan.collateral)?;
+    }
   
-    let interest = calculate_simple_interest(principle_to_repay_by_user.0, pool_config.debtinterestrate, time_period);
-    let user_position = (principle_to_repay_by_user.0, interest_to_repay_by_user + interest, collateral_submitted_by_user.0);
+    let user_position = BorrowerPositionResponse {
+      principal,
+      interest: debt.saturating_sub(principal),
+      collateral,
+    };
   
     Ok(user_position)
 }
 
 
-fn calculate_simple_interest(principal: Uint128, interest_rate: Uint128, time_period: u64) -> Uint128 {
-    principal * interest_rate * Uint128::from(time_period) / Uint128::from(NANOSECONDS_IN_YEAR)
+// rounded up so the loan is never short of collateral
+fn calculate_collateral_amount(borowing_amount: Uint128, strike_price: Decimal, overcollateralization_factor: Decimal) -> ContractResult<Uint128> {
+    Ok(borowing_amount.checked_mul_ceil(overcollateralization_factor)?.checked_div_ceil(strike_price)?)
+}
+
+fn get_time_period(now: Timestamp, time: Timestamp) -> ContractResult<u64> {
+    now.seconds().checked_sub(time.seconds()).ok_or(ContractError::Overflow {})
+}
+
+// Docs:
+// Grows every index by the interest accrued at its rate between the last accrual and `now`,
+// nothing accrues past maturity. The interest of each accrual is on the index as it was,
+// so the interest accrued before compounds: two accruals half a year apart at 10%
+// grow the index by 10.25% over the year, a single one by 10%.
+// There is a borrow index for the pool's debt rate, and one for every rate loans are still open at
+// after the admin changed it, dropped once the last of those loans is gone.
+fn accrue_interest(mut pool_state: PoolState, pool_config: &PoolConfig, now: u64) -> ContractResult<PoolState> {
+    pool_state.borrow_index_mut(pool_config.debtinterestrate);
+    pool_state.borrow_indices.retain(|borrow_index| {
+        borrow_index.rate == pool_config.debtinterestrate || !borrow_index.total_scaled_debt.is_zero()
+    });
+
+    let until = std::cmp::min(now, pool_config.maturationdate);
+    if until > pool_state.last_accrued {
+        let time_period = get_time_period(Timestamp::from_seconds(until), Timestamp::from_seconds(pool_state.last_accrued))?;
+        let supply_growth = Decimal::one().checked_add(interest_factor(pool_config.lendinterestrate, time_period)?)?;
+        pool_state.supply_index = pool_state.supply_index.checked_mul(supply_growth)?;
+        for borrow_index in pool_state.borrow_indices.iter_mut() {
+            let borrow_growth = Decimal::one().checked_add(interest_factor(borrow_index.rate, time_period)?)?;
+            borrow_index.index = borrow_index.index.checked_mul(borrow_growth)?;
+        }
+        pool_state.last_accrued = until;
+    }
+    Ok(pool_state)
+}
+
+// The interest on one unit of asset over `time_period` seconds at a yearly `interest_rate`
+fn interest_factor(interest_rate: Decimal, time_period: u64) -> ContractResult<Decimal> {
+    Ok(interest_rate.checked_mul(Decimal::checked_from_ratio(time_period, SECONDS_IN_YEAR)?)?)
+}
+
+// Accrues the interest until `now` into the pool state, once per block,
+// every handler moving balances starts with this so they are booked at the current indices
+fn accrue(storage: &mut dyn Storage, pool_config: &PoolConfig, now: u64) -> ContractResult<PoolState> {
+    let pool_state = accrue_interest(POOL_STATE.load(storage)?, pool_config, now)?;
+    POOL_STATE.save(storage, &pool_state)?;
+    Ok(pool_state)
 }
 
-fn calculate_collateral_amount(borowing_amount: Uint128, strike_price: Uint128, overcollateralization_factor: Uint128) -> Uint128 {
-    (borowing_amount * overcollateralization_factor) / strike_price
+// The pool state as it will be at `time` without storing anything, for queries and quotes
+fn pool_state_at(storage: &dyn Storage, pool_config: &PoolConfig, time: u64) -> ContractResult<PoolState> {
+    accrue_interest(POOL_STATE.load(storage)?, pool_config, time)
 }
 
-fn get_time_period(now: Timestamp, time: Timestamp) -> u64 {
-    now.seconds() - time.seconds()
+// fn queryPoolState()
+// Pool wide totals with the interest accrued until now
+fn query_pool_state(
+    deps: Deps,
+    env: Env,
+) -> ContractResult<PoolStateResponse> {
+    let pool_config = POOL_CONFIG.load(deps.storage)?;
+    let pool_state = pool_state_at(deps.storage, &pool_config, env.block.time.seconds())?;
+
+    let utilization = if pool_state.total_supplied.is_zero() {
+        Decimal::zero()
+    } else {
+        Decimal::checked_from_ratio(pool_state.total_borrowed, pool_state.total_supplied)?
+    };
+
+    Ok(PoolStateResponse {
+        supply_index: pool_state.supply_index,
+        borrow_index: pool_state.borrow_index(pool_config.debtinterestrate),
+        total_supplied: pool_state.total_supplied,
+        total_supply_interest: pool_state.total_supply()?.saturating_sub(pool_state.total_supplied),
+        total_borrowed: pool_state.total_borrowed,
+        total_debt_interest: pool_state.total_debt()?.saturating_sub(pool_state.total_borrowed),
+        utilization,
+    })
 }
 
+
 // Docs: COMPLETED
 // This function is used to make a deposit from the user's account to th
 // contract account and after that it implements the logic of the deposit
@@ -328,10 +1456,9 @@ fn execute_deposit(
     info: MessageInfo,
     msg: DepositMsg,
 ) -> Result<Response, ContractError> {
-    // This nonpayable function ensures that no coins are sent to the contract
-    nonpayable(&info);
+    assert_not_paused(deps.storage, Operation::Deposit)?;
 
-    let deposit_details: DepositMsg = from_json(&to_json_binary(&msg)?)?;
+    let deposit_details: DepositMsg = from_json(to_json_binary(&msg)?)?;
 
     let asset_config = ASSET_CONFIG.load(deps.storage)?;
     let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
@@ -348,421 +1475,1701 @@ fn execute_deposit(
         return Err(ContractError::PoolMatured {});
     }
 
-    // This contract needs to check if the user has given enough allowance to this contract to transfer funds on a  cw20 token detailed in msg
+    // This contract needs to get the funds from the user's account to this contract account
     // asset config is the configuration of the asset that the user wants to deposit
+    // native coins come attached to the message, cw20 tokens are pulled with the user's allowance
+    let funds_msgs = collect_funds(deps.as_ref(), &env, &info, &asset_config, asset_amount)?;
+
+    // from here we have deposit logic
+    let receipt_msgs = book_deposit(deps.storage, &pool_config, now, &info.sender, asset_amount)?;
+
+    // the queue is paid only once the deposit is in
+    let (queue_paid, queue_msgs) = pay_queued_withdrawals(deps.storage, &asset_config)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "deposit")
+    .add_attribute("queue_paid", queue_paid)
+    .add_submessages(funds_msgs)
+    .add_submessages(receipt_msgs)
+    .add_submessages(queue_msgs))
+}
+
+// Docs:
+// Deposit accounting shared by the allowance and the cw20 receive flows,
+// the caller is responsible for getting `amount` of the asset into the pool.
+// Returns the messages minting the lender's receipt tokens
+fn book_deposit(
+    storage: &mut dyn Storage,
+    pool_config: &PoolConfig,
+    now: u64,
+    lender: &Addr,
+    amount: Uint128,
+) -> ContractResult<Vec<SubMsg>> {
+    if now > pool_config.maturationdate {
+        return Err(ContractError::PoolMatured {});
+    }
+
+    let mut pool_state = accrue(storage, pool_config, now)?;
+    let mut position = LENDERS.may_load(storage, lender)?.unwrap_or_default();
+    let scaled_before = position.scaled;
+
+    // the deposit earns interest from the current supply index on
+    let scaled = amount.checked_div_floor(pool_state.supply_index)?;
+    position.principal = position.principal.checked_add(amount)?;
+    position.scaled = position.scaled.checked_add(scaled)?;
+    position.deposited_at = Timestamp::from_seconds(now);
+    let receipt_msgs = save_lender(storage, lender, &position, scaled_before)?;
+
+    pool_state.total_scaled_supply = pool_state.total_scaled_supply.checked_add(scaled)?;
+    pool_state.total_supplied = pool_state.total_supplied.checked_add(amount)?;
+    POOL_STATE.save(storage, &pool_state)?;
 
-    // check with asset cw20 if user has given allowance to this contract to transfer funds
-    let allowance_and_expiry: AllowanceResponse = fetch_allowance(
-        deps.as_ref(),
-        env.contract.address.clone(),
-        info.sender.clone(),
-        asset_config.denom.clone(),
+    let total_asset_available = TOTAL_ASSET_AVAILABLE.load(storage)?;
+    TOTAL_ASSET_AVAILABLE.save(storage, &total_asset_available.checked_add(amount)?)?;
+
+    Ok(receipt_msgs)
+}
+
+// Docs:
+// The lender's position. With a receipt token the pool books the shares it minted and burnt for the lender,
+// the receipt tokens they sent or got since are only seen here, against what they hold now.
+// The principal and the lock in follow the shares: the shares sent take their part of the principal with them,
+// the shares got are booked like a deposit of what they are worth now, the lock in runs from the deposit time
+// averaged over the shares so that a few shares sent to a lender can't hold back the rest of their position
+fn load_lender(
+    storage: &dyn Storage,
+    querier: &QuerierWrapper,
+    pool_state: &PoolState,
+    now: u64,
+    lender: &Addr,
+) -> ContractResult<LenderPosition> {
+    let mut position = LENDERS.may_load(storage, lender)?.unwrap_or_default();
+    let Some(receipt_token) = RECEIPT_TOKEN.may_load(storage)? else {
+        return Ok(position);
+    };
+    let res: BalanceResponse = querier.query_wasm_smart(
+        receipt_token,
+        &Cw20QueryMsg::Balance { address: lender.to_string() },
     )?;
 
-    // if the allowance is lesser than the msg.amount to transfer, 
-    // or the allowance has expired, then return an error
-    if allowance_and_expiry.allowance < asset_amount {
-        return Err(ContractError::InsufficientAllowance {});
+    if res.balance < position.scaled {
+        position.principal = position.principal.checked_multiply_ratio(res.balance, position.scaled)?;
+    } else if res.balance > position.scaled {
+        let received = res.balance.checked_sub(position.scaled)?;
+        position.principal = position.principal.checked_add(received.checked_mul_floor(pool_state.supply_index)?)?;
+
+        let deposited_at = position.deposited_at.seconds();
+        let later_by = Uint128::from(now.saturating_sub(deposited_at)).checked_multiply_ratio(received, res.balance)?;
+        let later_by = u64::try_from(later_by.u128()).map_err(|_| ContractError::Overflow {})?;
+        position.deposited_at = Timestamp::from_seconds(deposited_at.checked_add(later_by).ok_or(ContractError::Overflow {})?);
     }
-    else if allowance_and_expiry.expires.is_expired(&env.block) {
-        return Err(ContractError::AllowanceExpired {});
+    position.scaled = res.balance;
+    Ok(position)
+}
+
+// Docs:
+// Loads the lender's position and books the receipt tokens they sent or got,
+// what that changed their principal by goes into the total supplied of `pool_state`
+fn book_lender(
+    storage: &mut dyn Storage,
+    querier: &QuerierWrapper,
+    pool_state: &mut PoolState,
+    now: u64,
+    lender: &Addr,
+) -> ContractResult<LenderPosition> {
+    let booked = LENDERS.may_load(storage, lender)?.unwrap_or_default();
+    let position = load_lender(storage, querier, pool_state, now, lender)?;
+    if position != booked {
+        pool_state.total_supplied = pool_state.total_supplied.checked_add(position.principal)?.checked_sub(booked.principal)?;
+        LENDERS.save(storage, lender, &position)?;
     }
+    Ok(position)
+}
 
+// Docs:
+// Stores the lender's position. With a receipt token what the scaled balance grew by since `scaled_before`
+// is minted to the lender and what it shrank by is burnt, the burn takes the lender's allowance for the pool
+fn save_lender(
+    storage: &mut dyn Storage,
+    lender: &Addr,
+    position: &LenderPosition,
+    scaled_before: Uint128,
+) -> ContractResult<Vec<SubMsg>> {
+    LENDERS.save(storage, lender, position)?;
+    let Some(receipt_token) = RECEIPT_TOKEN.may_load(storage)? else {
+        return Ok(vec![]);
+    };
 
-    // Preparing the msg for transferring the funds here.
-    // We need to send this msg to the cw20 contract to transfer funds from the user's account to this contract account
-    let transfer_msg = Cw20ExecuteMsg::TransferFrom {
-        owner: info.sender.clone().to_string(),
-        recipient: env.contract.address.clone().to_string(),
-        amount: asset_amount,
+    let msg = if position.scaled > scaled_before {
+        Cw20ExecuteMsg::Mint { recipient: lender.to_string(), amount: position.scaled.checked_sub(scaled_before)? }
+    } else if position.scaled < scaled_before {
+        Cw20ExecuteMsg::BurnFrom { owner: lender.to_string(), amount: scaled_before.checked_sub(position.scaled)? }
+    } else {
+        return Ok(vec![]);
     };
-    
-    // from here we have deposit logic
+    Ok(vec![SubMsg::new(WasmMsg::Execute {
+        contract_addr: receipt_token.to_string(),
+        msg: to_json_binary(&msg)?,
+        funds: vec![],
+    })])
+}
 
-     let principle_deployed = PRINCIPLE_DEPLOYED.may_load(deps.storage, &info.sender)?.unwrap_or((Uint128::zero(), Timestamp::from_seconds(0)));
-     let interest_earned_by_user = INTEREST_EARNED.may_load(deps.storage, &info.sender)?.unwrap_or(Uint128::zero());
- 
-     let time_period = get_time_period(Timestamp::from_seconds(now), principle_deployed.1);
-     let interest_since_last_deposit = calculate_simple_interest(principle_deployed.0, pool_config.lendinterestrate, time_period);
- 
-     let principle_to_deposit = asset_amount;
- 
-     INTEREST_EARNED.save(deps.storage, &info.sender, &(interest_earned_by_user + interest_since_last_deposit))?;
-     PRINCIPLE_DEPLOYED.save(deps.storage, &info.sender, &(principle_deployed.0 + principle_to_deposit, Timestamp::from_seconds(now)))?;
- 
-     let mut total_asset_available = TOTAL_ASSET_AVAILABLE.load(deps.storage)?;
-     total_asset_available += principle_to_deposit;
-     TOTAL_ASSET_AVAILABLE.save(deps.storage, &total_asset_available)?;
- 
 
-    let msg = SubMsg::new(WasmMsg::Execute {
-        contract_addr: asset_config.denom.clone().to_string(),
-        msg: to_json_binary(&transfer_msg)?,
-        funds: vec![],
-    });
+
+// There's no fund to be added to the contract here.
+// Here the contract will send a Transfer call from itself to the token
+// to send money to the user!!!
+fn execute_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: WithdrawMsg,
+) -> ContractResult<Response> {
+    assert_not_paused(deps.storage, Operation::Withdraw)?;
+
+    // This nonpayable function ensures that no coins are sent to the contract
+    nonpayable(&info)?;
+
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    // After maturity the asset left has to be shared with the forfeited collateral,
+    // so lenders get out through Settle and Redeem instead
+    if now > pool_config.maturationdate {
+        return Err(ContractError::PoolMatured {});
+    }
+
+    let withdraw_details: WithdrawMsg = from_json(to_json_binary(&msg)?)?;
+    let withdraw_amount = withdraw_details.amount;
+
+    if asset_config.denom != withdraw_details.denom {
+        return Err(ContractError::InvalidAsset {});
+    }
+   
+    let mut pool_state = accrue(deps.storage, &pool_config, now)?;
+    let mut position = book_lender(deps.storage, &deps.querier, &mut pool_state, now, &info.sender)?;
+    let scaled_before = position.scaled;
+    let last_deposit_time = position.deposited_at;
+
+    let time_to_maturity = pool_config.maturationdate.saturating_sub(last_deposit_time.seconds());
+    let lock_in = Uint128::from(time_to_maturity).checked_multiply_ratio(pool_config.lock_in_period, 100u128)?;
+    let lock_in = u64::try_from(lock_in.u128()).map_err(|_| ContractError::Overflow {})?;
+    let lock_in_period_end = last_deposit_time.seconds().checked_add(lock_in).ok_or(ContractError::Overflow {})?;
+
+    if now < lock_in_period_end {
+        return Err(ContractError::LockinTimePeriodActive {});
+    } else if position.principal == Uint128::zero() {
+        return Err(ContractError::PositionNotAvailable {});
+    } else if position.principal < withdraw_amount {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    // the interest on what is withdrawn stays in the position and can be withdrawn separately,
+    // rounding down keeps the lender's balance from losing a unit to rounding
+    let scaled = std::cmp::min(withdraw_amount.checked_div_floor(pool_state.supply_index)?, position.scaled);
+    let principal = std::cmp::min(withdraw_amount, position.principal);
+
+    position.principal = position.principal.checked_sub(principal)?;
+    position.scaled = position.scaled.checked_sub(scaled)?;
+    let receipt_msgs = save_lender(deps.storage, &info.sender, &position, scaled_before)?;
+
+    pool_state.total_scaled_supply = pool_state.total_scaled_supply.checked_sub(scaled)?;
+    pool_state.total_supplied = pool_state.total_supplied.checked_sub(principal)?;
+    POOL_STATE.save(deps.storage, &pool_state)?;
+
+    // The withdrawal joins the back of the queue and is paid out as far as the asset available goes,
+    // what is lent out is paid once it comes back, after the withdrawals queued before it
+    WITHDRAWAL_QUEUE.push_back(deps.storage, &QueuedWithdrawal {
+        lender: info.sender.clone(),
+        amount: withdraw_amount,
+        queued_at: env.block.time,
+    })?;
+    let total_queued = TOTAL_QUEUED_WITHDRAWALS.may_load(deps.storage)?.unwrap_or_default();
+    TOTAL_QUEUED_WITHDRAWALS.save(deps.storage, &total_queued.checked_add(withdraw_amount)?)?;
+
+    let (queue_paid, msgs) = process_withdrawal_queue(deps.storage, &asset_config, MAX_LIMIT)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "withdraw")
+    .add_attribute("amount", withdraw_amount)
+    .add_attribute("queue_paid", queue_paid)
+    .add_submessages(receipt_msgs)
+    .add_submessages(msgs))
+}
+
+// Docs:
+// Anyone can pay out the queued withdrawals once the asset is back in the pool,
+// the inflows do it on their own for up to MAX_LIMIT withdrawals
+fn execute_process_queue(
+    deps: DepsMut,
+    info: MessageInfo,
+    limit: Option<u32>,
+) -> ContractResult<Response> {
+    assert_not_paused(deps.storage, Operation::Withdraw)?;
+
+    // This nonpayable function ensures that no coins are sent to the contract
+    nonpayable(&info)?;
+
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let (queue_paid, msgs) = process_withdrawal_queue(deps.storage, &asset_config, limit)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "process_queue")
+    .add_attribute("queue_paid", queue_paid)
+    .add_submessages(msgs))
+}
+
+// Docs:
+// Pays the queued withdrawals oldest first out of the asset available,
+// the one at the front is paid in part when the asset runs out and stays at the front.
+// At most `limit` withdrawals are visited, returns the amount paid and the payouts
+fn process_withdrawal_queue(
+    storage: &mut dyn Storage,
+    asset_config: &CoinConfig,
+    limit: u32,
+) -> ContractResult<(Uint128, Vec<SubMsg>)> {
+    let mut total_asset_available = TOTAL_ASSET_AVAILABLE.load(storage)?;
+    let mut paid = Uint128::zero();
+    let mut msgs = vec![];
+
+    for _ in 0..limit {
+        if total_asset_available.is_zero() {
+            break;
+        }
+        let mut withdrawal = match WITHDRAWAL_QUEUE.pop_front(storage)? {
+            Some(withdrawal) => withdrawal,
+            None => break,
+        };
+
+        let amount = std::cmp::min(withdrawal.amount, total_asset_available);
+        withdrawal.amount = withdrawal.amount.checked_sub(amount)?;
+        total_asset_available = total_asset_available.checked_sub(amount)?;
+        paid = paid.checked_add(amount)?;
+
+        if !withdrawal.amount.is_zero() {
+            WITHDRAWAL_QUEUE.push_front(storage, &withdrawal)?;
+        }
+        msgs.extend(payout_msgs(asset_config, &withdrawal.lender, amount)?);
+    }
+
+    TOTAL_ASSET_AVAILABLE.save(storage, &total_asset_available)?;
+    let total_queued = TOTAL_QUEUED_WITHDRAWALS.may_load(storage)?.unwrap_or_default();
+    TOTAL_QUEUED_WITHDRAWALS.save(storage, &total_queued.checked_sub(paid)?)?;
+
+    Ok((paid, msgs))
+}
+
+// Docs:
+// The queue is paid along with every inflow of the asset, unless withdrawals are paused
+fn pay_queued_withdrawals(
+    storage: &mut dyn Storage,
+    asset_config: &CoinConfig,
+) -> ContractResult<(Uint128, Vec<SubMsg>)> {
+    let pause_state = PAUSE_STATE.may_load(storage)?.unwrap_or_default();
+    if pause_state.is_paused(Operation::Withdraw) {
+        return Ok((Uint128::zero(), vec![]));
+    }
+    process_withdrawal_queue(storage, asset_config, MAX_LIMIT)
+}
+
+// Docs:
+// Books the receipt tokens the lender sent or got since the pool last saw their position.
+// Withdrawals do it on their own, this keeps the pool's totals up to date in between
+// and starts the lock in of the shares got from when they were got
+fn execute_sync_lender(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    lender: String,
+) -> ContractResult<Response> {
+    // This nonpayable function ensures that no coins are sent to the contract
+    nonpayable(&info)?;
+
+    let lender = deps.api.addr_validate(&lender)?;
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    let mut pool_state = accrue(deps.storage, &pool_config, now)?;
+    let position = book_lender(deps.storage, &deps.querier, &mut pool_state, now, &lender)?;
+    POOL_STATE.save(deps.storage, &pool_state)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "sync_lender")
+    .add_attribute("lender", lender)
+    .add_attribute("principal", position.principal)
+    .add_attribute("scaled", position.scaled))
+}
+
+fn execute_withdraw_interest(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> ContractResult<Response> {
+    assert_not_paused(deps.storage, Operation::WithdrawInterest)?;
+
+    // This nonpayable function ensures that no coins are sent to the contract
+    nonpayable(&info)?;
+
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+    if now > pool_config.maturationdate {
+        return Err(ContractError::PoolMatured {});
+    }
+
+    // the queue is owed the asset first, interest can't jump it
+    if !TOTAL_QUEUED_WITHDRAWALS.may_load(deps.storage)?.unwrap_or_default().is_zero() {
+        return Err(ContractError::WithdrawalsQueued {});
+    }
+
+    let mut pool_state = accrue(deps.storage, &pool_config, now)?;
+    let mut position = book_lender(deps.storage, &deps.querier, &mut pool_state, now, &info.sender)?;
+    let scaled_before = position.scaled;
+    let interest_earned_by_user = position.balance(&pool_state)?.saturating_sub(position.principal);
+
+    // TODO: could have used a 'revert if no interest' here
+
+    let scaled = std::cmp::min(interest_earned_by_user.checked_div_ceil(pool_state.supply_index)?, position.scaled);
+    position.scaled = position.scaled.checked_sub(scaled)?;
+    let receipt_msgs = save_lender(deps.storage, &info.sender, &position, scaled_before)?;
+
+    pool_state.total_scaled_supply = pool_state.total_scaled_supply.checked_sub(scaled)?;
+    POOL_STATE.save(deps.storage, &pool_state)?;
+
+    let total_asset_available = TOTAL_ASSET_AVAILABLE.load(deps.storage)?;
+    if total_asset_available < interest_earned_by_user {
+        return Err(ContractError::InsufficientFunds {});
+    }
+    TOTAL_ASSET_AVAILABLE.save(deps.storage, &(total_asset_available.checked_sub(interest_earned_by_user)?))?;
+
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    
+    // Preparing the msg for transferring the funds here.
+    // We need to send the funds from the contract's account to user's account
+    let msgs = payout_msgs(&asset_config, &info.sender, interest_earned_by_user)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "withdraw_interest")
+    .add_submessages(receipt_msgs)
+    .add_submessages(msgs))
+}
+
+fn execute_loan(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: LoanMsg,
+) -> Result<Response, ContractError> {
+    assert_not_paused(deps.storage, Operation::Loan)?;
+
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    if now > pool_config.maturationdate {
+        return Err(ContractError::PoolMatured {});
+    }
+
+    if pool_config.overcollateralizationfactor < Decimal::one() {
+        return Err(ContractError::InsufficientOCF {});
+    }
+
+    let tokens_details: LoanMsg = from_json(to_json_binary(&msg)?)?;
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+
+    if asset_config.denom != tokens_details.asset_denom {
+        return Err(ContractError::InvalidAsset {});
+    } 
+    if collateral_config.denom != tokens_details.collateral_denom {
+        return Err(ContractError::InvalidCollateral {});
+    }
+
+    // calculate the collateral needed for current loan
+
+    let new_collateral_needed = calculate_collateral_amount(tokens_details.asset_amount, pool_config.strikeprice, pool_config.overcollateralizationfactor)?;
+
+    // This contract needs to get the collateral from the user's account to the contract account
+    // collateral config is the configuration of the collateral that the user wants to stake
+    // native collateral comes attached to the message, cw20 collateral is pulled with the user's allowance
+    let collateral_msgs = collect_funds(deps.as_ref(), &env, &info, &collateral_config, new_collateral_needed)?;
+
+    let (loan_id, fee) = book_loan(deps.storage, &pool_config, now, &info.sender, tokens_details.asset_amount, new_collateral_needed)?;
+    assert_healthy(deps.as_ref(), &env, loan_id)?;
+
+    // Now we need to firstly transfer the collateral from the user's account to the contract account
+    // then we need to transfer the asset from the contract account to the user's account
+    let asset_msgs = payout_msgs(&asset_config, &info.sender, tokens_details.asset_amount.checked_sub(fee)?)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "loan")
+    .add_attribute("loan_id", loan_id.to_string())
+    .add_attribute("fee", fee)
+    .add_submessages(collateral_msgs)
+    .add_submessages(asset_msgs))
+
+}
+
+// Docs:
+// Loan accounting shared by the allowance and the cw20 receive flows,
+// opens a new loan of `asset_amount` against `collateral` that the caller gets into the pool
+// and reserves the asset that the caller pays out to the borrower.
+// Returns the id of the loan and the origination fee, kept for the protocol out of what is paid out
+fn book_loan(
+    storage: &mut dyn Storage,
+    pool_config: &PoolConfig,
+    now: u64,
+    borrower: &Addr,
+    asset_amount: Uint128,
+    collateral: Uint128,
+) -> ContractResult<(u64, Uint128)> {
+    if now > pool_config.maturationdate {
+        return Err(ContractError::PoolMatured {});
+    }
+
+    if pool_config.overcollateralizationfactor < Decimal::one() {
+        return Err(ContractError::InsufficientOCF {});
+    }
+
+    let total_asset_available = TOTAL_ASSET_AVAILABLE.load(storage)?;
+    if total_asset_available < asset_amount {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    // calculate the needful
+
+    let mut pool_state = accrue(storage, pool_config, now)?;
+
+    // the loan accrues interest at the pool's debt rate from its current borrow index on
+    let rate = pool_config.debtinterestrate;
+    let scaled_debt = asset_amount.checked_div_ceil(pool_state.borrow_index(rate))?;
+    let loan_id = LOAN_COUNT.may_load(storage)?.unwrap_or_default().checked_add(1).ok_or(ContractError::Overflow {})?;
+    LOAN_COUNT.save(storage, &loan_id)?;
+    let loan = Loan {
+        id: loan_id,
+        borrower: borrower.clone(),
+        principal: asset_amount,
+        scaled_debt,
+        collateral,
+        opened_at: Timestamp::from_seconds(now),
+        rate,
+    };
+    loans().save(storage, loan_id, &loan)?;
+
+    pool_state.add_scaled_debt(rate, scaled_debt)?;
+    pool_state.total_borrowed = pool_state.total_borrowed.checked_add(asset_amount)?;
+    POOL_STATE.save(storage, &pool_state)?;
+
+    TOTAL_ASSET_AVAILABLE.save(storage, &total_asset_available.checked_sub(asset_amount)?)?;
+
+    let total_collateral_available = TOTAL_COLLATERAL_AVAILABLE.load(storage)?;
+    TOTAL_COLLATERAL_AVAILABLE.save(storage, &total_collateral_available.checked_add(collateral)?)?;
+
+    let fee = asset_amount.checked_mul_floor(fee_config(storage)?.origination_fee)?;
+    book_protocol_earnings(storage, fee)?;
+
+    Ok((loan_id, fee))
+}
+
+
+// Just like in borrow there's a transfer of two tokens,
+// here also there's a transfer of two tokens
+// wherein, this time it's reversed,
+// user sends asset sum of principal and interest to the pool 
+// and receives collateral in return
+// Let's assume it to be full repay on the UI level
+// This function is used to repay the loan taken by the user
+
+
+fn execute_repay(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg : RepayMsg,
+) -> ContractResult<Response> {
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    let tokens_details: RepayMsg = from_json(to_json_binary(&msg)?)?;
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+
+    if asset_config.denom != tokens_details.asset_denom {
+        return Err(ContractError::InvalidAsset {});
+    } 
+    if collateral_config.denom != tokens_details.collateral_denom {
+        return Err(ContractError::InvalidCollateral {});
+    }
+
+    // I am fine with user repaying lesser than they have debt for since it's can also be a partial repayment.
+    let (amount_due, appropriate_collateral_to_unlock) = book_repay(deps.storage, &pool_config, now, &info.sender, tokens_details.loan_id, tokens_details.asset_principle)?;
+
+    // Getting the user's asset into the contract, native coins attached above the amount due are refunded
+    // and cw20 tokens are pulled with the user's allowance
+    let asset_msgs = collect_funds(deps.as_ref(), &env, &info, &asset_config, amount_due)?;
+  
+    // Transfer the tokens
+    let collateral_msgs = payout_msgs(&collateral_config, &info.sender, appropriate_collateral_to_unlock)?;
+    let (queue_paid, queue_msgs) = pay_queued_withdrawals(deps.storage, &asset_config)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "repay")
+    .add_attribute("loan_id", tokens_details.loan_id.to_string())
+    .add_attribute("queue_paid", queue_paid)
+    .add_submessages(asset_msgs)
+    .add_submessages(collateral_msgs)
+    .add_submessages(queue_msgs))    
+
+}
+
+// Docs:
+// Repay accounting shared by the allowance and the cw20 receive flows,
+// settles `loan_user_is_repaying` of the principal of the borrower's loan with the interest on it
+// and returns the asset amount due along with the collateral to unlock
+fn book_repay(
+    storage: &mut dyn Storage,
+    pool_config: &PoolConfig,
+    now: u64,
+    borrower: &Addr,
+    loan_id: u64,
+    loan_user_is_repaying: Uint128,
+) -> ContractResult<(Uint128, Uint128)> {
+    if now > pool_config.maturationdate {
+        return Err(ContractError::CollateralForfeited {});
+    }
+
+    if pool_config.overcollateralizationfactor < Decimal::one() {
+        return Err(ContractError::InsufficientOCF {});
+    }
+
+    let mut pool_state = accrue(storage, pool_config, now)?;
+    let mut position = load_loan(storage, loan_id)?;
+    if position.borrower != borrower {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // whatever clears the debt closes the loan and releases what collateral is left
+    if position.scaled_debt.is_zero() {
+        return Err(ContractError::PositionNotAvailable {});
+    }
+
+    if loan_user_is_repaying > position.principal {
+        return Err(ContractError::ExcessiveFunds {});
+    }
+
+    // Repaying a share of the principal repays the same share of the debt, interest included.
+    // The collateral held can differ from what the principal was sized at after a liquidation,
+    // so it is unlocked in proportion to the principal repaid as well
+    let (scaled_repaid, appropriate_collateral_to_unlock) = if loan_user_is_repaying == position.principal {
+        (position.scaled_debt, position.collateral)
+    } else {
+        (
+            position.scaled_debt.checked_multiply_ratio(loan_user_is_repaying, position.principal)?,
+            position.collateral.checked_multiply_ratio(loan_user_is_repaying, position.principal)?,
+        )
+    };
+    let amount_due = scaled_repaid.checked_mul_ceil(pool_state.borrow_index(position.rate))?;
+
+    position.principal = position.principal.checked_sub(loan_user_is_repaying)?;
+    position.scaled_debt = position.scaled_debt.checked_sub(scaled_repaid)?;
+    position.collateral = position.collateral.checked_sub(appropriate_collateral_to_unlock)?;
+    if position.scaled_debt.is_zero() && position.collateral.is_zero() {
+        remove_loan(storage, loan_id)?;
+    } else {
+        loans().save(storage, loan_id, &position)?;
+    }
+
+    pool_state.sub_scaled_debt(position.rate, scaled_repaid)?;
+    pool_state.total_borrowed = pool_state.total_borrowed.checked_sub(loan_user_is_repaying)?;
+    POOL_STATE.save(storage, &pool_state)?;
+
+    // the protocol's share of the interest is held apart from the asset lent out
+    let interest_repaid = amount_due.saturating_sub(loan_user_is_repaying);
+    let reserve = interest_repaid.checked_mul_floor(fee_config(storage)?.reserve_factor)?;
+    book_protocol_earnings(storage, reserve)?;
+
+    let total_asset_available = TOTAL_ASSET_AVAILABLE.load(storage)?;
+    TOTAL_ASSET_AVAILABLE.save(storage, &total_asset_available.checked_add(amount_due.checked_sub(reserve)?)?)?;
+
+    let total_collateral_available = TOTAL_COLLATERAL_AVAILABLE.load(storage)?;
+    let t_c_a = total_collateral_available.checked_sub(appropriate_collateral_to_unlock)?;
+    TOTAL_COLLATERAL_AVAILABLE.save(storage, &t_c_a)?;
+
+    Ok((amount_due, appropriate_collateral_to_unlock))
+}
+
+// Docs:
+// Repays a loan with whatever the holder wants to pay rather than a share of the principal,
+// native coins attached above what the payment went to are refunded
+// and cw20 tokens are pulled with the holder's allowance only for that much.
+fn execute_repay_amount(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    loan_id: u64,
+    amount: Uint128,
+) -> ContractResult<Response> {
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+
+    let repayment = book_repay_amount(deps.storage, &pool_config, env.block.time.seconds(), &info.sender, loan_id, amount)?;
+
+    let asset_msgs = collect_funds(deps.as_ref(), &env, &info, &asset_config, repayment.paid()?)?;
+    let collateral_msgs = payout_msgs(&collateral_config, &info.sender, repayment.collateral)?;
+    let (queue_paid, queue_msgs) = pay_queued_withdrawals(deps.storage, &asset_config)?;
+
+    Ok(repayment.response(loan_id)?
+    .add_attribute("queue_paid", queue_paid)
+    .add_submessages(asset_msgs)
+    .add_submessages(collateral_msgs)
+    .add_submessages(queue_msgs))
+}
+
+// What a payment towards a loan went to, and the collateral it unlocked
+struct Repayment {
+    interest: Uint128,
+    principal: Uint128,
+    collateral: Uint128,
+}
+
+impl Repayment {
+    fn paid(&self) -> ContractResult<Uint128> {
+        Ok(self.interest.checked_add(self.principal)?)
+    }
+
+    fn response(&self, loan_id: u64) -> ContractResult<Response> {
+        Ok(Response::new()
+        .add_attribute("action", "repay")
+        .add_attribute("loan_id", loan_id.to_string())
+        .add_attribute("paid", self.paid()?)
+        .add_attribute("interest_paid", self.interest)
+        .add_attribute("principal_paid", self.principal)
+        .add_attribute("collateral_unlocked", self.collateral))
+    }
+}
+
+// Docs:
+// Repay accounting of a payment shared by the allowance and the cw20 receive flows.
+// The origination fee is kept when the loan is paid out so a loan owes no fees,
+// the payment goes to the accrued interest first, then to the principal, the same way liquidations repay.
+// Paying off the whole debt closes the loan, anything above it isn't taken
+fn book_repay_amount(
+    storage: &mut dyn Storage,
+    pool_config: &PoolConfig,
+    now: u64,
+    holder: &Addr,
+    loan_id: u64,
+    payment: Uint128,
+) -> ContractResult<Repayment> {
+    if now > pool_config.maturationdate {
+        return Err(ContractError::CollateralForfeited {});
+    }
+
+    if payment.is_zero() {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    let mut pool_state = accrue(storage, pool_config, now)?;
+    let mut position = load_loan(storage, loan_id)?;
+    if position.borrower != holder {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let debt = position.debt(&pool_state)?;
+    if debt.is_zero() {
+        return Err(ContractError::PositionNotAvailable {});
+    }
+    let interest_owed = debt.saturating_sub(position.principal);
+
+    let (scaled_repaid, repayment) = if payment >= debt {
+        (position.scaled_debt, Repayment {
+            interest: interest_owed,
+            principal: position.principal,
+            collateral: position.collateral,
+        })
+    } else {
+        let interest = std::cmp::min(payment, interest_owed);
+        let principal = payment.checked_sub(interest)?;
+        // an interest only payment leaves all of the collateral locked
+        let collateral = if principal.is_zero() {
+            Uint128::zero()
+        } else {
+            position.collateral.checked_multiply_ratio(principal, position.principal)?
+        };
+        // below the debt, the scaled debt repaid rounds down in favour of the pool
+        (payment.checked_div_floor(pool_state.borrow_index(position.rate))?, Repayment { interest, principal, collateral })
+    };
+
+    position.principal = position.principal.checked_sub(repayment.principal)?;
+    position.scaled_debt = position.scaled_debt.checked_sub(scaled_repaid)?;
+    position.collateral = position.collateral.checked_sub(repayment.collateral)?;
+    if position.scaled_debt.is_zero() && position.collateral.is_zero() {
+        remove_loan(storage, loan_id)?;
+    } else {
+        loans().save(storage, loan_id, &position)?;
+    }
+
+    pool_state.sub_scaled_debt(position.rate, scaled_repaid)?;
+    pool_state.total_borrowed = pool_state.total_borrowed.checked_sub(repayment.principal)?;
+    POOL_STATE.save(storage, &pool_state)?;
+
+    // the protocol's share of the interest is held apart from the asset lent out
+    let reserve = repayment.interest.checked_mul_floor(fee_config(storage)?.reserve_factor)?;
+    book_protocol_earnings(storage, reserve)?;
+
+    let total_asset_available = TOTAL_ASSET_AVAILABLE.load(storage)?;
+    TOTAL_ASSET_AVAILABLE.save(storage, &total_asset_available.checked_add(repayment.paid()?.checked_sub(reserve)?)?)?;
+
+    let total_collateral_available = TOTAL_COLLATERAL_AVAILABLE.load(storage)?;
+    TOTAL_COLLATERAL_AVAILABLE.save(storage, &total_collateral_available.checked_sub(repayment.collateral)?)?;
+
+    Ok(repayment)
+}
+
+// Docs:
+// Once the pool has matured nobody can repay anymore, so every loan still owing is defaulted.
+// Their collateral is forfeited to the lenders, and the asset left in the pool along with
+// the forfeited collateral is snapshotted to be shared pro-rata to what each lender is owed.
+// Anyone can settle the pool. Each call goes through at most `limit` loans and then queued withdrawals,
+// those are gone once gone through so the next call picks up after them, and the call that leaves
+// none takes the snapshot. Once taken there is nothing left to settle.
+fn execute_settle(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    limit: Option<u32>,
+) -> ContractResult<Response> {
+    // This nonpayable function ensures that no coins are sent to the contract
+    nonpayable(&info)?;
+
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    if now <= pool_config.maturationdate {
+        return Err(ContractError::PoolNotMatured {});
+    }
+    if SETTLEMENT.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::PoolSettled {});
+    }
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    // interest stops at maturity
+    let mut pool_state = accrue(deps.storage, &pool_config, now)?;
+
+    // mark the unpaid loans as defaulted
+    let open_loans = loans()
+        .range(deps.storage, None, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut visited = open_loans.len();
+    let mut defaulted_loans = 0u32;
+    let mut released_msgs = vec![];
+    for (loan_id, loan) in open_loans {
+        remove_loan(deps.storage, loan_id)?;
+        pool_state.sub_scaled_debt(loan.rate, loan.scaled_debt)?;
+        pool_state.total_borrowed = pool_state.total_borrowed.checked_sub(loan.principal)?;
+
+        // a loan with nothing owed isn't defaulted, its collateral goes back to the holder
+        if loan.scaled_debt.is_zero() {
+            let total_collateral_available = TOTAL_COLLATERAL_AVAILABLE.load(deps.storage)?;
+            TOTAL_COLLATERAL_AVAILABLE.save(deps.storage, &total_collateral_available.checked_sub(loan.collateral)?)?;
+            released_msgs.extend(payout_msgs(&collateral_config, &loan.borrower, loan.collateral)?);
+            continue;
+        }
+
+        let defaulted = DefaultedLoan { debt: loan.debt(&pool_state)?, borrower: loan.borrower, collateral: loan.collateral };
+        DEFAULTED_LOANS.save(deps.storage, loan_id, &defaulted)?;
+        defaulted_loans += 1;
+    }
+
+    // what is still queued goes back into the positions and is redeemed with the rest
+    let mut receipt_msgs = vec![];
+    let mut total_queued = TOTAL_QUEUED_WITHDRAWALS.may_load(deps.storage)?.unwrap_or_default();
+    while visited < limit {
+        let Some(withdrawal) = WITHDRAWAL_QUEUE.pop_front(deps.storage)? else {
+            break;
+        };
+        visited += 1;
+
+        let scaled = withdrawal.amount.checked_div_floor(pool_state.supply_index)?;
+        let mut position = LENDERS.may_load(deps.storage, &withdrawal.lender)?.unwrap_or_default();
+        let scaled_before = position.scaled;
+        position.principal = position.principal.checked_add(withdrawal.amount)?;
+        position.scaled = position.scaled.checked_add(scaled)?;
+        receipt_msgs.extend(save_lender(deps.storage, &withdrawal.lender, &position, scaled_before)?);
+
+        pool_state.total_scaled_supply = pool_state.total_scaled_supply.checked_add(scaled)?;
+        pool_state.total_supplied = pool_state.total_supplied.checked_add(withdrawal.amount)?;
+        total_queued = total_queued.checked_sub(withdrawal.amount)?;
+    }
+    TOTAL_QUEUED_WITHDRAWALS.save(deps.storage, &total_queued)?;
+    POOL_STATE.save(deps.storage, &pool_state)?;
+
+    let response = Response::new()
+    .add_attribute("action", "settle")
+    .add_attribute("defaulted_loans", defaulted_loans.to_string())
+    .add_submessages(released_msgs)
+    .add_submessages(receipt_msgs);
+
+    let loans_left = loans().keys(deps.storage, None, None, Order::Ascending).next().is_some();
+    if loans_left || !WITHDRAWAL_QUEUE.is_empty(deps.storage)? {
+        return Ok(response.add_attribute("settled", "false"));
+    }
+
+    // what the lenders are owed at maturity
+    let total_lender_claims = pool_state.total_supply()?;
+
+    // whatever collateral is still held belongs to defaulted loans
+    let settlement = Settlement {
+        settled_at: now,
+        asset_available: TOTAL_ASSET_AVAILABLE.load(deps.storage)?,
+        collateral_forfeited: TOTAL_COLLATERAL_AVAILABLE.load(deps.storage)?,
+        total_lender_claims,
+    };
+    SETTLEMENT.save(deps.storage, &settlement)?;
+
+    Ok(response
+    .add_attribute("settled", "true")
+    .add_attribute("asset_available", settlement.asset_available)
+    .add_attribute("collateral_forfeited", settlement.collateral_forfeited)
+    .add_attribute("total_lender_claims", settlement.total_lender_claims))
+}
+
+// Docs:
+// Pays out the lender's share of the settled pool and closes their position,
+// the share is the lender's claim at maturity over the claims of all lenders
+fn execute_redeem(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> ContractResult<Response> {
+    assert_not_paused(deps.storage, Operation::Withdraw)?;
+
+    // This nonpayable function ensures that no coins are sent to the contract
+    nonpayable(&info)?;
+
+    let settlement = SETTLEMENT.may_load(deps.storage)?.ok_or(ContractError::PoolNotSettled {})?;
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+
+    let mut pool_state = POOL_STATE.load(deps.storage)?;
+    let position = book_lender(deps.storage, &deps.querier, &mut pool_state, env.block.time.seconds(), &info.sender)?;
+    let claim = lender_claim(deps.storage, &pool_config, &position)?;
+    if claim.is_zero() {
+        return Err(ContractError::PositionNotAvailable {});
+    }
+
+    let asset_share = settlement.asset_available.checked_multiply_ratio(claim, settlement.total_lender_claims)?;
+    let collateral_share = settlement.collateral_forfeited.checked_multiply_ratio(claim, settlement.total_lender_claims)?;
+
+    let receipt_msgs = save_lender(deps.storage, &info.sender, &LenderPosition::default(), position.scaled)?;
+    LENDERS.remove(deps.storage, &info.sender);
+
+    pool_state.total_scaled_supply = pool_state.total_scaled_supply.checked_sub(position.scaled)?;
+    pool_state.total_supplied = pool_state.total_supplied.checked_sub(position.principal)?;
+    POOL_STATE.save(deps.storage, &pool_state)?;
+
+    let total_asset_available = TOTAL_ASSET_AVAILABLE.load(deps.storage)?;
+    TOTAL_ASSET_AVAILABLE.save(deps.storage, &(total_asset_available.checked_sub(asset_share)?))?;
+
+    let total_collateral_available = TOTAL_COLLATERAL_AVAILABLE.load(deps.storage)?;
+    TOTAL_COLLATERAL_AVAILABLE.save(deps.storage, &(total_collateral_available.checked_sub(collateral_share)?))?;
+
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+
+    Ok(Response::new()
+    .add_attribute("action", "redeem")
+    .add_attribute("claim", claim)
+    .add_attribute("asset", asset_share)
+    .add_attribute("collateral", collateral_share)
+    .add_submessages(receipt_msgs)
+    .add_submessages(payout_msgs(&asset_config, &info.sender, asset_share)?)
+    .add_submessages(payout_msgs(&collateral_config, &info.sender, collateral_share)?))
+}
+
+// Docs:
+// What the pool owes a lender at maturity, the principal plus all interest not withdrawn yet
+fn lender_claim(
+    storage: &dyn Storage,
+    pool_config: &PoolConfig,
+    position: &LenderPosition,
+) -> ContractResult<Uint128> {
+    let at_maturity = pool_state_at(storage, pool_config, pool_config.maturationdate)?;
+
+    Ok(position.balance(&at_maturity)?)
+}
+
+// Docs:
+// Offers a loan to a new holder, who repays it from then on and gets its collateral back.
+// The loan is a debt as much as a claim on the collateral, so nobody gets one without taking it
+// with AcceptLoan, a later transfer replaces the offer.
+// With `msg` the recipient is a contract that is told about the loan offered, as with cw721 SendNft
+fn execute_transfer_loan(
+    deps: DepsMut,
+    info: MessageInfo,
+    recipient: String,
+    token_id: String,
+    msg: Option<Binary>,
+) -> ContractResult<Response> {
+    // This nonpayable function ensures that no coins are sent to the contract
+    nonpayable(&info)?;
+
+    let recipient = deps.api.addr_validate(&recipient)?;
+    let loan_id = parse_loan_id(&token_id)?;
+    let loan = load_loan(deps.storage, loan_id)?;
+    if loan.borrower != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    PENDING_LOAN_TRANSFERS.save(deps.storage, loan_id, &recipient)?;
+
+    let mut response = Response::new()
+    .add_attribute("action", if msg.is_some() { "send_nft" } else { "transfer_nft" })
+    .add_attribute("sender", info.sender.clone())
+    .add_attribute("recipient", recipient.clone())
+    .add_attribute("token_id", token_id.clone());
+
+    if let Some(msg) = msg {
+        let receive_msg = Cw721ReceiverExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: info.sender.to_string(),
+            token_id,
+            msg,
+        });
+        response = response.add_message(WasmMsg::Execute {
+            contract_addr: recipient.to_string(),
+            msg: to_json_binary(&receive_msg)?,
+            funds: vec![],
+        });
+    }
+
+    Ok(response)
+}
+
+// Docs:
+// Second step of a loan transfer, sent by the recipient the holder transferred the loan to
+fn execute_accept_loan(
+    deps: DepsMut,
+    info: MessageInfo,
+    loan_id: u64,
+) -> ContractResult<Response> {
+    // This nonpayable function ensures that no coins are sent to the contract
+    nonpayable(&info)?;
+
+    let recipient = PENDING_LOAN_TRANSFERS.may_load(deps.storage, loan_id)?;
+    if recipient.as_ref() != Some(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut loan = load_loan(deps.storage, loan_id)?;
+    let previous_holder = std::mem::replace(&mut loan.borrower, info.sender.clone());
+    loans().save(deps.storage, loan_id, &loan)?;
+    PENDING_LOAN_TRANSFERS.remove(deps.storage, loan_id);
+
+    Ok(Response::new()
+    .add_attribute("action", "accept_loan")
+    .add_attribute("loan_id", loan_id.to_string())
+    .add_attribute("sender", previous_holder)
+    .add_attribute("recipient", info.sender))
+}
+
+// Docs:
+// Topping up is how a borrower defends a loan against liquidation,
+// so just like repaying it can't be paused
+fn execute_top_up_collateral(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    loan_id: u64,
+    amount: Uint128,
+) -> ContractResult<Response> {
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+
+    let collateral_msgs = collect_funds(deps.as_ref(), &env, &info, &collateral_config, amount)?;
+    book_top_up_collateral(deps.storage, &pool_config, env.block.time.seconds(), &info.sender, loan_id, amount)?;
 
     Ok(Response::new()
-    .add_attribute("action", "deposit")
-    .add_submessage(msg))
+    .add_attribute("action", "top_up_collateral")
+    .add_attribute("loan_id", loan_id.to_string())
+    .add_attribute("amount", amount)
+    .add_submessages(collateral_msgs))
 }
 
+// Docs:
+// Top up accounting shared by the allowance and the cw20 receive flows,
+// adds `amount` of collateral that the caller gets into the pool to the holder's loan
+fn book_top_up_collateral(
+    storage: &mut dyn Storage,
+    pool_config: &PoolConfig,
+    now: u64,
+    holder: &Addr,
+    loan_id: u64,
+    amount: Uint128,
+) -> ContractResult<()> {
+    if now > pool_config.maturationdate {
+        return Err(ContractError::CollateralForfeited {});
+    }
+
+    let mut loan = load_loan(storage, loan_id)?;
+    if loan.borrower != holder {
+        return Err(ContractError::Unauthorized {});
+    }
 
+    loan.collateral = loan.collateral.checked_add(amount)?;
+    loans().save(storage, loan_id, &loan)?;
 
-// There's no fund to be added to the contract here.
-// Here the contract will send a Transfer call from itself to the token
-// to send money to the user!!!
-fn execute_withdraw(
+    let total_collateral_available = TOTAL_COLLATERAL_AVAILABLE.load(storage)?;
+    TOTAL_COLLATERAL_AVAILABLE.save(storage, &total_collateral_available.checked_add(amount)?)?;
+
+    Ok(())
+}
+
+// Docs:
+// Releases collateral of a loan above what it requires, that is the over-collateralization factor
+// of its current debt valued at the oracle price, the same ratio a new loan is opened at,
+// or the liquidation threshold if that's higher so the loan is never left liquidatable.
+// Taking value out of the pool like a loan does, it's paused along with loans
+fn execute_withdraw_collateral(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    msg: WithdrawMsg,
+    loan_id: u64,
+    amount: Uint128,
 ) -> ContractResult<Response> {
     // This nonpayable function ensures that no coins are sent to the contract
-    nonpayable(&info);
+    nonpayable(&info)?;
+    assert_not_paused(deps.storage, Operation::Loan)?;
 
     let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
-    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
     let now = env.block.time.seconds();
+    if now > pool_config.maturationdate {
+        return Err(ContractError::CollateralForfeited {});
+    }
 
-    let withdraw_details: WithdrawMsg = from_json(&to_json_binary(&msg)?)?;
-    let withdraw_amount = withdraw_details.amount;
+    let mut loan = load_loan(deps.storage, loan_id)?;
+    if loan.borrower != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if amount > loan.collateral {
+        return Err(ContractError::InsufficientCollateral {});
+    }
 
-    if asset_config.denom != withdraw_details.denom {
-        return Err(ContractError::InvalidAsset {});
+    loan.collateral = loan.collateral.checked_sub(amount)?;
+    let debt = current_debt(deps.storage, &pool_config, now, &loan)?;
+    let liquidation_config = LIQUIDATION_CONFIG.load(deps.storage)?;
+    let required_ratio = std::cmp::max(pool_config.overcollateralizationfactor, liquidation_config.threshold);
+    let collateral_required = debt.checked_mul_ceil(required_ratio)?;
+    if collateral_value(deps.as_ref(), &loan)? < collateral_required {
+        return Err(ContractError::Undercollateralized {});
     }
-   
-    let (principle_deployed, last_deposit_time) = PRINCIPLE_DEPLOYED.may_load(deps.storage, &info.sender)?.unwrap_or((Uint128::zero(), Timestamp::from_seconds(0)));
-    let interest_earned_by_user = INTEREST_EARNED.may_load(deps.storage, &info.sender)?.unwrap_or(Uint128::zero());
+    loans().save(deps.storage, loan_id, &loan)?;
 
-    let lock_in_period_end = last_deposit_time.plus_seconds((pool_config.lock_in_period.u128() * ((pool_config.maturationdate - last_deposit_time.seconds() ) as u128) / 100) as u64).seconds();
+    let total_collateral_available = TOTAL_COLLATERAL_AVAILABLE.load(deps.storage)?;
+    TOTAL_COLLATERAL_AVAILABLE.save(deps.storage, &total_collateral_available.checked_sub(amount)?)?;
 
-    if now < lock_in_period_end {
-        return Err(ContractError::LockinTimePeriodActive {});
-    } else if principle_deployed == Uint128::zero() {
-        return Err(ContractError::PositionNotAvailable {});
-    } else if principle_deployed < withdraw_amount {
-        return Err(ContractError::InsufficientFunds {});
-    }
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
 
-    // compare which is earlier, now or pool config maturity, return which ever is earlier
-    // this ensures that user is not earing interest after the pool has matured
-    let min_time = std::cmp::min(now, pool_config.maturationdate);
+    Ok(Response::new()
+    .add_attribute("action", "withdraw_collateral")
+    .add_attribute("loan_id", loan_id.to_string())
+    .add_attribute("amount", amount)
+    .add_submessages(payout_msgs(&collateral_config, &info.sender, amount)?))
+}
 
-    let time_period = get_time_period(Timestamp::from_seconds(min_time), last_deposit_time);
-    let interest = calculate_simple_interest(principle_deployed, pool_config.lendinterestrate, time_period);
+// Docs:
+// Rolls a loan over into a successor pool instead of repaying it before maturity.
+// The successor has to be a pool of this same code, anything else could take the collateral and pay nothing back.
+// The loan is booked as repaid in full here and its collateral is sent to the successor with AcceptRollover,
+// which opens a loan of the debt for the holder and pays the asset back to this pool with RepayRollover.
+// The reply checks that the debt came in that way, refunds what came above it to the holder,
+// and fails the whole transaction otherwise, so the loan is either moved or left as it was.
+fn execute_rollover(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    loan_id: u64,
+    target_pool: String,
+) -> ContractResult<Response> {
+    // This nonpayable function ensures that no coins are sent to the contract
+    nonpayable(&info)?;
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
 
-    INTEREST_EARNED.save(deps.storage, &info.sender, &(interest_earned_by_user + interest))?;
-    PRINCIPLE_DEPLOYED.save(deps.storage, &info.sender, &(principle_deployed - withdraw_amount, Timestamp::from_seconds(now)))?;
+    let target_pool = deps.api.addr_validate(&target_pool)?;
+    if target_pool == env.contract.address {
+        return Err(ContractError::InvalidRolloverTarget {});
+    }
+    let code_id = deps.querier.query_wasm_contract_info(&env.contract.address)?.code_id;
+    if deps.querier.query_wasm_contract_info(&target_pool)?.code_id != code_id {
+        return Err(ContractError::InvalidRolloverTarget {});
+    }
+    let successor: PoolDetailsResponse = deps.querier.query_wasm_smart(&target_pool, &QueryMsg::AllDetails {})?;
+    if successor.config.asset != pool_config.asset
+        || successor.config.collateral != pool_config.collateral
+        || successor.config.maturationdate <= pool_config.maturationdate
+    {
+        return Err(ContractError::InvalidRolloverTarget {});
+    }
 
-    let mut total_asset_available = TOTAL_ASSET_AVAILABLE.load(deps.storage)?;
-    total_asset_available -= withdraw_amount;
-    TOTAL_ASSET_AVAILABLE.save(deps.storage, &total_asset_available)?;
+    let principal = load_loan(deps.storage, loan_id)?.principal;
+    let (amount_due, collateral) = book_repay(deps.storage, &pool_config, env.block.time.seconds(), &info.sender, loan_id, principal)?;
+    if collateral.is_zero() {
+        return Err(ContractError::InsufficientCollateral {});
+    }
 
-    // Preparing the msg for transferring the funds here.
-    // We need to send this msg to the cw20 contract to transfer funds from the  contract's account to user's account
-    let transfer_msg = Cw20ExecuteMsg::Transfer {
-        recipient : info.sender.clone().to_string(),
-        amount: withdraw_amount,
+    PENDING_ROLLOVER.save(deps.storage, &PendingRollover {
+        loan_id,
+        holder: info.sender.clone(),
+        target_pool: target_pool.clone(),
+        amount_due,
+        repaid: Uint128::zero(),
+    })?;
+
+    let borrower = info.sender.to_string();
+    let accept_msg = match collateral_config.cw20_address() {
+        None => WasmMsg::Execute {
+            contract_addr: target_pool.to_string(),
+            msg: to_json_binary(&ExecuteMsg::Transact(TransactMsg::AcceptRollover { borrower, asset_amount: amount_due }))?,
+            funds: vec![Coin { denom: collateral_config.denom, amount: collateral }],
+        },
+        Some(cw20_addr) => WasmMsg::Execute {
+            contract_addr: cw20_addr.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Send {
+                contract: target_pool.to_string(),
+                amount: collateral,
+                msg: to_json_binary(&ReceiveMsg::AcceptRollover { borrower, asset_amount: amount_due })?,
+            })?,
+            funds: vec![],
+        },
     };
 
-    let msg = SubMsg::new(WasmMsg::Execute {
-        contract_addr: asset_config.denom.clone().to_string(),
-        msg: to_json_binary(&transfer_msg)?,
-        funds: vec![],
-    });
-
     Ok(Response::new()
-    .add_attribute("action", "withdraw")
-    .add_submessage(msg))
+    .add_attribute("action", "rollover")
+    .add_attribute("loan_id", loan_id.to_string())
+    .add_attribute("target_pool", target_pool)
+    .add_attribute("amount_due", amount_due)
+    .add_attribute("collateral", collateral)
+    .add_submessage(SubMsg::reply_on_success(accept_msg, ROLLOVER_REPLY_ID)))
 }
 
-fn execute_withdraw_interest(
+fn execute_repay_rollover(
     deps: DepsMut,
-    _env: Env,
     info: MessageInfo,
 ) -> ContractResult<Response> {
-    // This nonpayable function ensures that no coins are sent to the contract
-    nonpayable(&info);
+    // cw20 asset comes with the repay rollover hook instead
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    if asset_config.cw20_address().is_some() {
+        return Err(ContractError::InvalidAsset {});
+    }
+    let amount = must_pay(&info, &asset_config.denom)?;
 
-    let interest_earned_by_user = INTEREST_EARNED.may_load(deps.storage, &info.sender)?.unwrap_or(Uint128::zero());
+    book_rollover_repayment(deps.storage, &info.sender, amount)
+}
 
-    // TODO: could have used a 'revert if no interest' here
+// Docs:
+// Books what the successor pool pays back for the rollover in progress, the reply checks it covers the debt.
+// There is nothing to repay outside of a rollover and nobody but its successor can repay it
+fn book_rollover_repayment(
+    storage: &mut dyn Storage,
+    sender: &Addr,
+    amount: Uint128,
+) -> ContractResult<Response> {
+    let mut rollover = PENDING_ROLLOVER.may_load(storage)?.ok_or(ContractError::Unauthorized {})?;
+    if rollover.target_pool != sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    rollover.repaid = rollover.repaid.checked_add(amount)?;
+    PENDING_ROLLOVER.save(storage, &rollover)?;
 
-    INTEREST_EARNED.save(deps.storage, &info.sender, &Uint128::zero())?;
+    Ok(Response::new()
+    .add_attribute("action", "repay_rollover")
+    .add_attribute("loan_id", rollover.loan_id.to_string())
+    .add_attribute("amount", amount))
+}
 
-    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
-    
-    // Preparing the msg for transferring the funds here.
-    // We need to send this msg to the cw20 contract to transfer funds from the contract's account to user's account
-    let transfer_msg = Cw20ExecuteMsg::Transfer {
-        recipient : info.sender.clone().to_string(),
-        amount: interest_earned_by_user,
-    };
+fn execute_allow_rollover(
+    deps: DepsMut,
+    info: MessageInfo,
+    from_pool: Option<String>,
+) -> ContractResult<Response> {
+    // This nonpayable function ensures that no coins are sent to the contract
+    nonpayable(&info)?;
 
-    let msg = SubMsg::new(WasmMsg::Execute {
-        contract_addr: asset_config.denom.clone().to_string(),
-        msg: to_json_binary(&transfer_msg)?,
-        funds: vec![],
-    });
+    let response = Response::new()
+    .add_attribute("action", "allow_rollover")
+    .add_attribute("borrower", info.sender.clone());
 
-    Ok(Response::new()
-    .add_attribute("action", "withdraw_interest")
-    .add_submessage(msg))
+    match from_pool {
+        Some(pool) => {
+            let pool_addr = deps.api.addr_validate(&pool)?;
+            ROLLOVER_SOURCES.save(deps.storage, &info.sender, &pool_addr)?;
+            Ok(response.add_attribute("from_pool", pool_addr))
+        },
+        None => {
+            ROLLOVER_SOURCES.remove(deps.storage, &info.sender);
+            Ok(response.add_attribute("from_pool", "none"))
+        },
+    }
 }
 
-fn execute_loan(
+fn execute_accept_rollover(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    msg: LoanMsg,
-) -> Result<Response, ContractError> {
-    // This nonpayable function ensures that no coins are sent to the contract
-    nonpayable(&info);
+    borrower: String,
+    asset_amount: Uint128,
+) -> ContractResult<Response> {
+    // cw20 collateral comes with the accept rollover hook instead
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+    if collateral_config.cw20_address().is_some() {
+        return Err(ContractError::InvalidCollateral {});
+    }
+    let collateral = must_pay(&info, &collateral_config.denom)?;
 
-    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
-    let now = env.block.time.seconds();
+    accept_rollover(deps, env, info.sender, borrower, asset_amount, collateral)
+}
 
-    if now > pool_config.maturationdate {
-        return Err(ContractError::PoolMatured {});
+// Docs:
+// Loan opening shared by the native and the cw20 accept rollover flows.
+// It's an ordinary loan against the collateral the caller brings, held by `borrower` and paid back to the caller
+// with RepayRollover, the only message the caller takes while it waits for it.
+// The borrower has to have allowed rollovers from the caller, the pool their loan is in,
+// so nobody else can put debt on them. The origination fee is added on top of the loan
+// so that what is paid out covers `asset_amount`, and all of the collateral is kept on the loan.
+fn accept_rollover(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    borrower: String,
+    asset_amount: Uint128,
+    collateral: Uint128,
+) -> ContractResult<Response> {
+    assert_not_paused(deps.storage, Operation::Loan)?;
+
+    let borrower = deps.api.addr_validate(&borrower)?;
+    if ROLLOVER_SOURCES.may_load(deps.storage, &borrower)?.as_ref() != Some(&sender) {
+        return Err(ContractError::Unauthorized {});
     }
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
 
-    if pool_config.overcollateralizationfactor < Uint128::new(1) {
-        return Err(ContractError::InsufficientOCF {});
+    let paid_out_share = Decimal::one().checked_sub(fee_config(deps.storage)?.origination_fee)?;
+    let principal = asset_amount.checked_div_ceil(paid_out_share).map_err(|_| ContractError::Overflow {})?;
+    let collateral_needed = calculate_collateral_amount(principal, pool_config.strikeprice, pool_config.overcollateralizationfactor)?;
+    if collateral < collateral_needed {
+        return Err(ContractError::InsufficientCollateral {});
     }
 
-    let tokens_details: LoanMsg = from_json(&to_json_binary(&msg)?)?;
+    let (loan_id, fee) = book_loan(deps.storage, &pool_config, env.block.time.seconds(), &borrower, principal, collateral)?;
+    assert_healthy(deps.as_ref(), &env, loan_id)?;
+
     let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
-    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+    let paid_out = principal.checked_sub(fee)?;
+    let repay_msg = match asset_config.cw20_address() {
+        None => WasmMsg::Execute {
+            contract_addr: sender.to_string(),
+            msg: to_json_binary(&ExecuteMsg::Transact(TransactMsg::RepayRollover {}))?,
+            funds: vec![Coin { denom: asset_config.denom, amount: paid_out }],
+        },
+        Some(cw20_addr) => WasmMsg::Execute {
+            contract_addr: cw20_addr.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Send {
+                contract: sender.to_string(),
+                amount: paid_out,
+                msg: to_json_binary(&ReceiveMsg::RepayRollover {})?,
+            })?,
+            funds: vec![],
+        },
+    };
 
-    if asset_config.denom != tokens_details.asset_denom {
-        return Err(ContractError::InvalidAsset {});
-    } 
-    if collateral_config.denom != tokens_details.collateral_denom {
-        return Err(ContractError::InvalidCollateral {});
+    Ok(Response::new()
+    .add_attribute("action", "accept_rollover")
+    .add_attribute("loan_id", loan_id.to_string())
+    .add_attribute("borrower", borrower)
+    .add_attribute("fee", fee)
+    .add_message(repay_msg))
+}
+
+// Docs:
+// Makes sure a loan isn't opened or left below the liquidation threshold at the oracle price
+fn assert_healthy(
+    deps: Deps,
+    env: &Env,
+    loan_id: u64,
+) -> ContractResult<()> {
+    let liquidation_config = LIQUIDATION_CONFIG.load(deps.storage)?;
+    let pool_config = POOL_CONFIG.load(deps.storage)?;
+    let loan = load_loan(deps.storage, loan_id)?;
+
+    let debt = current_debt(deps.storage, &pool_config, env.block.time.seconds(), &loan)?;
+    if debt.is_zero() {
+        return Ok(());
     }
 
-    let mut total_asset_available = TOTAL_ASSET_AVAILABLE.load(deps.storage)?;
-    if total_asset_available < tokens_details.asset_amount {
-        return Err(ContractError::InsufficientFunds {});
+    let collateral_value = collateral_value(deps, &loan)?;
+    if Decimal::checked_from_ratio(collateral_value, debt)? < liquidation_config.threshold {
+        return Err(ContractError::Undercollateralized {});
     }
+    Ok(())
+}
 
-    // This contract needs to check if the user has given enough allowance to this contract to transfer funds from collateral cw20 token
-    // collateral config is the configuration of the collateral that the user wants to stake
-    // check with collateral cw20 if user has given allowance to this contract to transfer funds
-    let allowance_and_expiry: AllowanceResponse = fetch_allowance(
-        deps.as_ref(),
-        env.contract.address.clone(),
-        info.sender.clone(),
-        collateral_config.denom.clone(),
-    )?;
+// Docs:
+// Anyone can repay part of the debt of a loan below the liquidation threshold
+// and take the matching collateral at the oracle price plus the liquidation bonus.
+// At most close factor of the debt is repaid in one go, and never more than the collateral covers,
+// native coins above that are refunded and cw20 tokens are pulled only for what is repaid.
+fn execute_liquidate(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    loan_id: u64,
+    repay_amount: Uint128,
+) -> ContractResult<Response> {
+    assert_not_paused(deps.storage, Operation::Liquidate)?;
 
-    // calculate the needful
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
+    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
 
-    let (overall_collateral_submitted_by_user, last_collateral_time) = COLLATERAL_SUBMITTED.may_load(deps.storage, &info.sender)?.unwrap_or((Uint128::zero(), Timestamp::from_seconds(0)));
-    let (overall_principle_to_repay_by_user, last_principle_time) = PRINCIPLE_TO_REPAY.may_load(deps.storage, &info.sender)?.unwrap_or((Uint128::zero(), Timestamp::from_seconds(0)));
-    let overall_interest_to_repay_by_user = INTEREST_TO_REPAY.may_load(deps.storage, &info.sender)?.unwrap_or(Uint128::zero());
+    let liquidation = book_liquidation(deps.branch(), &env, loan_id, repay_amount)?;
+    let funds_msgs = collect_funds(deps.as_ref(), &env, &info, &asset_config, liquidation.repaid)?;
+    let (queue_paid, queue_msgs) = pay_queued_withdrawals(deps.storage, &asset_config)?;
 
-    if last_collateral_time != last_principle_time {
-        return Err(ContractError::InvalidState {});
-    }
+    Ok(liquidation.response(loan_id, &collateral_config, &info.sender)?
+    .add_attribute("queue_paid", queue_paid)
+    .add_submessages(funds_msgs)
+    .add_submessages(queue_msgs))
+}
 
-    // calculate the collateral needed for current loan
+// What a liquidation repaid and seized, and the collateral it left the holder
+struct Liquidation {
+    repaid: Uint128,
+    collateral_seized: Uint128,
+    holder: Addr,
+    collateral_released: Uint128,
+}
 
-    let new_collateral_needed = calculate_collateral_amount(tokens_details.asset_amount, pool_config.strikeprice, pool_config.overcollateralizationfactor);
+impl Liquidation {
+    fn response(&self, loan_id: u64, collateral_config: &CoinConfig, liquidator: &Addr) -> ContractResult<Response> {
+        Ok(Response::new()
+        .add_attribute("action", "liquidate")
+        .add_attribute("loan_id", loan_id.to_string())
+        .add_attribute("repaid", self.repaid)
+        .add_attribute("collateral_seized", self.collateral_seized)
+        .add_attribute("collateral_released", self.collateral_released)
+        .add_submessages(payout_msgs(collateral_config, liquidator, self.collateral_seized)?)
+        .add_submessages(payout_msgs(collateral_config, &self.holder, self.collateral_released)?))
+    }
+}
 
-    // check if the user has approved collateral_needed amount
+// Docs:
+// Liquidation accounting shared by the allowance and the cw20 receive flows,
+// the repayment goes to the accrued interest first and then to the principal.
+// Returns the asset amount actually repaid and the collateral seized for it.
+// A liquidation that clears the debt closes the loan, whatever collateral is left goes back to the holder.
+fn book_liquidation(
+    deps: DepsMut,
+    env: &Env,
+    loan_id: u64,
+    repay_amount: Uint128,
+) -> ContractResult<Liquidation> {
+    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let liquidation_config = LIQUIDATION_CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
 
-    if allowance_and_expiry.allowance < new_collateral_needed {
-        return Err(ContractError::InsufficientAllowance {});
-    }
-    if allowance_and_expiry.expires.is_expired(&env.block) {
-        return Err(ContractError::AllowanceExpired {});
+    // after maturity the collateral is forfeited to the lenders through settlement
+    if now > pool_config.maturationdate {
+        return Err(ContractError::PoolMatured {});
     }
 
-    // calculating new position interest
-
-    let old_time_period = get_time_period(Timestamp::from_seconds(now), last_principle_time);
-    let interest_on_old_principle = calculate_simple_interest(overall_principle_to_repay_by_user, pool_config.debtinterestrate, old_time_period);
-    
-    INTEREST_TO_REPAY.save(deps.storage, &info.sender, &(overall_interest_to_repay_by_user + interest_on_old_principle))?;
-    PRINCIPLE_TO_REPAY.save(deps.storage, &info.sender, &(overall_principle_to_repay_by_user + tokens_details.asset_amount, Timestamp::from_seconds(now)))?;
-    COLLATERAL_SUBMITTED.save(deps.storage, &info.sender, &(overall_collateral_submitted_by_user + new_collateral_needed, Timestamp::from_seconds(now)))?;
+    let mut pool_state = accrue(deps.storage, &pool_config, now)?;
+    let mut position = load_loan(deps.storage, loan_id)?;
+    let debt = position.debt(&pool_state)?;
+    if debt.is_zero() {
+        return Err(ContractError::PositionNotAvailable {});
+    }
 
-    total_asset_available -= tokens_details.asset_amount;
-    TOTAL_ASSET_AVAILABLE.save(deps.storage, &total_asset_available)?;
+    let oracle = ORACLE.load(deps.storage)?;
+    let asset_config = ASSET_CONFIG.load(deps.storage)?;
+    let collateral_config = COLLATERAL_CONFIG.load(deps.storage)?;
+    let price = query_price(&deps.querier, &oracle, &collateral_config.denom, &asset_config.denom)?;
 
-    let mut total_collateral_available = TOTAL_COLLATERAL_AVAILABLE.load(deps.storage)?;
-    total_collateral_available += new_collateral_needed;
-    TOTAL_COLLATERAL_AVAILABLE.save(deps.storage, &total_collateral_available)?;
+    let collateral_submitted = position.collateral;
+    let collateral_value = collateral_submitted.checked_mul_floor(price)?;
 
-    // Now we need to firstly transfer the collateral from the user's account to the contract account
-    // then we need to transfer the asset from the contract account to the user's account
+    if Decimal::checked_from_ratio(collateral_value, debt)? >= liquidation_config.threshold {
+        return Err(ContractError::PositionHealthy {});
+    }
 
-    // Preparing the msg for transferring the funds here.
-    // Using Transfer since contract's account is to be used by contract itself
-    // We need to send this msg to the cw20 contract to transfer funds from the user's account to this contract account
-    let collateral_transfer_request = Cw20ExecuteMsg::TransferFrom {
-        owner: info.sender.clone().to_string(),
-        recipient: env.contract.address.clone().to_string(),
-        amount: new_collateral_needed,
+    // collateral paid per unit of asset repaid
+    let bonus_factor = Decimal::one().checked_add(liquidation_config.bonus)?;
+    let mut repaid = std::cmp::min(repay_amount, debt.checked_mul_floor(liquidation_config.close_factor)?);
+    let mut collateral_seized = match bonus_factor.checked_div(price) {
+        Ok(collateral_per_asset) => repaid.checked_mul_floor(collateral_per_asset)?,
+        Err(_) => collateral_submitted,
     };
 
-    let collateral_msg = SubMsg::new(WasmMsg::Execute {
-        contract_addr: collateral_config.denom.clone().to_string(),
-        msg: to_json_binary(&collateral_transfer_request)?,
-        funds: vec![],
-    });
+    // once the collateral doesn't cover the debt plus bonus, all of it goes for what it's worth
+    if collateral_seized > collateral_submitted {
+        collateral_seized = collateral_submitted;
+        repaid = collateral_value.checked_div_floor(bonus_factor)?;
+    }
 
-    // Preparing the msg for transferring the funds here.
-    // We need to send this msg to the cw20 contract to transfer funds from the contract's account to this user's account
-    let asset_transfer_request = Cw20ExecuteMsg::Transfer {
-        recipient: info.sender.clone().to_string(),
-        amount: tokens_details.asset_amount,
-    };
+    if repaid.is_zero() {
+        return Err(ContractError::InsufficientFunds {});
+    }
 
-    let asset_msg = SubMsg::new(WasmMsg::Execute {
-        contract_addr: asset_config.denom.clone().to_string(),
-        msg: to_json_binary(&asset_transfer_request)?,
-        funds: vec![],
-    });
+    let interest_to_repay = debt.saturating_sub(position.principal);
+    let interest_repaid = std::cmp::min(repaid, interest_to_repay);
+    let principle_repaid = std::cmp::min(repaid.checked_sub(interest_repaid)?, position.principal);
+    let scaled_repaid = std::cmp::min(repaid.checked_div_floor(pool_state.borrow_index(position.rate))?, position.scaled_debt);
+
+    position.principal = position.principal.checked_sub(principle_repaid)?;
+    position.scaled_debt = position.scaled_debt.checked_sub(scaled_repaid)?;
+    position.collateral = position.collateral.checked_sub(collateral_seized)?;
+    let collateral_released = if position.scaled_debt.is_zero() {
+        remove_loan(deps.storage, loan_id)?;
+        position.collateral
+    } else {
+        loans().save(deps.storage, loan_id, &position)?;
+        Uint128::zero()
+    };
 
-    Ok(Response::new()
-    .add_attribute("action", "loan")
-    .add_submessage(collateral_msg)
-    .add_submessage(asset_msg))
+    pool_state.sub_scaled_debt(position.rate, scaled_repaid)?;
+    pool_state.total_borrowed = pool_state.total_borrowed.checked_sub(principle_repaid)?;
+    POOL_STATE.save(deps.storage, &pool_state)?;
 
-}
+    let reserve = interest_repaid.checked_mul_floor(fee_config(deps.storage)?.reserve_factor)?;
+    book_protocol_earnings(deps.storage, reserve)?;
 
+    let total_asset_available = TOTAL_ASSET_AVAILABLE.load(deps.storage)?;
+    TOTAL_ASSET_AVAILABLE.save(deps.storage, &(total_asset_available.checked_add(repaid.checked_sub(reserve)?)?))?;
 
-// Just like in borrow there's a transfer of two tokens,
-// here also there's a transfer of two tokens
-// wherein, this time it's reversed,
-// user sends asset sum of principal and interest to the pool 
-// and receives collateral in return
-// Let's assume it to be full repay on the UI level
-// This function is used to repay the loan taken by the user
+    let total_collateral_available = TOTAL_COLLATERAL_AVAILABLE.load(deps.storage)?;
+    let t_c_a = total_collateral_available.checked_sub(collateral_seized)?.checked_sub(collateral_released)?;
+    TOTAL_COLLATERAL_AVAILABLE.save(deps.storage, &t_c_a)?;
 
+    Ok(Liquidation { repaid, collateral_seized, holder: position.borrower, collateral_released })
+}
 
-fn execute_repay(
+// Docs:
+// Lets the admin retune the pool, each field that is set gets validated and emitted as an attribute.
+// Existing lenders are protected: maturity can only be pushed back and lock in can only be lowered.
+fn execute_update_config(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    msg : RepayMsg,
+    msg: UpdateConfigMsg,
 ) -> ContractResult<Response> {
-    // This nonpayable function ensures that no coins are sent to the contract
-    nonpayable(&info);
+    nonpayable(&info)?;
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
 
-    let pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
+    let mut pool_config: PoolConfig = POOL_CONFIG.load(deps.storage)?;
     let now = env.block.time.seconds();
 
-    if now > pool_config.maturationdate {
-        return Err(ContractError::CollateralForfeited {});
+    if SETTLEMENT.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::PoolSettled {});
     }
 
-    if pool_config.overcollateralizationfactor < Uint128::new(1) {
-        return Err(ContractError::InsufficientOCF {});
+    // the interest until now is accrued at the rates it was promised at
+    accrue(deps.storage, &pool_config, now)?;
+
+    let mut response = Response::new().add_attribute("action", "update_config");
+
+    if let Some(debtinterestrate) = msg.debtinterestrate {
+        pool_config.debtinterestrate = debtinterestrate;
+        response = response.add_attribute("debtinterestrate", debtinterestrate.to_string());
+    }
+    if let Some(lendinterestrate) = msg.lendinterestrate {
+        pool_config.lendinterestrate = lendinterestrate;
+        response = response.add_attribute("lendinterestrate", lendinterestrate.to_string());
     }
 
-    let tokens_details: RepayMsg = from_json(&to_json_binary(&msg)?)?;
-    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
-    let collateral_config: CoinConfig = COLLATERAL_CONFIG.load(deps.storage)?;
+    if let Some(overcollateralizationfactor) = msg.overcollateralizationfactor {
+        pool_config.overcollateralizationfactor = overcollateralizationfactor;
+        response = response.add_attribute("overcollateralizationfactor", overcollateralizationfactor.to_string());
+    }
 
-    if asset_config.denom != tokens_details.asset_denom {
-        return Err(ContractError::InvalidAsset {});
-    } 
-    if collateral_config.denom != tokens_details.collateral_denom {
-        return Err(ContractError::InvalidCollateral {});
+    if let Some(strikeprice) = msg.strikeprice {
+        pool_config.strikeprice = strikeprice;
+        response = response.add_attribute("strikeprice", strikeprice.to_string());
     }
 
-    let (overall_collateral_submitted_by_user, last_collateral_time) = COLLATERAL_SUBMITTED.may_load(deps.storage, &info.sender)?.unwrap_or((Uint128::zero(), Timestamp::from_seconds(0)));
-    let (overall_principle_to_repay_by_user, last_principle_time) = PRINCIPLE_TO_REPAY.may_load(deps.storage, &info.sender)?.unwrap_or((Uint128::zero(), Timestamp::from_seconds(0)));
-    let interest_to_repay_by_user_yet = INTEREST_TO_REPAY.may_load(deps.storage, &info.sender)?.unwrap_or(Uint128::zero());
+    if let Some(lock_in_period) = msg.lock_in_period {
+        if lock_in_period > pool_config.lock_in_period {
+            return Err(ContractError::InvalidConfig { reason: "lock in period can only be lowered".to_string() });
+        }
+        pool_config.lock_in_period = lock_in_period;
+        response = response.add_attribute("lock_in_period", lock_in_period);
+    }
 
-    // TODO: what if somehow - (define) some collateral is left
-    if interest_to_repay_by_user_yet == Uint128::zero() && overall_principle_to_repay_by_user == Uint128::zero() {
-        return Err(ContractError::PositionNotAvailable {});
+    if let Some(maturationdate) = msg.maturationdate {
+        if now > pool_config.maturationdate {
+            return Err(ContractError::PoolMatured {});
+        }
+        if maturationdate < pool_config.maturationdate {
+            return Err(ContractError::InvalidConfig { reason: "maturity can only be pushed back".to_string() });
+        }
+        pool_config.maturationdate = maturationdate;
+        response = response.add_attribute("maturationdate", maturationdate.to_string());
     }
 
-    if last_collateral_time != last_principle_time {
-        return Err(ContractError::InvalidState {});
+    if let Some(oracle) = msg.oracle {
+        let oracle_addr = deps.api.addr_validate(&oracle)?;
+        ORACLE.save(deps.storage, &oracle_addr)?;
+        response = response.add_attribute("oracle", oracle_addr);
     }
 
-    let current_time_period = get_time_period(Timestamp::from_seconds(now), last_principle_time);
-    let interest_on_current_principle = calculate_simple_interest(overall_principle_to_repay_by_user, pool_config.debtinterestrate, current_time_period);
+    if let Some(liquidation) = msg.liquidation {
+        validate_liquidation_config(&liquidation)?;
+        LIQUIDATION_CONFIG.save(deps.storage, &liquidation)?;
+        response = response
+            .add_attribute("liquidation_threshold", liquidation.threshold.to_string())
+            .add_attribute("liquidation_bonus", liquidation.bonus.to_string())
+            .add_attribute("close_factor", liquidation.close_factor.to_string());
+    }
 
-    let total_interest_to_pay = interest_to_repay_by_user_yet + interest_on_current_principle;
-    let total_collateral_to_unlock = calculate_collateral_amount(overall_principle_to_repay_by_user, pool_config.strikeprice, pool_config.overcollateralizationfactor);
+    let mut fees = fee_config(deps.storage)?;
+    if let Some(new_fees) = msg.fees {
+        fees = new_fees;
+        response = response
+            .add_attribute("reserve_factor", fees.reserve_factor.to_string())
+            .add_attribute("origination_fee", fees.origination_fee.to_string());
+    }
 
-    // I am fine with user repaying lesser than they have debt for since it's can also be a partial repayment.
+    // the fees are checked against the new rates as well
+    validate_pool_config(&pool_config)?;
+    validate_fee_config(&fees, &pool_config)?;
+    POOL_CONFIG.save(deps.storage, &pool_config)?;
+    FEE_CONFIG.save(deps.storage, &fees)?;
 
-    // checking approval for contract to take user's tokens and making transaction message
-    let allowance_and_expiry: AllowanceResponse = fetch_allowance(
-        deps.as_ref(),
-        env.contract.address.clone(),
-        info.sender.clone(),
-        asset_config.denom.clone(),
-    )?;
+    Ok(response)
+}
 
-    if allowance_and_expiry.expires.is_expired(&env.block) {
-        return Err(ContractError::AllowanceExpired {});
-    }
+// Docs:
+// Pays out everything the protocol earned so far, the earnings were never part of the
+// asset available so this doesn't touch what lenders are owed
+fn execute_collect_protocol_fees(
+    deps: DepsMut,
+    info: MessageInfo,
+    recipient: String,
+) -> ContractResult<Response> {
+    nonpayable(&info)?;
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
 
-    let loan_user_is_repaying = tokens_details.asset_principle;
-    let appropriate_collateral_to_unlock = (total_collateral_to_unlock * loan_user_is_repaying) / overall_principle_to_repay_by_user;
-    let interest_user_has_to_pay = calculate_simple_interest(loan_user_is_repaying, pool_config.debtinterestrate, current_time_period) + interest_to_repay_by_user_yet;
-    
-    if allowance_and_expiry.allowance < loan_user_is_repaying + interest_user_has_to_pay {
-        return Err(ContractError::InsufficientAllowance {});
-    }
+    let recipient = deps.api.addr_validate(&recipient)?;
+    let earnings = TOTAL_PROTOCOL_EARNINGS.may_load(deps.storage)?.unwrap_or_default();
+    TOTAL_PROTOCOL_EARNINGS.save(deps.storage, &Uint128::zero())?;
 
-    let i_t_r = total_interest_to_pay - interest_user_has_to_pay;
-    let p_t_r =overall_principle_to_repay_by_user - loan_user_is_repaying;
-    let c_s = overall_collateral_submitted_by_user - appropriate_collateral_to_unlock;
+    let asset_config: CoinConfig = ASSET_CONFIG.load(deps.storage)?;
 
-    INTEREST_TO_REPAY.save(deps.storage, &info.sender, &i_t_r)?;
-    PRINCIPLE_TO_REPAY.save(deps.storage, &info.sender, &(p_t_r, Timestamp::from_seconds(now)))?;
-    COLLATERAL_SUBMITTED.save(deps.storage, &info.sender, &(c_s, Timestamp::from_seconds(now)))?;
+    Ok(Response::new()
+    .add_attribute("action", "collect_protocol_fees")
+    .add_attribute("recipient", recipient.to_string())
+    .add_attribute("amount", earnings)
+    .add_submessages(payout_msgs(&asset_config, &recipient, earnings)?))
+}
 
-    let mut total_asset_available = TOTAL_ASSET_AVAILABLE.load(deps.storage)?;
-    total_asset_available += loan_user_is_repaying + interest_user_has_to_pay;
-    TOTAL_ASSET_AVAILABLE.save(deps.storage, &total_asset_available)?;
+// Docs:
+// First step of handing the pool over, nothing changes until the proposed admin accepts,
+// so a typo in the address can't lock the admin out
+fn execute_propose_admin(
+    deps: DepsMut,
+    info: MessageInfo,
+    admin: String,
+) -> ContractResult<Response> {
+    nonpayable(&info)?;
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
 
-    let total_collateral_available = TOTAL_COLLATERAL_AVAILABLE.load(deps.storage)?;
-    let t_c_a = total_collateral_available - appropriate_collateral_to_unlock;
-    TOTAL_COLLATERAL_AVAILABLE.save(deps.storage, &t_c_a)?;
+    let pending_admin = deps.api.addr_validate(&admin)?;
+    PENDING_ADMIN.save(deps.storage, &pending_admin)?;
 
-  
-    // Transfer the tokens
-    let collateral_transfer_request = Cw20ExecuteMsg::Transfer {
-        recipient: info.sender.clone().to_string(),
-        amount: appropriate_collateral_to_unlock,
-    };
+    Ok(Response::new()
+    .add_attribute("action", "propose_admin")
+    .add_attribute("pending_admin", pending_admin))
+}
 
-    let collateral_msg = SubMsg::new(WasmMsg::Execute {
-        contract_addr: collateral_config.denom.clone().to_string(),
-        msg: to_json_binary(&collateral_transfer_request)?,
-        funds: vec![],
-    });
+fn execute_accept_admin(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> ContractResult<Response> {
+    nonpayable(&info)?;
 
-    let x = loan_user_is_repaying + interest_user_has_to_pay;
+    let pending_admin = PENDING_ADMIN.may_load(deps.storage)?;
+    if pending_admin.as_ref() != Some(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
 
-    // Preparing the msg for transferring the funds here.
-    // We need to send this msg to the cw20 contract to transfer funds from the contract's account to this user's account
-    let asset_transfer_request = Cw20ExecuteMsg::TransferFrom  { 
-        owner: info.sender.clone().to_string(),
-        recipient: env.contract.address.clone().to_string(),
-        amount: x
-    };
+    PENDING_ADMIN.remove(deps.storage);
+    ADMIN.set(deps, Some(info.sender.clone()))?;
 
-    let asset_msg = SubMsg::new(WasmMsg::Execute {
-        contract_addr: asset_config.denom.clone().to_string(),
-        msg: to_json_binary(&asset_transfer_request)?,
-        funds: vec![],
-    });
+    Ok(Response::new()
+    .add_attribute("action", "accept_admin")
+    .add_attribute("admin", info.sender))
+}
+
+fn execute_set_guardian(
+    deps: DepsMut,
+    info: MessageInfo,
+    guardian: Option<String>,
+) -> ContractResult<Response> {
+    nonpayable(&info)?;
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+
+    let response = Response::new().add_attribute("action", "set_guardian");
+    match guardian {
+        Some(guardian) => {
+            let guardian_addr = deps.api.addr_validate(&guardian)?;
+            GUARDIAN.save(deps.storage, &guardian_addr)?;
+            Ok(response.add_attribute("guardian", guardian_addr))
+        }
+        None => {
+            GUARDIAN.remove(deps.storage);
+            Ok(response.add_attribute("guardian", "none"))
+        }
+    }
+}
+
+// Docs:
+// Halts or resumes operations when a token contract or the oracle misbehaves.
+// Repaying, topping up and rolling loans over can't be paused by anyone,
+// so borrowers are never trapped while liquidations or maturity catch up with them.
+fn execute_update_pause(
+    deps: DepsMut,
+    info: MessageInfo,
+    operations: Vec<Operation>,
+    paused: bool,
+) -> ContractResult<Response> {
+    nonpayable(&info)?;
+
+    let is_admin = ADMIN.is_admin(deps.as_ref(), &info.sender)?;
+    let is_guardian = GUARDIAN.may_load(deps.storage)?.as_ref() == Some(&info.sender);
+    if !is_admin && !is_guardian {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut pause_state = PAUSE_STATE.may_load(deps.storage)?.unwrap_or_default();
+    for operation in &operations {
+        pause_state.set(*operation, paused);
+    }
+    PAUSE_STATE.save(deps.storage, &pause_state)?;
+
+    let operations = operations.iter().map(|operation| operation.to_string()).collect::<Vec<_>>();
 
     Ok(Response::new()
-    .add_attribute("action", "repay")
-    .add_submessage(asset_msg)
-    .add_submessage(collateral_msg))    
+    .add_attribute("action", if paused { "pause" } else { "unpause" })
+    .add_attribute("operations", operations.join(",")))
+}
 
-}
\ No newline at end of file
+fn assert_not_paused(
+    storage: &dyn Storage,
+    operation: Operation,
+) -> ContractResult<()> {
+    let pause_state = PAUSE_STATE.may_load(storage)?.unwrap_or_default();
+    if pause_state.is_paused(operation) {
+        return Err(ContractError::Paused { operation: operation.to_string() });
+    }
+    Ok(())
+}
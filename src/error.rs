@@ -1,4 +1,9 @@
-use cosmwasm_std::{StdError};
+use cosmwasm_std::{
+    CheckedFromRatioError, CheckedMultiplyFractionError, CheckedMultiplyRatioError, DivideByZeroError, OverflowError,
+    StdError,
+};
+use cw_controllers::AdminError;
+use cw_utils::{ParseReplyError, PaymentError};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -6,6 +11,15 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("{0}")]
+    ParseReply(#[from] ParseReplyError),
+
+    #[error("Bank Contract : Unknown reply id {id}")]
+    UnknownReply { id: u64 },
+
     #[error("Bank Contract : Invalid Asset")]
     InvalidAsset {},
 
@@ -36,9 +50,27 @@ pub enum ContractError {
     #[error("Bank Contract : Pool has not matured yet")]
     PoolNotMatured {},
 
+    #[error("Bank Contract : Pool has been settled, redeem the position instead")]
+    PoolSettled {},
+
+    #[error("Bank Contract : Pool has not been settled yet")]
+    PoolNotSettled {},
+
     #[error("Bank Contract : Position is undercollateralized")]
     Undercollateralized {},
 
+    #[error("Bank Contract : Position is not undercollateralized")]
+    PositionHealthy {},
+
+    #[error("Bank Contract : Invalid config, {reason}")]
+    InvalidConfig { reason: String },
+
+    #[error("Bank Contract : {operation} is paused")]
+    Paused { operation: String },
+
+    #[error("Bank Contract : Cannot migrate from {contract} {version}")]
+    InvalidMigration { contract: String, version: String },
+
     #[error("Bank Contract : Overflow")]
     Overflow {},
 
@@ -48,9 +80,6 @@ pub enum ContractError {
     #[error("Bank Contract : Pool expired and collateral has been forfeited")]
     CollateralForfeited {},
 
-    #[error("Bank Contract : Invalid state detected")]
-    InvalidState {},
-
     #[error("Bank Contract : Insufficient over-collateralization factor")]
     InsufficientOCF {},
 
@@ -63,11 +92,64 @@ pub enum ContractError {
     #[error("Bank Contract : Position is not available for this operation")]
     PositionNotAvailable {},
 
-    #[error("Bank Contract : Option expired (expired at {expired:?})")]
-    OptionExpired { expired: u64 },
+    #[error("Bank Contract : Withdrawals are queued, they are paid before any interest")]
+    WithdrawalsQueued {},
+
+    #[error("Bank Contract : Rollover target must be a pool of the same code lending the same asset against the same collateral and maturing later")]
+    InvalidRolloverTarget {},
+
+    #[error("Bank Contract : A rollover is waiting for the successor pool's repayment")]
+    RolloverPending {},
+}
+
+impl From<AdminError> for ContractError {
+    fn from(err: AdminError) -> Self {
+        match err {
+            AdminError::Std(err) => ContractError::Std(err),
+            AdminError::NotAdmin {} => ContractError::Unauthorized {},
+        }
+    }
+}
+
+// Every arithmetic failure surfaces as Overflow, amounts that large or divisions
+// by zero can only come from inputs the pool can't book
+impl From<OverflowError> for ContractError {
+    fn from(_: OverflowError) -> Self {
+        ContractError::Overflow {}
+    }
+}
+
+impl From<DivideByZeroError> for ContractError {
+    fn from(_: DivideByZeroError) -> Self {
+        ContractError::Overflow {}
+    }
+}
+
+impl From<CheckedMultiplyFractionError> for ContractError {
+    fn from(_: CheckedMultiplyFractionError) -> Self {
+        ContractError::Overflow {}
+    }
+}
+
+impl From<CheckedMultiplyRatioError> for ContractError {
+    fn from(_: CheckedMultiplyRatioError) -> Self {
+        ContractError::Overflow {}
+    }
+}
+
+impl From<CheckedFromRatioError> for ContractError {
+    fn from(_: CheckedFromRatioError) -> Self {
+        ContractError::Overflow {}
+    }
+}
 
-    #[error("Bank Contract : Option not expired (expires at {expires:?})")]
-    OptionNotExpired { expires: u64 },
+impl From<semver::SemVerError> for ContractError {
+    fn from(err: semver::SemVerError) -> Self {
+        ContractError::StdErr {
+            kind: "SemVer".to_string(),
+            detail: err.to_string(),
+        }
+    }
 }
 
 pub type ContractResult<T> = Result<T, ContractError>;
@@ -0,0 +1,33 @@
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Addr, Decimal, QuerierWrapper, StdResult};
+
+/// Query interface the pool expects from its price oracle
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum OracleQueryMsg {
+    /// Price of one unit of `base` expressed in units of `quote`,
+    /// both are denoms in the pool's format (native denom or `cw20:<address>`)
+    #[returns(PriceResponse)]
+    Price { base: String, quote: String },
+}
+
+#[cw_serde]
+pub struct PriceResponse {
+    pub price: Decimal,
+}
+
+pub fn query_price(
+    querier: &QuerierWrapper,
+    oracle: &Addr,
+    base: &str,
+    quote: &str,
+) -> StdResult<Decimal> {
+    let res: PriceResponse = querier.query_wasm_smart(
+        oracle,
+        &OracleQueryMsg::Price {
+            base: base.to_string(),
+            quote: quote.to_string(),
+        },
+    )?;
+    Ok(res.price)
+}
@@ -5,6 +5,7 @@ pub mod types;
 pub mod msg;
 pub mod state;
 pub mod external;
+pub mod migrations;
 // pub mod simple_fd;
 
 // // Transactions 
@@ -12,5 +13,5 @@ pub mod external;
 // pub mod withdraw;
 // pub mod borrow;
 // pub mod repay;
-// #[cfg(test)]
-// pub mod tests;
+#[cfg(test)]
+pub mod tests;
@@ -0,0 +1,255 @@
+use cosmwasm_std::{Addr, Decimal, DepsMut, Env, Order, StdResult, Timestamp, Uint128};
+use cw_storage_plus::{Item, Map};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::contract::validate_liquidation_config;
+use crate::error::{ContractError, ContractResult};
+use crate::msg::MigrateMsg;
+use crate::state::{
+    loans, ASSET_CONFIG, COLLATERAL_CONFIG, LENDERS, LIQUIDATION_CONFIG, LOAN_COUNT, ORACLE, POOL_CONFIG,
+    POOL_STATE, SECONDS_IN_YEAR,
+};
+use crate::types::{LenderPosition, Loan, PoolState};
+
+/// Version of the pools deployed before the contract recorded its cw2 version
+pub const LEGACY_VERSION: &str = "0.1.0";
+
+/// Per user positions before 0.3.0, interest was recomputed from the time of the last update
+pub const LEGACY_PRINCIPLE_DEPLOYED: Map<&Addr, (Uint128, Timestamp)> = Map::new("principle_deployed");
+pub const LEGACY_INTEREST_EARNED: Map<&Addr, Uint128> = Map::new("interest_earned");
+pub const LEGACY_PRINCIPLE_TO_REPAY: Map<&Addr, (Uint128, Timestamp)> = Map::new("principle_to_repay");
+pub const LEGACY_INTEREST_TO_REPAY: Map<&Addr, Uint128> = Map::new("interest_to_repay");
+pub const LEGACY_COLLATERAL_SUBMITTED: Map<&Addr, (Uint128, Timestamp)> = Map::new("collateral_deployed");
+
+/// The rates of the pool config before 0.4.0, in billionths per year
+#[derive(Serialize, Deserialize)]
+struct LegacyPoolRates {
+    debtinterestrate: Uint128,
+    lendinterestrate: Uint128,
+}
+const LEGACY_POOL_RATES: Item<LegacyPoolRates> = Item::new("pool_config");
+const LEGACY_RATE_UNITS: u128 = 1_000_000_000;
+
+/// The single position of every borrower before 0.5.0 split them into loans
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LegacyBorrowerPosition {
+    pub principal: Uint128,
+    pub scaled_debt: Uint128,
+    pub collateral: Uint128,
+}
+pub const LEGACY_BORROWERS: Map<&Addr, LegacyBorrowerPosition> = Map::new("borrowers");
+
+/// The pool state before 0.5.0 gave every loan a rate of its own, with a single borrow index
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyPoolState {
+    pub supply_index: Decimal,
+    pub borrow_index: Decimal,
+    pub last_accrued: u64,
+    pub total_scaled_supply: Uint128,
+    pub total_scaled_debt: Uint128,
+    pub total_supplied: Uint128,
+    pub total_borrowed: Uint128,
+}
+pub const LEGACY_POOL_STATE: Item<LegacyPoolState> = Item::new("pool_state");
+
+type Migration = fn(DepsMut, &Env, &MigrateMsg) -> ContractResult<()>;
+
+/// Every storage migration, keyed by the version that introduced the new layout, oldest first
+const MIGRATIONS: &[(&str, Migration)] = &[
+    ("0.2.0", migrate_to_v0_2_0),
+    ("0.3.0", migrate_to_v0_3_0),
+    ("0.4.0", migrate_to_v0_4_0),
+    ("0.5.0", migrate_to_v0_5_0),
+];
+
+// Docs:
+// Runs, in order, every migration introduced after `from` up to and including `to`
+pub fn run_migrations(
+    mut deps: DepsMut,
+    env: &Env,
+    from: &Version,
+    to: &Version,
+    msg: &MigrateMsg,
+) -> ContractResult<Vec<String>> {
+    let mut applied = vec![];
+    for (version, migration) in MIGRATIONS {
+        let version = Version::parse(version)?;
+        if &version > from && &version <= to {
+            migration(deps.branch(), env, msg)?;
+            applied.push(version.to_string());
+        }
+    }
+    Ok(applied)
+}
+
+// Docs:
+// 0.2.0 added native coins, the oracle and liquidations.
+// Legacy pools only knew cw20 tokens and stored their bare address as the denom,
+// they now need the `cw20:` prefix, and the oracle and liquidation config have to be provided.
+fn migrate_to_v0_2_0(deps: DepsMut, _env: &Env, msg: &MigrateMsg) -> ContractResult<()> {
+    let cw20_denom = |denom: String| {
+        if denom.starts_with("cw20:") {
+            denom
+        } else {
+            format!("cw20:{}", denom)
+        }
+    };
+
+    let mut pool_config = POOL_CONFIG.load(deps.storage)?;
+    pool_config.asset = cw20_denom(pool_config.asset);
+    pool_config.collateral = cw20_denom(pool_config.collateral);
+    POOL_CONFIG.save(deps.storage, &pool_config)?;
+
+    let mut asset_config = ASSET_CONFIG.load(deps.storage)?;
+    asset_config.denom = cw20_denom(asset_config.denom);
+    ASSET_CONFIG.save(deps.storage, &asset_config)?;
+
+    let mut collateral_config = COLLATERAL_CONFIG.load(deps.storage)?;
+    collateral_config.denom = cw20_denom(collateral_config.denom);
+    COLLATERAL_CONFIG.save(deps.storage, &collateral_config)?;
+
+    let oracle = msg.oracle.as_ref().ok_or_else(|| ContractError::InvalidConfig {
+        reason: "oracle is required to migrate to 0.2.0".to_string(),
+    })?;
+    ORACLE.save(deps.storage, &deps.api.addr_validate(oracle)?)?;
+
+    let liquidation = msg.liquidation.as_ref().ok_or_else(|| ContractError::InvalidConfig {
+        reason: "liquidation config is required to migrate to 0.2.0".to_string(),
+    })?;
+    validate_liquidation_config(liquidation)?;
+    LIQUIDATION_CONFIG.save(deps.storage, liquidation)?;
+
+    Ok(())
+}
+
+// Docs:
+// 0.3.0 moved interest to pool wide indices. The interest every position accrued until now
+// is folded into its balance at indices of 1, from there on it grows with the indices.
+fn migrate_to_v0_3_0(deps: DepsMut, env: &Env, _msg: &MigrateMsg) -> ContractResult<()> {
+    let pool_config = POOL_CONFIG.load(deps.storage)?;
+    let rates = LEGACY_POOL_RATES.load(deps.storage)?;
+    let now = std::cmp::min(env.block.time.seconds(), pool_config.maturationdate);
+    let mut pool_state = LegacyPoolState {
+        supply_index: Decimal::one(),
+        borrow_index: Decimal::one(),
+        last_accrued: now,
+        total_scaled_supply: Uint128::zero(),
+        total_scaled_debt: Uint128::zero(),
+        total_supplied: Uint128::zero(),
+        total_borrowed: Uint128::zero(),
+    };
+
+    let lenders = LEGACY_PRINCIPLE_DEPLOYED
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (lender, (principal, last_deposit_time)) in lenders {
+        let interest_earned = LEGACY_INTEREST_EARNED.may_load(deps.storage, &lender)?.unwrap_or_default();
+        let time_period = now.saturating_sub(last_deposit_time.seconds());
+        let interest = legacy_simple_interest(principal, rates.lendinterestrate, time_period)?;
+
+        let position = LenderPosition {
+            principal,
+            scaled: principal.checked_add(interest_earned)?.checked_add(interest)?,
+            deposited_at: last_deposit_time,
+        };
+        pool_state.total_scaled_supply = pool_state.total_scaled_supply.checked_add(position.scaled)?;
+        pool_state.total_supplied = pool_state.total_supplied.checked_add(principal)?;
+        LENDERS.save(deps.storage, &lender, &position)?;
+
+        LEGACY_PRINCIPLE_DEPLOYED.remove(deps.storage, &lender);
+        LEGACY_INTEREST_EARNED.remove(deps.storage, &lender);
+    }
+
+    let borrowers = LEGACY_PRINCIPLE_TO_REPAY
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (borrower, (principal, last_principle_time)) in borrowers {
+        let interest_to_repay = LEGACY_INTEREST_TO_REPAY.may_load(deps.storage, &borrower)?.unwrap_or_default();
+        let (collateral, _) = LEGACY_COLLATERAL_SUBMITTED.may_load(deps.storage, &borrower)?.unwrap_or_default();
+        let time_period = now.saturating_sub(last_principle_time.seconds());
+        let interest = legacy_simple_interest(principal, rates.debtinterestrate, time_period)?;
+
+        let position = LegacyBorrowerPosition {
+            principal,
+            scaled_debt: principal.checked_add(interest_to_repay)?.checked_add(interest)?,
+            collateral,
+        };
+        pool_state.total_scaled_debt = pool_state.total_scaled_debt.checked_add(position.scaled_debt)?;
+        pool_state.total_borrowed = pool_state.total_borrowed.checked_add(principal)?;
+        LEGACY_BORROWERS.save(deps.storage, &borrower, &position)?;
+
+        LEGACY_PRINCIPLE_TO_REPAY.remove(deps.storage, &borrower);
+        LEGACY_INTEREST_TO_REPAY.remove(deps.storage, &borrower);
+        LEGACY_COLLATERAL_SUBMITTED.remove(deps.storage, &borrower);
+    }
+
+    LEGACY_POOL_STATE.save(deps.storage, &pool_state)?;
+    Ok(())
+}
+
+fn legacy_simple_interest(principal: Uint128, interest_rate: Uint128, time_period: u64) -> ContractResult<Uint128> {
+    let numerator = interest_rate.checked_mul(Uint128::from(time_period))?;
+    let denominator = Uint128::new(LEGACY_RATE_UNITS).checked_mul(Uint128::from(SECONDS_IN_YEAR))?;
+    Ok(principal.checked_multiply_ratio(numerator, denominator)?)
+}
+
+// Docs:
+// 0.4.0 turned the rates and factors of the pool config into decimals with the rates per year.
+// The factors were whole numbers and read the same as decimals, the rates were in billionths.
+fn migrate_to_v0_4_0(deps: DepsMut, _env: &Env, _msg: &MigrateMsg) -> ContractResult<()> {
+    let rates = LEGACY_POOL_RATES.load(deps.storage)?;
+    let mut pool_config = POOL_CONFIG.load(deps.storage)?;
+
+    pool_config.debtinterestrate = Decimal::checked_from_ratio(rates.debtinterestrate, LEGACY_RATE_UNITS)?;
+    pool_config.lendinterestrate = Decimal::checked_from_ratio(rates.lendinterestrate, LEGACY_RATE_UNITS)?;
+    POOL_CONFIG.save(deps.storage, &pool_config)?;
+
+    Ok(())
+}
+
+// Docs:
+// 0.5.0 let a borrower hold several loans, each with an id and a rate of its own.
+// The position of every borrower becomes a loan of its own, numbered in address order,
+// opened now at the current debt rate, and the single borrow index becomes the index of that rate.
+fn migrate_to_v0_5_0(deps: DepsMut, env: &Env, _msg: &MigrateMsg) -> ContractResult<()> {
+    let pool_config = POOL_CONFIG.load(deps.storage)?;
+    let legacy_state = LEGACY_POOL_STATE.load(deps.storage)?;
+    let mut pool_state = PoolState::new(legacy_state.last_accrued, pool_config.debtinterestrate);
+    pool_state.supply_index = legacy_state.supply_index;
+    pool_state.total_scaled_supply = legacy_state.total_scaled_supply;
+    pool_state.total_supplied = legacy_state.total_supplied;
+    pool_state.total_borrowed = legacy_state.total_borrowed;
+    let borrow_index = pool_state.borrow_index_mut(pool_config.debtinterestrate);
+    borrow_index.index = legacy_state.borrow_index;
+    borrow_index.total_scaled_debt = legacy_state.total_scaled_debt;
+    POOL_STATE.save(deps.storage, &pool_state)?;
+
+    let positions = LEGACY_BORROWERS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut loan_id = LOAN_COUNT.may_load(deps.storage)?.unwrap_or_default();
+    for (borrower, position) in positions {
+        LEGACY_BORROWERS.remove(deps.storage, &borrower);
+        if position.scaled_debt.is_zero() && position.collateral.is_zero() {
+            continue;
+        }
+
+        loan_id += 1;
+        let loan = Loan {
+            id: loan_id,
+            borrower,
+            principal: position.principal,
+            scaled_debt: position.scaled_debt,
+            collateral: position.collateral,
+            opened_at: env.block.time,
+            rate: pool_config.debtinterestrate,
+        };
+        loans().save(deps.storage, loan_id, &loan)?;
+    }
+    LOAN_COUNT.save(deps.storage, &loan_id)?;
+
+    Ok(())
+}
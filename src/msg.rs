@@ -1,87 +1,697 @@
-use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Addr, Binary, Decimal, Timestamp, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use cw20::Cw20ReceiveMsg;
 
-use crate::types::PoolConfig;
+use crate::types::{FeeConfig, LiquidationConfig, Operation, PauseState, PoolConfig, QueuedWithdrawal, Settlement};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub config: PoolConfig,
     pub oracle: String,
     pub admin: String,
+    pub liquidation: LiquidationConfig,
+    /// no fees when left out
+    pub fees: Option<FeeConfig>,
+    /// code id of cw20-base to instantiate the lenders' receipt token with,
+    /// positions stay with the address that deposited when left out
+    pub receipt_token_code_id: Option<u64>,
 }
 
+/// Settings that didn't exist in the version being migrated from,
+/// pools deployed before the oracle and liquidations need both
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {
+    pub oracle: Option<String>,
+    pub liquidation: Option<LiquidationConfig>,
+}
+
+/// Native coins are attached to Deposit, Loan and Repay,
+/// cw20 tokens are pulled with TransferFrom against the user's allowance
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum TransactMsg {
-    /// This accepts a properly-encoded ReceiveMsg from a cw20 contract
-    Receive(Cw20ReceiveMsg),
     Deposit (DepositMsg),
     Withdraw (WithdrawMsg),
     Loan (LoanMsg),
     Repay (RepayMsg),
+    /// Pays `amount` of the asset towards the holder's loan `loan_id`, the accrued interest first and then the principal.
+    /// Paying only the interest leaves the collateral locked, the collateral is unlocked in proportion to the principal repaid
+    /// and whatever is above the debt is refunded
+    RepayAmount {
+        loan_id: u64,
+        amount: Uint128,
+    },
 
+    /// Pays the lender the interest earned so far, refused while withdrawals are queued
     WithdrawInterest {},
+
+    /// Books the receipt tokens `lender` sent or got since the pool last saw their position, anyone can call it.
+    /// The principal follows the shares and the shares got are locked in from when they are booked
+    SyncLender {
+        lender: String,
+    },
+
+    /// Once the pool has matured, books every unpaid loan as defaulted with its collateral forfeited
+    /// and snapshots what is left for the lenders. Anyone can call it, each call goes through at most `limit`
+    /// loans and queued withdrawals where the last one stopped and the snapshot is taken by the call that finishes
+    Settle {
+        limit: Option<u32>,
+    },
+    /// After settlement, pays the lender their pro-rata share of the remaining asset and forfeited collateral
+    Redeem {},
+
+    /// Pays the queued withdrawals out of the asset available, oldest first, at most `limit` of them.
+    /// Deposits, repayments and liquidations do this as well, anyone can call it.
+    ProcessQueue {
+        limit: Option<u32>,
+    },
+
+    /// Repays up to `repay_amount` of an undercollateralized loan's debt in the asset
+    /// and sends the matching collateral plus the liquidation bonus to the caller
+    Liquidate {
+        loan_id: u64,
+        repay_amount: Uint128,
+    },
+
+    /// Adds `amount` of collateral to the holder's loan `loan_id`, keeping it away from liquidation.
+    /// Native collateral is attached to the message, cw20 collateral is pulled with the holder's allowance
+    TopUpCollateral {
+        loan_id: u64,
+        amount: Uint128,
+    },
+    /// Moves the holder's loan `loan_id` into `target_pool`, a pool of the same asset and collateral maturing later.
+    /// The loan is repaid here with a loan of its debt from the target pool, opened for the holder against the same collateral,
+    /// atomically: if the target pool doesn't pay the debt back nothing happens
+    Rollover {
+        loan_id: u64,
+        target_pool: String,
+    },
+    /// Lets the sender's loans in `from_pool` roll over into this pool, `None` stops it.
+    /// Nothing can open a loan held by someone else without it
+    AllowRollover {
+        from_pool: Option<String>,
+    },
+    /// Opens a loan held by `borrower` against the attached collateral and pays `asset_amount` back to the caller
+    /// with RepayRollover, the origination fee comes on top of the loan. This is how a pool rolls a loan over into this one,
+    /// only the pool the borrower allowed rollovers from can call it
+    AcceptRollover {
+        borrower: String,
+        asset_amount: Uint128,
+    },
+    /// Pays the attached asset towards the loan being rolled over out of this pool.
+    /// Only the successor pool of the rollover in progress can send it, within the rollover's transaction
+    RepayRollover {},
+    /// Takes over the loan `loan_id` its holder transferred to the sender, along with its debt
+    AcceptLoan {
+        loan_id: u64,
+    },
+    /// Sends `amount` of collateral of the holder's loan `loan_id` back to them,
+    /// as long as what is left is still worth the over-collateralization factor of the debt at the oracle price
+    /// and stays above the liquidation threshold
+    WithdrawCollateral {
+        loan_id: u64,
+        amount: Uint128,
+    },
 }
 
 
 #[cw_serde]
 pub struct DepositMsg {
-    pub denom : Addr,
+    pub denom : String,
     pub amount: Uint128,
 }
 #[cw_serde]
 pub struct WithdrawMsg {
-    pub denom : Addr,
+    pub denom : String,
     pub amount: Uint128,
 }
 
 #[cw_serde]
 pub struct RepayMsg {
-    pub asset_denom : Addr,
+    pub loan_id: u64,
+    pub asset_denom : String,
     pub asset_principle: Uint128,
-    pub collateral_denom : Addr,
+    pub collateral_denom : String,
 }
 
 #[cw_serde]
 pub struct LoanMsg {
-    pub asset_denom : Addr,
+    pub asset_denom : String,
     pub asset_amount: Uint128,
-    pub collateral_denom : Addr,
+    pub collateral_denom : String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum ExecuteMsg {
+    /// This accepts a properly-encoded ReceiveMsg from a cw20 contract
+    Receive(Cw20ReceiveMsg),
     Transact(TransactMsg),
+    Admin(AdminMsg),
+    /// cw721 transfer of a loan, the loan id is the token id. The holder repays the loan
+    /// and gets its collateral back, only the holder can transfer it.
+    /// A loan carries a debt, so it only moves once the recipient takes it with AcceptLoan,
+    /// until then the holder can transfer it to someone else instead
+    #[serde(rename = "transfer_nft")]
+    TransferNft {
+        recipient: String,
+        token_id: String,
+    },
+    /// cw721 transfer of a loan to a contract, which is called with a Cw721ReceiveMsg
+    /// and takes the loan with AcceptLoan like any other recipient
+    #[serde(rename = "send_nft")]
+    SendNft {
+        contract: String,
+        token_id: String,
+        msg: Binary,
+    },
+}
+
+/// What a contract receiving a loan through SendNft is called with, as `{"receive_nft": ...}`
+#[cw_serde]
+pub struct Cw721ReceiveMsg {
+    pub sender: String,
+    pub token_id: String,
+    pub msg: Binary,
+}
+
+#[cw_serde]
+pub enum Cw721ReceiverExecuteMsg {
+    ReceiveNft(Cw721ReceiveMsg),
 }
 
+/// Messages only the admin can send,
+/// except AcceptAdmin which comes from the proposed admin and UpdatePause which the guardian can send too
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "camelCase")]
+pub enum AdminMsg {
+    UpdateConfig (Box<UpdateConfigMsg>),
+    /// First step of an admin transfer, the new admin has to accept it
+    ProposeAdmin {
+        admin: String,
+    },
+    /// Second step of an admin transfer, sent by the proposed admin
+    AcceptAdmin {},
+    /// Sets or removes the guardian, who can pause and unpause operations like the admin
+    SetGuardian {
+        guardian: Option<String>,
+    },
+    /// Pauses or unpauses the given operations,
+    /// repay is never paused since borrowers need it to get their collateral back
+    UpdatePause {
+        operations: Vec<Operation>,
+        paused: bool,
+    },
+    /// Sends all the protocol earnings to `recipient`
+    CollectProtocolFees {
+        recipient: String,
+    },
+}
+
+/// Fields left out are not changed.
+/// Interest accrued until the update keeps the old rates, the new ones apply from then on.
+#[cw_serde]
+pub struct UpdateConfigMsg {
+    pub debtinterestrate: Option<Decimal>,
+    pub lendinterestrate: Option<Decimal>,
+    pub overcollateralizationfactor: Option<Decimal>,
+    pub strikeprice: Option<Decimal>,
+    /// can only be lowered, so existing lenders are never locked in longer
+    pub lock_in_period: Option<Uint128>,
+    /// can only be pushed back, so existing lenders never mature earlier
+    pub maturationdate: Option<u64>,
+    pub oracle: Option<String>,
+    pub liquidation: Option<LiquidationConfig>,
+    /// applies to interest repaid and loans taken from then on
+    pub fees: Option<FeeConfig>,
+}
+
+/// Messages embedded in `Cw20ReceiveMsg.msg` when tokens are sent to the pool
+/// with cw20 `Send`. The sent amount is what gets credited, so no allowance is needed.
+#[cw_serde]
+pub enum ReceiveMsg {
+    /// Deposit the sent asset tokens as principal
+    Deposit {},
+    /// Repay `asset_principle` of the sender's loan `loan_id`,
+    /// the sent asset tokens must cover the principal plus interest and any excess is refunded
+    Repay {
+        loan_id: u64,
+        asset_principle: Uint128,
+    },
+    /// Pay the sent asset tokens towards the sender's loan `loan_id`, the accrued interest first and then the principal,
+    /// whatever is sent above the debt is refunded
+    RepayAmount {
+        loan_id: u64,
+    },
+    /// Post the sent collateral tokens and borrow `asset_amount` against them as a new loan,
+    /// any collateral above what the loan requires is refunded
+    PostCollateral {
+        asset_amount: Uint128,
+    },
+    /// Liquidate loan `loan_id` with the sent asset tokens,
+    /// whatever is sent above the close factor limit is refunded
+    Liquidate {
+        loan_id: u64,
+    },
+    /// Open a loan held by `borrower` against the sent collateral tokens and pay `asset_amount` back to the sender,
+    /// as `AcceptRollover` does with native collateral
+    AcceptRollover {
+        borrower: String,
+        asset_amount: Uint128,
+    },
+    /// Pay the sent asset tokens towards the loan being rolled over out of this pool, as `RepayRollover` does with native coins
+    RepayRollover {},
+    /// Add the sent collateral tokens to the sender's loan `loan_id`
+    TopUpCollateral {
+        loan_id: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, QueryResponses)]
+#[serde(rename_all = "camelCase")]
 pub enum QueryMsg {
+    #[returns(PoolDetailsResponse)]
     AllDetails {},
 
+    #[returns(UserDetailsResponse)]
     UserDetails {
         user: Addr
     },
 
+    #[returns(DepositQuoteResponse)]
     GetDepositQuote {
         user: Addr,
         amount: Uint128,
     },
 
+    #[returns(UserDetailsResponse)]
     GetWithdrawableAndRepayablePositions {
         user: Addr,
     },
 
+    #[returns(LoanQuoteResponse)]
     GetLoanQuote {
         amount: Uint128,
     },
 
+    #[returns(RepayQuoteResponse)]
     GetRepayQuote {
-        user:Addr
+        loan_id: u64,
+    },
+
+    #[returns(Option<Settlement>)]
+    Settlement {},
+
+    /// Collateral value at the oracle price, debt with interest accrued until now,
+    /// and their ratio for one loan, the loan is undercollateralized below one
+    #[returns(HealthFactorResponse)]
+    HealthFactor {
+        loan_id: u64,
+    },
+
+    /// A loan with the interest owed until now
+    #[returns(LoanInfo)]
+    Loan {
+        loan_id: u64,
+    },
+
+    /// Balance of the debt token, the principal plus interest owed until now over every loan of `address`.
+    /// Answers like a cw20 `Balance` query, the debt token itself can't be transferred
+    #[returns(cw20::BalanceResponse)]
+    Balance {
+        address: String,
+    },
+
+    /// cw20 token info of the debt token, its supply is what every borrower owes until now
+    #[returns(cw20::TokenInfoResponse)]
+    #[serde(rename = "token_info")]
+    TokenInfo {},
+
+    /// The admin and the admin proposed to take over, if any
+    #[returns(AdminResponse)]
+    Admin {},
+
+    /// Interest indices and pool wide totals with the interest accrued until now
+    #[returns(PoolStateResponse)]
+    PoolState {},
+
+    /// The paused operations and the guardian, if any
+    #[returns(PauseStateResponse)]
+    PauseState {},
+
+    /// The pool the borrower's loans are allowed to roll over from, if any
+    #[returns(RolloverSourceResponse)]
+    RolloverSource {
+        borrower: Addr,
+    },
+
+    /// Checks the pool's books against its balances and its positions,
+    /// visits every lender and loan so it is meant for monitoring and tests
+    #[returns(InvariantsResponse)]
+    Invariants {},
+
+    /// Lenders in address order with the interest accrued until now,
+    /// `limit` defaults to 10 and is at most 30
+    #[returns(ListLendersResponse)]
+    ListLenders {
+        start_after: Option<Addr>,
+        limit: Option<u32>,
     },
 
+    /// Borrowers in the order of the borrower index, shorter addresses first, with the interest owed
+    /// until now over all their loans. `limit` defaults to 10 and is at most 30, pass the last borrower of a page as `start_after`
+    #[returns(ListBorrowersResponse)]
+    ListBorrowers {
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+
+    /// Open loans in id order with the interest owed until now,
+    /// `limit` defaults to 10 and is at most 30
+    #[returns(ListLoansResponse)]
+    ListLoans {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    /// The open loans of `borrower` in id order, paged like ListLoans
+    #[returns(ListLoansResponse)]
+    LoansByBorrower {
+        borrower: Addr,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    /// Loans left unpaid at maturity in id order, as booked by settlement,
+    /// `limit` defaults to 10 and is at most 30
+    #[returns(ListDefaultedLoansResponse)]
+    ListDefaultedLoans {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    /// Queued withdrawals from the front of the queue,
+    /// `limit` defaults to 10 and is at most 30
+    #[returns(WithdrawalQueueResponse)]
+    WithdrawalQueue {
+        limit: Option<u32>,
+    },
+
+    /// cw721 holder of a loan, loans have no approvals
+    #[returns(OwnerOfResponse)]
+    #[serde(rename = "owner_of")]
+    OwnerOf {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+
+    /// cw721 info of a loan, the loan with the interest owed until now as the extension
+    #[returns(NftInfoResponse)]
+    #[serde(rename = "nft_info")]
+    NftInfo {
+        token_id: String,
+    },
+
+    /// cw721 count of the open loans
+    #[returns(NumTokensResponse)]
+    #[serde(rename = "num_tokens")]
+    NumTokens {},
+
+    /// cw721 ids of the loans `owner` holds, paged like ListLoans
+    #[returns(TokensResponse)]
+    #[serde(rename = "tokens")]
+    Tokens {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// cw721 ids of every open loan, paged like ListLoans
+    #[returns(TokensResponse)]
+    #[serde(rename = "all_tokens")]
+    AllTokens {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// cw721 name and symbol of the loans
+    #[returns(NftContractInfoResponse)]
+    #[serde(rename = "contract_info")]
+    ContractInfo {},
+
+    /// Who the loan was transferred to and hasn't accepted it yet, if anyone
+    #[returns(PendingLoanTransferResponse)]
+    PendingLoanTransfer {
+        loan_id: u64,
+    },
+}
+
+#[cw_serde]
+pub struct PoolDetailsResponse {
+    pub config: PoolConfig,
+    pub admin: Option<Addr>,
+    pub total_asset_available: Uint128,
+    pub total_collateral_available: Uint128,
+    pub fees: FeeConfig,
+    /// fees not collected yet, held apart from the asset available
+    pub total_protocol_earnings: Uint128,
+    pub receipt_token: Option<Addr>,
+}
+
+/// A lender's principal and the interest it earns by maturity
+#[cw_serde]
+pub struct LenderPositionResponse {
+    pub principal: Uint128,
+    pub interest: Uint128,
+}
+
+/// A borrower's principal over all their loans, the interest owed on it by maturity and the collateral locked for it
+#[cw_serde]
+pub struct BorrowerPositionResponse {
+    pub principal: Uint128,
+    pub interest: Uint128,
+    pub collateral: Uint128,
+}
+
+#[cw_serde]
+pub struct UserDetailsResponse {
+    pub lender: LenderPositionResponse,
+    pub borrower: BorrowerPositionResponse,
+}
+
+/// The lender's position at maturity without and with the quoted deposit
+#[cw_serde]
+pub struct DepositQuoteResponse {
+    pub current: LenderPositionResponse,
+    pub with_deposit: LenderPositionResponse,
+}
+
+/// Interest owed at maturity and collateral required for a loan of `amount` taken now,
+/// the origination fee is kept from the amount paid out
+#[cw_serde]
+pub struct LoanQuoteResponse {
+    pub amount: Uint128,
+    pub interest: Uint128,
+    pub collateral: Uint128,
+    pub fee: Uint128,
+}
+
+/// What repaying the whole of one loan costs now and the collateral it unlocks
+#[cw_serde]
+pub struct RepayQuoteResponse {
+    pub principal: Uint128,
+    pub interest: Uint128,
+    pub collateral: Uint128,
+}
+
+#[cw_serde]
+pub struct HealthFactorResponse {
+    pub collateral_value: Uint128,
+    pub debt: Uint128,
+    pub health_factor: Decimal,
+}
+
+#[cw_serde]
+pub struct PoolStateResponse {
+    pub supply_index: Decimal,
+    /// borrow index of the pool's debt interest rate, the one new loans are opened at
+    pub borrow_index: Decimal,
+    /// principal deposited by the lenders
+    pub total_supplied: Uint128,
+    /// interest owed to the lenders and not withdrawn yet
+    pub total_supply_interest: Uint128,
+    /// principal lent out to the borrowers
+    pub total_borrowed: Uint128,
+    /// interest owed by the borrowers and not repaid yet
+    pub total_debt_interest: Uint128,
+    /// borrowed over deposited principal
+    pub utilization: Decimal,
+}
+
+#[cw_serde]
+pub struct AdminResponse {
+    pub admin: Option<Addr>,
+    pub pending_admin: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct PauseStateResponse {
+    pub pause_state: PauseState,
+    pub guardian: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct RolloverSourceResponse {
+    pub from_pool: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct PendingLoanTransferResponse {
+    pub recipient: Option<Addr>,
+}
+
+/// A lender's position with the interest earned until now
+#[cw_serde]
+pub struct LenderInfo {
+    pub lender: Addr,
+    pub principal: Uint128,
+    pub interest: Uint128,
+    /// the lock in runs from here
+    pub deposited_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct ListLendersResponse {
+    pub lenders: Vec<LenderInfo>,
+}
+
+/// A borrower's open loans added up, with the interest owed until now
+#[cw_serde]
+pub struct BorrowerInfo {
+    pub borrower: Addr,
+    pub principal: Uint128,
+    pub interest: Uint128,
+    pub collateral: Uint128,
+    /// how many loans the borrower holds
+    pub loans: u32,
+}
+
+#[cw_serde]
+pub struct ListBorrowersResponse {
+    pub borrowers: Vec<BorrowerInfo>,
+}
+
+/// A loan with the interest owed until now
+#[cw_serde]
+pub struct LoanInfo {
+    pub id: u64,
+    pub borrower: Addr,
+    pub principal: Uint128,
+    pub interest: Uint128,
+    pub collateral: Uint128,
+    pub opened_at: Timestamp,
+    /// debt interest rate when the loan was opened, the loan owes interest at it
+    pub rate: Decimal,
+}
+
+#[cw_serde]
+pub struct ListLoansResponse {
+    pub loans: Vec<LoanInfo>,
+}
+
+#[cw_serde]
+pub struct DefaultedLoanInfo {
+    pub loan_id: u64,
+    pub borrower: Addr,
+    /// principal plus interest owed at maturity
+    pub debt: Uint128,
+    pub collateral: Uint128,
+}
+
+#[cw_serde]
+pub struct ListDefaultedLoansResponse {
+    pub loans: Vec<DefaultedLoanInfo>,
+}
+
+#[cw_serde]
+pub struct WithdrawalQueueResponse {
+    /// still to be paid out over the whole queue
+    pub total_queued: Uint128,
+    pub length: u32,
+    pub withdrawals: Vec<QueuedWithdrawal>,
+}
+
+/// A total as the pool books it next to what it should match
+#[cw_serde]
+pub struct InvariantCheck {
+    pub recorded: Uint128,
+    pub actual: Uint128,
+}
+
+impl InvariantCheck {
+    pub fn holds(&self) -> bool {
+        self.recorded == self.actual
+    }
+}
+
+#[cw_serde]
+pub struct InvariantsResponse {
+    /// TOTAL_ASSET_AVAILABLE plus the protocol earnings against the asset the pool holds
+    pub asset_balance: InvariantCheck,
+    /// TOTAL_COLLATERAL_AVAILABLE against the collateral the pool holds
+    pub collateral_balance: InvariantCheck,
+    /// pool wide lender totals against the sums over every lender
+    pub total_supplied: InvariantCheck,
+    pub total_scaled_supply: InvariantCheck,
+    /// pool wide borrower totals against the sums over every loan
+    pub total_borrowed: InvariantCheck,
+    pub total_scaled_debt: InvariantCheck,
+    /// TOTAL_COLLATERAL_AVAILABLE against the collateral of every loan, until settlement
+    pub borrower_collateral: InvariantCheck,
+    /// the queued total against the sum over the withdrawal queue
+    pub queued_withdrawals: InvariantCheck,
+    /// interest the borrowers pay by maturity above what the lenders are promised,
+    /// interest already repaid and withdrawn included and the protocol's share left out
+    pub interest_surplus: Uint128,
+    /// interest the lenders are promised by maturity above what the borrowers pay
+    pub interest_shortfall: Uint128,
+    /// names of the checks that don't hold
+    pub discrepancies: Vec<String>,
+}
+
+#[cw_serde]
+pub struct NftApproval {
+    pub spender: String,
+    pub expires: cw_utils::Expiration,
+}
+
+#[cw_serde]
+pub struct OwnerOfResponse {
+    pub owner: String,
+    /// always empty, loans can't be approved
+    pub approvals: Vec<NftApproval>,
+}
+
+#[cw_serde]
+pub struct NftInfoResponse {
+    pub token_uri: Option<String>,
+    pub extension: LoanInfo,
+}
+
+#[cw_serde]
+pub struct NumTokensResponse {
+    pub count: u64,
+}
+
+#[cw_serde]
+pub struct TokensResponse {
+    pub tokens: Vec<String>,
+}
+
+#[cw_serde]
+pub struct NftContractInfoResponse {
+    pub name: String,
+    pub symbol: String,
 }
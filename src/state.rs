@@ -1,30 +1,69 @@
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use cosmwasm_std::{Addr, Uint128};
+use cw_controllers::Admin;
+use cw_storage_plus::{Deque, Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 
-use cosmwasm_std::{Addr, Uint128, Timestamp};
-use cw_storage_plus::{Item, Map};
 
-
-use crate::types::{PoolConfig, CoinConfig};
+use crate::types::{PoolConfig, CoinConfig, DefaultedLoan, FeeConfig, LenderPosition, LiquidationConfig, Loan, PauseState, PendingRollover, PoolState, QueuedWithdrawal, Settlement};
 
 
 pub const POOL_CONFIG: Item<PoolConfig> = Item::new("pool_config");
+pub const LIQUIDATION_CONFIG: Item<LiquidationConfig> = Item::new("liquidation_config");
+// no fees until set
+pub const FEE_CONFIG: Item<FeeConfig> = Item::new("fee_config");
 
 
-pub const INTEREST_EARNED: Map<&Addr, Uint128> = Map::new("interest_earned");
-pub const PRINCIPLE_DEPLOYED: Map<&Addr, (Uint128,Timestamp)> = Map::new("principle_deployed");
-
-pub const INTEREST_TO_REPAY: Map<&Addr, Uint128> = Map::new("interest_to_repay");
-pub const COLLATERAL_SUBMITTED: Map<&Addr, (Uint128,Timestamp)> = Map::new("collateral_deployed");
-    
-pub const PRINCIPLE_TO_REPAY: Map<&Addr, (Uint128,Timestamp)> = Map::new("principle_to_repay");
+pub const POOL_STATE: Item<PoolState> = Item::new("pool_state");
+pub const LENDERS: Map<&Addr, LenderPosition> = Map::new("lenders");
+// cw20 receipt token of the lenders' shares, when the pool was instantiated with one
+pub const RECEIPT_TOKEN: Item<Addr> = Item::new("receipt_token");
+// the last loan id handed out, ids start at 1
+pub const LOAN_COUNT: Item<u64> = Item::new("loan_count");
+// withdrawals waiting for the asset to be repaid, oldest first
+pub const WITHDRAWAL_QUEUE: Deque<QueuedWithdrawal> = Deque::new("withdrawal_queue");
+pub const TOTAL_QUEUED_WITHDRAWALS: Item<Uint128> = Item::new("total_queued_withdrawals");
 
 pub const ASSET_CONFIG: Item<CoinConfig> = Item::new("asset_config");
 pub const COLLATERAL_CONFIG: Item<CoinConfig> = Item::new("collateral_config");
 
 pub const TOTAL_ASSET_AVAILABLE: Item<Uint128> = Item::new("total_asset_available");
 pub const TOTAL_COLLATERAL_AVAILABLE: Item<Uint128> = Item::new("total_collateral_available");
+// fees held for the protocol, never lent out nor paid to lenders
 pub const TOTAL_PROTOCOL_EARNINGS: Item<Uint128> = Item::new("total_protocol_earnings");
 
-pub const ADMIN: Item<Addr> = Item::new("admin");
-pub const NANOSECONDS_IN_YEAR: u64 = 365 * 24 * 60 * 60 * 1_000_000_000;
+pub const SETTLEMENT: Item<Settlement> = Item::new("settlement");
+// only set between a rollover and the reply of the successor pool, within the same transaction
+pub const PENDING_ROLLOVER: Item<PendingRollover> = Item::new("pending_rollover");
+// the pool each borrower allowed to roll their loans over into this one
+pub const ROLLOVER_SOURCES: Map<&Addr, Addr> = Map::new("rollover_sources");
+// debt (principal plus interest) of every loan left unpaid at maturity and the collateral forfeited for it
+pub const DEFAULTED_LOANS: Map<u64, DefaultedLoan> = Map::new("defaulted_loans_by_id");
+// the address each loan was transferred to, the loan moves once it accepts
+pub const PENDING_LOAN_TRANSFERS: Map<u64, Addr> = Map::new("pending_loan_transfers");
+
+pub const ADMIN: Admin = Admin::new("admin");
+// set by the admin and becomes the admin once it accepts
+pub const PENDING_ADMIN: Item<Addr> = Item::new("pending_admin");
+pub const ORACLE: Item<Addr> = Item::new("oracle");
+// can pause and unpause operations besides the admin
+pub const GUARDIAN: Item<Addr> = Item::new("guardian");
+pub const PAUSE_STATE: Item<PauseState> = Item::new("pause_state");
+pub const SECONDS_IN_YEAR: u64 = 365 * 24 * 60 * 60;
+
+pub struct LoanIndexes<'a> {
+    pub borrower: MultiIndex<'a, Addr, Loan, u64>,
+}
+
+impl<'a> IndexList<Loan> for LoanIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Loan>> + '_> {
+        let v: Vec<&dyn Index<Loan>> = vec![&self.borrower];
+        Box::new(v.into_iter())
+    }
+}
+
+// open loans by id, indexed by borrower
+pub fn loans<'a>() -> IndexedMap<'a, u64, Loan, LoanIndexes<'a>> {
+    let indexes = LoanIndexes {
+        borrower: MultiIndex::new(|_, loan| loan.borrower.clone(), "loans", "loans__borrower"),
+    };
+    IndexedMap::new("loans", indexes)
+}
@@ -0,0 +1,150 @@
+use cosmwasm_std::{Addr, Decimal, Uint128};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{AdminMsg, AdminResponse, ExecuteMsg, PoolDetailsResponse, QueryMsg, UpdateConfigMsg};
+use crate::types::PoolConfig;
+
+fn setup() -> (App, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+    (app, pool)
+}
+
+fn no_changes() -> UpdateConfigMsg {
+    UpdateConfigMsg {
+        debtinterestrate: None,
+        lendinterestrate: None,
+        overcollateralizationfactor: None,
+        strikeprice: None,
+        lock_in_period: None,
+        maturationdate: None,
+        oracle: None,
+        liquidation: None,
+        fees: None,
+    }
+}
+
+fn update_config(app: &mut App, pool: &Addr, sender: &str, msg: UpdateConfigMsg) -> Result<(), ContractError> {
+    let msg = ExecuteMsg::Admin(AdminMsg::UpdateConfig(Box::new(msg)));
+    app.execute_contract(Addr::unchecked(sender), pool.clone(), &msg, &[])
+        .map(|_| ())
+        .map_err(|err| err.downcast().unwrap())
+}
+
+fn pool_config_of(app: &App, pool: &Addr) -> PoolConfig {
+    let details: PoolDetailsResponse = app
+        .wrap()
+        .query_wasm_smart(pool, &QueryMsg::AllDetails {})
+        .unwrap();
+    details.config
+}
+
+#[test]
+fn admin_updates_config() {
+    let (mut app, pool) = setup();
+    let before = pool_config_of(&app, &pool);
+
+    let msg = UpdateConfigMsg {
+        debtinterestrate: Some(DEBT_RATE * Decimal::percent(200)),
+        lock_in_period: Some(Uint128::new(5)),
+        maturationdate: Some(before.maturationdate + YEAR),
+        ..no_changes()
+    };
+    update_config(&mut app, &pool, ADMIN, msg).unwrap();
+
+    let after = pool_config_of(&app, &pool);
+    assert_eq!(after.debtinterestrate, Decimal::percent(20));
+    assert_eq!(after.lock_in_period.u128(), 5);
+    assert_eq!(after.maturationdate, before.maturationdate + YEAR);
+    assert_eq!(after.lendinterestrate, before.lendinterestrate);
+}
+
+#[test]
+fn only_admin_updates_config() {
+    let (mut app, pool) = setup();
+
+    let err = update_config(&mut app, &pool, LENDER, no_changes()).unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err);
+}
+
+#[test]
+fn config_updates_protect_existing_lenders() {
+    let (mut app, pool) = setup();
+    let config = pool_config_of(&app, &pool);
+
+    let msg = UpdateConfigMsg {
+        maturationdate: Some(config.maturationdate - 1),
+        ..no_changes()
+    };
+    let err = update_config(&mut app, &pool, ADMIN, msg).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidConfig { .. }));
+
+    let msg = UpdateConfigMsg {
+        lock_in_period: Some(Uint128::new(20)),
+        ..no_changes()
+    };
+    let err = update_config(&mut app, &pool, ADMIN, msg).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidConfig { .. }));
+
+    let msg = UpdateConfigMsg {
+        lendinterestrate: Some(DEBT_RATE + Decimal::permille(1)),
+        ..no_changes()
+    };
+    let err = update_config(&mut app, &pool, ADMIN, msg).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidConfig { .. }));
+
+    let msg = UpdateConfigMsg {
+        overcollateralizationfactor: Some(Decimal::percent(99)),
+        ..no_changes()
+    };
+    let err = update_config(&mut app, &pool, ADMIN, msg).unwrap_err();
+    assert_eq!(ContractError::InsufficientOCF {}, err);
+}
+
+#[test]
+fn admin_transfer_takes_two_steps() {
+    let (mut app, pool) = setup();
+    let new_admin = "new_admin";
+
+    let propose = ExecuteMsg::Admin(AdminMsg::ProposeAdmin { admin: new_admin.to_string() });
+    let accept = ExecuteMsg::Admin(AdminMsg::AcceptAdmin {});
+
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER), pool.clone(), &propose, &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+    app.execute_contract(Addr::unchecked(ADMIN), pool.clone(), &propose, &[])
+        .unwrap();
+
+    // proposing doesn't hand anything over yet
+    let res: AdminResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::Admin {})
+        .unwrap();
+    assert_eq!(res.admin, Some(Addr::unchecked(ADMIN)));
+    assert_eq!(res.pending_admin, Some(Addr::unchecked(new_admin)));
+
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER), pool.clone(), &accept, &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+    app.execute_contract(Addr::unchecked(new_admin), pool.clone(), &accept, &[])
+        .unwrap();
+
+    let res: AdminResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::Admin {})
+        .unwrap();
+    assert_eq!(res.admin, Some(Addr::unchecked(new_admin)));
+    assert_eq!(res.pending_admin, None);
+
+    // the old admin is out
+    let err = update_config(&mut app, &pool, ADMIN, no_changes()).unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err);
+    update_config(&mut app, &pool, new_admin, no_changes()).unwrap();
+}
@@ -0,0 +1,26 @@
+use cosmwasm_std::{coin, Uint128};
+use cw20::Cw20Coin;
+
+use crate::amount::Amount;
+
+#[test]
+fn from_parts_splits_native_and_cw20() {
+    assert_eq!(
+        Amount::from_parts("uusdc".to_string(), Uint128::new(5)),
+        Amount::Native(coin(5, "uusdc"))
+    );
+    assert_eq!(
+        Amount::from_parts("cw20:token".to_string(), Uint128::new(5)),
+        Amount::Cw20(Cw20Coin {
+            address: "token".to_string(),
+            amount: Uint128::new(5),
+        })
+    );
+}
+
+#[test]
+fn denom_round_trips_through_from_parts() {
+    for amount in [Amount::native(7, "uusdc"), Amount::cw20(7, "token")] {
+        assert_eq!(Amount::from_parts(amount.denom(), amount.amount()), amount);
+    }
+}
@@ -0,0 +1,156 @@
+use cosmwasm_std::{coins, Addr, Decimal, Uint128};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{AdminMsg, ExecuteMsg, LoanInfo, QueryMsg, ReceiveMsg, TransactMsg, UpdateConfigMsg};
+use crate::types::LiquidationConfig;
+
+fn top_up_msg(amount: u128) -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::TopUpCollateral { loan_id: 1, amount: Uint128::new(amount) })
+}
+
+fn withdraw_collateral_msg(amount: u128) -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::WithdrawCollateral { loan_id: 1, amount: Uint128::new(amount) })
+}
+
+fn loan_collateral(app: &App, pool: &Addr) -> u128 {
+    let loan: LoanInfo = app.wrap().query_wasm_smart(pool, &QueryMsg::Loan { loan_id: 1 }).unwrap();
+    loan.collateral.u128()
+}
+
+// The borrower takes 1_000 against 2_000 collateral at a price of 1
+fn borrowed_pool() -> (App, Addr, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let (pool, oracle) = instantiate_pool_with_oracle(&mut app, pool_config(ASSET, COLLATERAL, now));
+
+    deposit(&mut app, &pool, LENDER, 10_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+    (app, pool, oracle)
+}
+
+#[test]
+fn top_up_keeps_a_loan_from_liquidation() {
+    let (mut app, pool, oracle) = borrowed_pool();
+    // 2_000 * 0.6 / 1_000 is below the 125% threshold
+    set_price(&mut app, &oracle, Decimal::percent(60));
+
+    // anything attached above the amount is refunded
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &top_up_msg(500), &coins(600, COLLATERAL))
+        .unwrap();
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE - 2_500);
+    assert_eq!(loan_collateral(&app, &pool), 2_500);
+
+    let msg = ExecuteMsg::Transact(TransactMsg::Liquidate { loan_id: 1, repay_amount: Uint128::new(500) });
+    let err = app
+        .execute_contract(Addr::unchecked(KEEPER), pool.clone(), &msg, &coins(500, ASSET))
+        .unwrap_err();
+    assert_eq!(ContractError::PositionHealthy {}, err.downcast().unwrap());
+
+    // the collateral topped up is unlocked with the rest on repayment
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 1_000), &coins(1_000, ASSET))
+        .unwrap();
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn only_collateral_above_the_required_ratio_is_withdrawn() {
+    let (mut app, pool, _) = borrowed_pool();
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &top_up_msg(500), &coins(500, COLLATERAL))
+        .unwrap();
+    advance(&mut app, YEAR / 2);
+
+    // 1_050 of debt needs 2_100 of the 2_500 collateral
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &withdraw_collateral_msg(401), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Undercollateralized {}, err.downcast().unwrap());
+
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &withdraw_collateral_msg(400), &[])
+        .unwrap();
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE - 2_100);
+    assert_eq!(loan_collateral(&app, &pool), 2_100);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn withdrawal_never_leaves_a_loan_liquidatable() {
+    let (mut app, pool, _) = borrowed_pool();
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &top_up_msg(1_000), &coins(1_000, COLLATERAL))
+        .unwrap();
+
+    // a 250% threshold asks for more than the 2x over-collateralization
+    let msg = ExecuteMsg::Admin(AdminMsg::UpdateConfig(Box::new(UpdateConfigMsg {
+        debtinterestrate: None,
+        lendinterestrate: None,
+        overcollateralizationfactor: None,
+        strikeprice: None,
+        lock_in_period: None,
+        maturationdate: None,
+        oracle: None,
+        liquidation: Some(LiquidationConfig { threshold: Decimal::percent(250), ..liquidation_config() }),
+        fees: None,
+    })));
+    app.execute_contract(Addr::unchecked(ADMIN), pool.clone(), &msg, &[])
+        .unwrap();
+
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &withdraw_collateral_msg(501), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Undercollateralized {}, err.downcast().unwrap());
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &withdraw_collateral_msg(500), &[])
+        .unwrap();
+    assert_eq!(loan_collateral(&app, &pool), 2_500);
+}
+
+#[test]
+fn only_the_holder_moves_collateral_before_maturity() {
+    let (mut app, pool, _) = borrowed_pool();
+
+    let err = app
+        .execute_contract(Addr::unchecked(KEEPER), pool.clone(), &withdraw_collateral_msg(1), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+    let msg = ExecuteMsg::Transact(TransactMsg::TopUpCollateral { loan_id: 9, amount: Uint128::new(100) });
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &msg, &coins(100, COLLATERAL))
+        .unwrap_err();
+    assert_eq!(ContractError::PositionNotAvailable {}, err.downcast().unwrap());
+
+    // once matured the collateral is forfeited to the lenders
+    advance(&mut app, YEAR + 1);
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &top_up_msg(100), &coins(100, COLLATERAL))
+        .unwrap_err();
+    assert_eq!(ContractError::CollateralForfeited {}, err.downcast().unwrap());
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool, &withdraw_collateral_msg(1), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::CollateralForfeited {}, err.downcast().unwrap());
+}
+
+#[test]
+fn cw20_collateral_is_topped_up_with_send() {
+    let mut app = native_app();
+    let pool = instantiate_cw20_pool(&mut app);
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+    loan_cw20(&mut app, &pool, BORROWER, 1_000);
+
+    let msg = ReceiveMsg::TopUpCollateral { loan_id: 1 };
+    assert_eq!(
+        ContractError::InvalidCollateral {},
+        send(&mut app, &pool.asset, BORROWER, &pool.pool, 500, &msg).unwrap_err()
+    );
+    send(&mut app, &pool.collateral, BORROWER, &pool.pool, 500, &msg).unwrap();
+
+    assert_eq!(token_balance(&app, &pool.collateral, BORROWER), INITIAL_BALANCE - 2_500);
+    assert_eq!(loan_collateral(&app, &pool.pool), 2_500);
+
+    transact(&mut app, &pool, BORROWER, TransactMsg::WithdrawCollateral { loan_id: 1, amount: Uint128::new(500) }).unwrap();
+    assert_eq!(token_balance(&app, &pool.collateral, BORROWER), INITIAL_BALANCE - 2_000);
+    assert!(invariants(&app, &pool.pool).discrepancies.is_empty());
+}
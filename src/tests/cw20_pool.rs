@@ -0,0 +1,175 @@
+use cosmwasm_std::{Addr, Uint128};
+use cw_multi_test::App;
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{
+    LenderPositionResponse, QueryMsg, ReceiveMsg, RepayMsg, TransactMsg, UserDetailsResponse, WithdrawMsg,
+};
+use crate::types::Settlement;
+
+fn setup() -> (App, Cw20Pool) {
+    let mut app = native_app();
+    let pool = instantiate_cw20_pool(&mut app);
+    (app, pool)
+}
+
+// Approves `amount` of the asset and repays `principal` with it
+fn repay_cw20(app: &mut App, pool: &Cw20Pool, borrower: &str, principal: u128, amount: u128) -> Result<(), ContractError> {
+    approve(app, &pool.asset, borrower, &pool.pool, amount, None);
+    let msg = TransactMsg::Repay(RepayMsg {
+        loan_id: 1,
+        asset_denom: pool.asset_denom(),
+        asset_principle: Uint128::new(principal),
+        collateral_denom: pool.collateral_denom(),
+    });
+    transact(app, pool, borrower, msg)
+}
+
+fn withdraw_msg(pool: &Cw20Pool, amount: u128) -> TransactMsg {
+    TransactMsg::Withdraw(WithdrawMsg { denom: pool.asset_denom(), amount: Uint128::new(amount) })
+}
+
+fn user_details(app: &App, pool: &Cw20Pool, user: &str) -> UserDetailsResponse {
+    app.wrap()
+        .query_wasm_smart(&pool.pool, &QueryMsg::UserDetails { user: Addr::unchecked(user) })
+        .unwrap()
+}
+
+#[test]
+fn deposit_with_allowance_and_withdraw_after_lock_in() {
+    let (mut app, pool) = setup();
+
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+    assert_eq!(token_balance(&app, &pool.asset, pool.pool.as_str()), 10_000);
+    assert_eq!(token_balance(&app, &pool.asset, LENDER), INITIAL_BALANCE - 10_000);
+
+    // the lock in is 10% of the time left to maturity
+    let err = transact(&mut app, &pool, LENDER, withdraw_msg(&pool, 4_000)).unwrap_err();
+    assert_eq!(ContractError::LockinTimePeriodActive {}, err);
+
+    advance(&mut app, YEAR / 10);
+    transact(&mut app, &pool, LENDER, withdraw_msg(&pool, 4_000)).unwrap();
+
+    assert_eq!(token_balance(&app, &pool.asset, pool.pool.as_str()), 6_000);
+    assert_eq!(token_balance(&app, &pool.asset, LENDER), INITIAL_BALANCE - 6_000);
+    assert_eq!(user_details(&app, &pool, LENDER).lender.principal.u128(), 6_000);
+}
+
+#[test]
+fn deposit_with_send_needs_no_allowance() {
+    let (mut app, pool) = setup();
+
+    send(&mut app, &pool.asset, LENDER, &pool.pool, 10_000, &ReceiveMsg::Deposit {}).unwrap();
+
+    assert_eq!(token_balance(&app, &pool.asset, pool.pool.as_str()), 10_000);
+    // 5% a year on the principal by maturity
+    assert_eq!(
+        user_details(&app, &pool, LENDER).lender,
+        LenderPositionResponse { principal: Uint128::new(10_000), interest: Uint128::new(500) }
+    );
+}
+
+#[test]
+fn lender_withdraws_interest_paid_by_borrower() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+    loan_cw20(&mut app, &pool, BORROWER, 5_000);
+
+    advance(&mut app, YEAR / 2);
+    repay_cw20(&mut app, &pool, BORROWER, 5_000, 5_250).unwrap();
+
+    transact(&mut app, &pool, LENDER, TransactMsg::WithdrawInterest {}).unwrap();
+
+    // half a year at 5% on 10_000, the rest of the borrower's interest stays in the pool
+    assert_eq!(token_balance(&app, &pool.asset, LENDER), INITIAL_BALANCE - 10_000 + 250);
+    assert_eq!(token_balance(&app, &pool.asset, pool.pool.as_str()), 10_000);
+    assert_eq!(user_details(&app, &pool, LENDER).lender.principal.u128(), 10_000);
+}
+
+#[test]
+fn loan_is_repaid_partially_then_fully() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+    loan_cw20(&mut app, &pool, BORROWER, 1_000);
+
+    assert_eq!(token_balance(&app, &pool.asset, BORROWER), INITIAL_BALANCE + 1_000);
+    assert_eq!(token_balance(&app, &pool.collateral, BORROWER), INITIAL_BALANCE - 2_000);
+    assert_eq!(token_balance(&app, &pool.collateral, pool.pool.as_str()), 2_000);
+
+    advance(&mut app, YEAR / 2);
+
+    // repaying 40% of the principal pays 40% of the interest and unlocks 40% of the collateral
+    repay_cw20(&mut app, &pool, BORROWER, 400, 420).unwrap();
+    assert_eq!(token_balance(&app, &pool.asset, BORROWER), INITIAL_BALANCE + 1_000 - 420);
+    assert_eq!(token_balance(&app, &pool.collateral, BORROWER), INITIAL_BALANCE - 1_200);
+
+    let borrower = user_details(&app, &pool, BORROWER).borrower;
+    assert_eq!(borrower.principal.u128(), 600);
+    assert_eq!(borrower.collateral.u128(), 1_200);
+
+    // the allowance only has to cover what is due
+    repay_cw20(&mut app, &pool, BORROWER, 600, 630).unwrap();
+    assert_eq!(token_balance(&app, &pool.asset, BORROWER), INITIAL_BALANCE - 50);
+    assert_eq!(token_balance(&app, &pool.collateral, BORROWER), INITIAL_BALANCE);
+    assert_eq!(token_balance(&app, &pool.asset, pool.pool.as_str()), 10_050);
+    assert_eq!(token_balance(&app, &pool.collateral, pool.pool.as_str()), 0);
+
+    let err = repay_cw20(&mut app, &pool, BORROWER, 1, 1).unwrap_err();
+    assert_eq!(ContractError::PositionNotAvailable {}, err);
+}
+
+#[test]
+fn loan_and_repay_with_send_refund_the_excess() {
+    let (mut app, pool) = setup();
+    send(&mut app, &pool.asset, LENDER, &pool.pool, 10_000, &ReceiveMsg::Deposit {}).unwrap();
+
+    let msg = ReceiveMsg::PostCollateral { asset_amount: Uint128::new(1_000) };
+    send(&mut app, &pool.collateral, BORROWER, &pool.pool, 2_500, &msg).unwrap();
+    assert_eq!(token_balance(&app, &pool.asset, BORROWER), INITIAL_BALANCE + 1_000);
+    assert_eq!(token_balance(&app, &pool.collateral, BORROWER), INITIAL_BALANCE - 2_000);
+
+    advance(&mut app, YEAR / 2);
+
+    let msg = ReceiveMsg::Repay { loan_id: 1, asset_principle: Uint128::new(1_000) };
+    send(&mut app, &pool.asset, BORROWER, &pool.pool, 1_100, &msg).unwrap();
+    assert_eq!(token_balance(&app, &pool.asset, BORROWER), INITIAL_BALANCE - 50);
+    assert_eq!(token_balance(&app, &pool.collateral, BORROWER), INITIAL_BALANCE);
+}
+
+#[test]
+fn matured_pool_settles_and_redeems() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 6_000);
+    deposit_cw20(&mut app, &pool, LENDER2, 4_000);
+    loan_cw20(&mut app, &pool, BORROWER, 5_000);
+
+    advance(&mut app, YEAR + 1);
+
+    // nothing moves in or out of a matured pool but through settlement
+    let msg = ReceiveMsg::Deposit {};
+    assert_eq!(ContractError::PoolMatured {}, send(&mut app, &pool.asset, LENDER, &pool.pool, 1_000, &msg).unwrap_err());
+
+    let err = transact(&mut app, &pool, LENDER, withdraw_msg(&pool, 1_000)).unwrap_err();
+    assert_eq!(ContractError::PoolMatured {}, err);
+
+    let err = repay_cw20(&mut app, &pool, BORROWER, 5_000, 5_500).unwrap_err();
+    assert_eq!(ContractError::CollateralForfeited {}, err);
+
+    transact(&mut app, &pool, KEEPER, TransactMsg::Settle { limit: None }).unwrap();
+    let settlement: Option<Settlement> = app.wrap().query_wasm_smart(&pool.pool, &QueryMsg::Settlement {}).unwrap();
+    let settlement = settlement.unwrap();
+    assert_eq!(settlement.asset_available.u128(), 5_000);
+    assert_eq!(settlement.collateral_forfeited.u128(), 10_000);
+
+    // the lenders share the asset left and the forfeited collateral 60/40
+    transact(&mut app, &pool, LENDER, TransactMsg::Redeem {}).unwrap();
+    transact(&mut app, &pool, LENDER2, TransactMsg::Redeem {}).unwrap();
+
+    assert_eq!(token_balance(&app, &pool.asset, LENDER), INITIAL_BALANCE - 6_000 + 3_000);
+    assert_eq!(token_balance(&app, &pool.collateral, LENDER), INITIAL_BALANCE + 6_000);
+    assert_eq!(token_balance(&app, &pool.asset, LENDER2), INITIAL_BALANCE - 4_000 + 2_000);
+    assert_eq!(token_balance(&app, &pool.collateral, LENDER2), INITIAL_BALANCE + 4_000);
+    assert_eq!(token_balance(&app, &pool.asset, pool.pool.as_str()), 0);
+    assert_eq!(token_balance(&app, &pool.collateral, pool.pool.as_str()), 0);
+}
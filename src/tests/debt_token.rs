@@ -0,0 +1,72 @@
+use cosmwasm_std::{coins, Addr, Uint128};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, TokenInfoResponse};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+
+// The debt token is read with the plain cw20 queries
+fn debt(app: &App, pool: &Addr, borrower: &str) -> u128 {
+    let res: BalanceResponse = app
+        .wrap()
+        .query_wasm_smart(pool, &Cw20QueryMsg::Balance { address: borrower.to_string() })
+        .unwrap();
+    res.balance.u128()
+}
+
+fn borrowed_pool() -> (App, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+    deposit(&mut app, &pool, LENDER, 10_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+    (app, pool)
+}
+
+#[test]
+fn debt_balance_follows_loans_and_repayments() {
+    let (mut app, pool) = borrowed_pool();
+    assert_eq!(debt(&app, &pool, BORROWER), 1_000);
+    assert_eq!(debt(&app, &pool, LENDER), 0);
+
+    // interest adds to the balance, so does every loan of the borrower
+    advance(&mut app, YEAR / 2);
+    assert_eq!(debt(&app, &pool, BORROWER), 1_050);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(500), &coins(1_000, COLLATERAL))
+        .unwrap();
+    // debt rounds up, in favour of the pool
+    assert_eq!(debt(&app, &pool, BORROWER), 1_551);
+
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 1_000), &coins(1_050, ASSET))
+        .unwrap();
+    assert_eq!(debt(&app, &pool, BORROWER), 501);
+}
+
+#[test]
+fn debt_token_supply_is_the_debt_of_every_borrower() {
+    let (mut app, pool) = borrowed_pool();
+    app.execute_contract(Addr::unchecked(KEEPER), pool.clone(), &loan_msg(2_000), &coins(4_000, COLLATERAL))
+        .unwrap();
+    advance(&mut app, YEAR / 2);
+
+    let info: TokenInfoResponse = app.wrap().query_wasm_smart(&pool, &Cw20QueryMsg::TokenInfo {}).unwrap();
+    assert_eq!(
+        info,
+        TokenInfoResponse {
+            name: "Gold backed USDC debt".to_string(),
+            symbol: "dgUSDC".to_string(),
+            decimals: 6,
+            total_supply: Uint128::new(3_150),
+        }
+    );
+}
+
+#[test]
+fn debt_token_cannot_be_transferred() {
+    let (mut app, pool) = borrowed_pool();
+
+    let msg = Cw20ExecuteMsg::Transfer { recipient: KEEPER.to_string(), amount: Uint128::new(1_000) };
+    assert!(app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &msg, &[]).is_err());
+    assert_eq!(debt(&app, &pool, BORROWER), 1_000);
+    assert_eq!(debt(&app, &pool, KEEPER), 0);
+}
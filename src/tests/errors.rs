@@ -0,0 +1,375 @@
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+use cosmwasm_std::{coins, Addr, Decimal, StdError, Uint128};
+use cw2::set_contract_version;
+use cw20::Expiration;
+use cw_multi_test::{App, Executor};
+use cw_utils::PaymentError;
+
+use super::*;
+use crate::amount::Amount;
+use crate::contract::migrate;
+use crate::error::ContractError;
+use crate::msg::{
+    AdminMsg, DepositMsg, ExecuteMsg, LoanMsg, MigrateMsg, QueryMsg, ReceiveMsg, RepayMsg, TransactMsg, WithdrawMsg,
+};
+use crate::state::PENDING_ROLLOVER;
+use crate::types::{Operation, PendingRollover, PoolConfig};
+
+// Every ContractError variant, each reached the way a user of the cw20 pool would hit it
+
+fn setup() -> (App, Cw20Pool) {
+    let mut app = native_app();
+    let pool = instantiate_cw20_pool(&mut app);
+    (app, pool)
+}
+
+fn deposit_msg(pool: &Cw20Pool, amount: u128) -> TransactMsg {
+    TransactMsg::Deposit(DepositMsg { denom: pool.asset_denom(), amount: Uint128::new(amount) })
+}
+
+fn repay_msg(pool: &Cw20Pool, principal: u128) -> TransactMsg {
+    TransactMsg::Repay(RepayMsg {
+        loan_id: 1,
+        asset_denom: pool.asset_denom(),
+        asset_principle: Uint128::new(principal),
+        collateral_denom: pool.collateral_denom(),
+    })
+}
+
+fn loan_msg(pool: &Cw20Pool, amount: u128) -> TransactMsg {
+    TransactMsg::Loan(LoanMsg {
+        asset_denom: pool.asset_denom(),
+        asset_amount: Uint128::new(amount),
+        collateral_denom: pool.collateral_denom(),
+    })
+}
+
+fn instantiate_err(app: &mut App, config: PoolConfig) -> ContractError {
+    let oracle = instantiate_oracle(app, Decimal::one());
+    let code_id = app.store_code(pool_contract());
+    let msg = instantiate_pool_msg(config, &oracle);
+    app.instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "pool", None)
+        .unwrap_err()
+        .downcast()
+        .unwrap()
+}
+
+#[test]
+fn std() {
+    let (mut app, pool) = setup();
+
+    let msg = ExecuteMsg::Receive(cw20::Cw20ReceiveMsg {
+        sender: LENDER.to_string(),
+        amount: Uint128::new(1_000),
+        msg: b"{\"unknown\":{}}".to_vec().into(),
+    });
+    let err: ContractError = app
+        .execute_contract(pool.asset.clone(), pool.pool.clone(), &msg, &[])
+        .unwrap_err()
+        .downcast()
+        .unwrap();
+    assert!(matches!(err, ContractError::Std(StdError::ParseErr { .. })));
+}
+
+#[test]
+fn payment() {
+    let (mut app, pool) = setup();
+    approve(&mut app, &pool.asset, LENDER, &pool.pool, 1_000, None);
+
+    // cw20 deposits don't take native coins
+    let err: ContractError = app
+        .execute_contract(
+            Addr::unchecked(LENDER),
+            pool.pool.clone(),
+            &ExecuteMsg::Transact(deposit_msg(&pool, 1_000)),
+            &coins(1_000, ASSET),
+        )
+        .unwrap_err()
+        .downcast()
+        .unwrap();
+    assert_eq!(ContractError::Payment(PaymentError::NonPayable {}), err);
+}
+
+#[test]
+fn invalid_asset() {
+    let (mut app, pool) = setup();
+
+    let msg = TransactMsg::Deposit(DepositMsg { denom: pool.collateral_denom(), amount: Uint128::new(1_000) });
+    assert_eq!(ContractError::InvalidAsset {}, transact(&mut app, &pool, LENDER, msg).unwrap_err());
+
+    let err = send(&mut app, &pool.collateral, LENDER, &pool.pool, 1_000, &ReceiveMsg::Deposit {}).unwrap_err();
+    assert_eq!(ContractError::InvalidAsset {}, err);
+}
+
+#[test]
+fn invalid_collateral() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+
+    let msg = ReceiveMsg::PostCollateral { asset_amount: Uint128::new(1_000) };
+    let err = send(&mut app, &pool.asset, BORROWER, &pool.pool, 2_000, &msg).unwrap_err();
+    assert_eq!(ContractError::InvalidCollateral {}, err);
+}
+
+#[test]
+fn std_err() {
+    let mut deps = mock_dependencies();
+    set_contract_version(deps.as_mut().storage, concat!("crates.io:", env!("CARGO_PKG_NAME")), "one").unwrap();
+
+    let err = migrate(deps.as_mut(), mock_env(), MigrateMsg { oracle: None, liquidation: None }).unwrap_err();
+    assert!(matches!(err, ContractError::StdErr { kind, .. } if kind == "SemVer"));
+}
+
+#[test]
+fn unauthorized() {
+    let (mut app, pool) = setup();
+
+    let msg = ExecuteMsg::Admin(AdminMsg::SetGuardian { guardian: Some(KEEPER.to_string()) });
+    let err: ContractError = app
+        .execute_contract(Addr::unchecked(LENDER), pool.pool.clone(), &msg, &[])
+        .unwrap_err()
+        .downcast()
+        .unwrap();
+    assert_eq!(ContractError::Unauthorized {}, err);
+}
+
+#[test]
+fn lockin_time_period_active() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+
+    let msg = TransactMsg::Withdraw(WithdrawMsg { denom: pool.asset_denom(), amount: Uint128::new(1_000) });
+    assert_eq!(ContractError::LockinTimePeriodActive {}, transact(&mut app, &pool, LENDER, msg).unwrap_err());
+}
+
+#[test]
+fn allowance_expired() {
+    let (mut app, pool) = setup();
+    let expires = Expiration::AtHeight(app.block_info().height + 1);
+    approve(&mut app, &pool.asset, LENDER, &pool.pool, 1_000, Some(expires));
+    advance(&mut app, 60);
+
+    let err = transact(&mut app, &pool, LENDER, deposit_msg(&pool, 1_000)).unwrap_err();
+    assert_eq!(ContractError::AllowanceExpired {}, err);
+}
+
+#[test]
+fn invalid_funds() {
+    // native coins are attached to messages, the pool never pulls them with an allowance
+    let err = Amount::native(1_000, ASSET)
+        .transfer_from_msg(&Addr::unchecked(LENDER), &Addr::unchecked("pool"))
+        .unwrap_err();
+    assert_eq!(ContractError::InvalidFunds { denom: ASSET.to_string() }, err);
+}
+
+#[test]
+fn insufficient_allowance() {
+    let (mut app, pool) = setup();
+    approve(&mut app, &pool.asset, LENDER, &pool.pool, 999, None);
+
+    let err = transact(&mut app, &pool, LENDER, deposit_msg(&pool, 1_000)).unwrap_err();
+    assert_eq!(ContractError::InsufficientAllowance {}, err);
+}
+
+#[test]
+fn pool_matured() {
+    let (mut app, pool) = setup();
+    approve(&mut app, &pool.asset, LENDER, &pool.pool, 1_000, None);
+    advance(&mut app, YEAR + 1);
+
+    let err = transact(&mut app, &pool, LENDER, deposit_msg(&pool, 1_000)).unwrap_err();
+    assert_eq!(ContractError::PoolMatured {}, err);
+}
+
+#[test]
+fn pool_not_matured() {
+    let (mut app, pool) = setup();
+
+    let err = transact(&mut app, &pool, KEEPER, TransactMsg::Settle { limit: None }).unwrap_err();
+    assert_eq!(ContractError::PoolNotMatured {}, err);
+}
+
+#[test]
+fn pool_settled() {
+    let (mut app, pool) = setup();
+    advance(&mut app, YEAR + 1);
+    transact(&mut app, &pool, KEEPER, TransactMsg::Settle { limit: None }).unwrap();
+
+    let err = transact(&mut app, &pool, KEEPER, TransactMsg::Settle { limit: None }).unwrap_err();
+    assert_eq!(ContractError::PoolSettled {}, err);
+}
+
+#[test]
+fn pool_not_settled() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+    advance(&mut app, YEAR + 1);
+
+    let err = transact(&mut app, &pool, LENDER, TransactMsg::Redeem {}).unwrap_err();
+    assert_eq!(ContractError::PoolNotSettled {}, err);
+}
+
+#[test]
+fn undercollateralized() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+
+    // 2x collateral at half the strike price is right at 1, below the 1.25 threshold
+    set_price(&mut app, &pool.oracle, Decimal::percent(50));
+    approve(&mut app, &pool.collateral, BORROWER, &pool.pool, 2_000, None);
+
+    let err = transact(&mut app, &pool, BORROWER, loan_msg(&pool, 1_000)).unwrap_err();
+    assert_eq!(ContractError::Undercollateralized {}, err);
+}
+
+#[test]
+fn position_healthy() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+    loan_cw20(&mut app, &pool, BORROWER, 1_000);
+
+    let msg = ReceiveMsg::Liquidate { loan_id: 1 };
+    let err = send(&mut app, &pool.asset, KEEPER, &pool.pool, 500, &msg).unwrap_err();
+    assert_eq!(ContractError::PositionHealthy {}, err);
+}
+
+#[test]
+fn invalid_config() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+
+    let mut config = pool_config(ASSET, COLLATERAL, now);
+    config.maturationdate = now;
+    let err = instantiate_err(&mut app, config);
+    assert!(matches!(err, ContractError::InvalidConfig { .. }));
+}
+
+#[test]
+fn paused() {
+    let (mut app, pool) = setup();
+    let msg = ExecuteMsg::Admin(AdminMsg::UpdatePause { operations: vec![Operation::Deposit], paused: true });
+    app.execute_contract(Addr::unchecked(ADMIN), pool.pool.clone(), &msg, &[])
+        .unwrap();
+
+    let err = send(&mut app, &pool.asset, LENDER, &pool.pool, 1_000, &ReceiveMsg::Deposit {}).unwrap_err();
+    assert_eq!(ContractError::Paused { operation: "deposit".to_string() }, err);
+}
+
+#[test]
+fn invalid_migration() {
+    let mut deps = mock_dependencies();
+    set_contract_version(deps.as_mut().storage, "crates.io:cw20-base", "0.16.0").unwrap();
+
+    let err = migrate(deps.as_mut(), mock_env(), MigrateMsg { oracle: None, liquidation: None }).unwrap_err();
+    assert_eq!(
+        ContractError::InvalidMigration { contract: "crates.io:cw20-base".to_string(), version: "0.16.0".to_string() },
+        err
+    );
+}
+
+#[test]
+fn overflow() {
+    let (app, pool) = setup();
+
+    let err = app
+        .wrap()
+        .query_wasm_smart::<crate::msg::LoanQuoteResponse>(&pool.pool, &QueryMsg::GetLoanQuote { amount: Uint128::MAX })
+        .unwrap_err();
+    assert!(err.to_string().contains(&ContractError::Overflow {}.to_string()));
+}
+
+#[test]
+fn excessive_funds() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+    loan_cw20(&mut app, &pool, BORROWER, 1_000);
+    approve(&mut app, &pool.asset, BORROWER, &pool.pool, 2_000, None);
+
+    let err = transact(&mut app, &pool, BORROWER, repay_msg(&pool, 1_001)).unwrap_err();
+    assert_eq!(ContractError::ExcessiveFunds {}, err);
+}
+
+#[test]
+fn collateral_forfeited() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+    loan_cw20(&mut app, &pool, BORROWER, 1_000);
+    approve(&mut app, &pool.asset, BORROWER, &pool.pool, 1_100, None);
+    advance(&mut app, YEAR + 1);
+
+    let err = transact(&mut app, &pool, BORROWER, repay_msg(&pool, 1_000)).unwrap_err();
+    assert_eq!(ContractError::CollateralForfeited {}, err);
+}
+
+#[test]
+fn insufficient_ocf() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+
+    let mut config = pool_config(ASSET, COLLATERAL, now);
+    config.overcollateralizationfactor = Decimal::percent(90);
+    assert_eq!(ContractError::InsufficientOCF {}, instantiate_err(&mut app, config));
+}
+
+#[test]
+fn insufficient_collateral() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+
+    let msg = ReceiveMsg::PostCollateral { asset_amount: Uint128::new(1_000) };
+    let err = send(&mut app, &pool.collateral, BORROWER, &pool.pool, 1_999, &msg).unwrap_err();
+    assert_eq!(ContractError::InsufficientCollateral {}, err);
+}
+
+#[test]
+fn insufficient_funds() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 1_000);
+    approve(&mut app, &pool.collateral, BORROWER, &pool.pool, 4_000, None);
+
+    // the pool only holds 1_000 to lend
+    let err = transact(&mut app, &pool, BORROWER, loan_msg(&pool, 2_000)).unwrap_err();
+    assert_eq!(ContractError::InsufficientFunds {}, err);
+}
+
+#[test]
+fn position_not_available() {
+    let (mut app, pool) = setup();
+    approve(&mut app, &pool.asset, BORROWER, &pool.pool, 1_000, None);
+
+    let err = transact(&mut app, &pool, BORROWER, repay_msg(&pool, 1_000)).unwrap_err();
+    assert_eq!(ContractError::PositionNotAvailable {}, err);
+}
+
+#[test]
+fn withdrawals_queued() {
+    let (mut app, pool) = setup();
+    deposit_cw20(&mut app, &pool, LENDER, 1_000);
+    loan_cw20(&mut app, &pool, BORROWER, 800);
+    advance(&mut app, YEAR / 10);
+
+    // 800 of the withdrawal waits for the loan to be repaid
+    let withdraw = TransactMsg::Withdraw(WithdrawMsg { denom: pool.asset_denom(), amount: Uint128::new(1_000) });
+    transact(&mut app, &pool, LENDER, withdraw).unwrap();
+
+    let err = transact(&mut app, &pool, LENDER, TransactMsg::WithdrawInterest {}).unwrap_err();
+    assert_eq!(ContractError::WithdrawalsQueued {}, err);
+}
+
+#[test]
+fn rollover_pending() {
+    let mut deps = mock_dependencies();
+    let rollover = PendingRollover {
+        loan_id: 1,
+        holder: Addr::unchecked(BORROWER),
+        target_pool: Addr::unchecked("successor"),
+        amount_due: Uint128::new(1_050),
+        repaid: Uint128::zero(),
+    };
+    PENDING_ROLLOVER.save(deps.as_mut().storage, &rollover).unwrap();
+
+    // nothing but the successor's repayment gets in while the rollover is waiting for it
+    let msg = ExecuteMsg::Transact(TransactMsg::Deposit(DepositMsg { denom: ASSET.to_string(), amount: Uint128::new(1_050) }));
+    let err = execute(deps.as_mut(), mock_env(), mock_info("successor", &coins(1_050, ASSET)), msg).unwrap_err();
+    assert_eq!(ContractError::RolloverPending {}, err);
+}
@@ -0,0 +1,167 @@
+use cosmwasm_std::{coins, Addr, Decimal, Uint128};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{AdminMsg, ExecuteMsg, InstantiateMsg, LoanQuoteResponse, PoolDetailsResponse, QueryMsg, TransactMsg, UpdateConfigMsg};
+use crate::types::FeeConfig;
+
+// Half of the interest repaid goes to the protocol, which leaves the lenders 5% of the 10%,
+// and 1% of every loan is kept as an origination fee
+fn fees() -> FeeConfig {
+    FeeConfig {
+        reserve_factor: Decimal::percent(50),
+        origination_fee: Decimal::percent(1),
+    }
+}
+
+fn pool_with_fees(app: &mut App, fees: FeeConfig) -> Result<(Addr, Addr), ContractError> {
+    let now = app.block_info().time.seconds();
+    let oracle = instantiate_oracle(app, Decimal::one());
+    let code_id = app.store_code(pool_contract());
+    let msg = InstantiateMsg {
+        fees: Some(fees),
+        ..instantiate_pool_msg(pool_config(ASSET, COLLATERAL, now), &oracle)
+    };
+    let pool = app
+        .instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "pool", None)
+        .map_err(|err| err.downcast::<ContractError>().unwrap())?;
+    Ok((pool, oracle))
+}
+
+// The borrower takes 1_000 out of 10_000 against 2_000 collateral
+fn borrowed_pool() -> (App, Addr, Addr) {
+    let mut app = native_app();
+    let (pool, oracle) = pool_with_fees(&mut app, fees()).unwrap();
+    deposit(&mut app, &pool, LENDER, 10_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+    (app, pool, oracle)
+}
+
+fn details(app: &App, pool: &Addr) -> PoolDetailsResponse {
+    app.wrap().query_wasm_smart(pool, &QueryMsg::AllDetails {}).unwrap()
+}
+
+fn collect_msg(recipient: &str) -> ExecuteMsg {
+    ExecuteMsg::Admin(AdminMsg::CollectProtocolFees { recipient: recipient.to_string() })
+}
+
+#[test]
+fn origination_fee_is_kept_out_of_the_loan() {
+    let (app, pool, _) = borrowed_pool();
+
+    let quote: LoanQuoteResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::GetLoanQuote { amount: Uint128::new(1_000) })
+        .unwrap();
+    assert_eq!(quote.fee.u128(), 10);
+
+    // the full 1_000 is owed, only 990 is paid out
+    assert_eq!(balance(&app, BORROWER, ASSET), INITIAL_BALANCE + 990);
+    let details = details(&app, &pool);
+    assert_eq!(details.fees, fees());
+    assert_eq!(details.total_protocol_earnings.u128(), 10);
+    assert_eq!(details.total_asset_available.u128(), 9_000);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn reserve_factor_takes_a_share_of_the_interest_repaid() {
+    let (mut app, pool, _) = borrowed_pool();
+    advance(&mut app, YEAR / 2);
+
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 1_000), &coins(1_050, ASSET))
+        .unwrap();
+
+    // 10 from the origination fee and half of the 50 interest
+    let details = details(&app, &pool);
+    assert_eq!(details.total_protocol_earnings.u128(), 35);
+    assert_eq!(details.total_asset_available.u128(), 10_025);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn reserve_factor_takes_a_share_of_the_interest_liquidated() {
+    let (mut app, pool, oracle) = borrowed_pool();
+    advance(&mut app, YEAR / 2);
+    set_price(&mut app, &oracle, Decimal::percent(60));
+
+    let msg = ExecuteMsg::Transact(TransactMsg::Liquidate {
+        loan_id: 1,
+        repay_amount: Uint128::new(1_050),
+    });
+    app.execute_contract(Addr::unchecked(KEEPER), pool.clone(), &msg, &coins(1_050, ASSET))
+        .unwrap();
+
+    // half of the 1_050 owed is repaid, interest first
+    let details = details(&app, &pool);
+    assert_eq!(details.total_protocol_earnings.u128(), 35);
+    assert_eq!(details.total_asset_available.u128(), 9_500);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn only_the_admin_collects_the_earnings() {
+    let (mut app, pool, _) = borrowed_pool();
+
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER), pool.clone(), &collect_msg(LENDER), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+    app.execute_contract(Addr::unchecked(ADMIN), pool.clone(), &collect_msg(KEEPER), &[])
+        .unwrap();
+    assert_eq!(balance(&app, KEEPER, ASSET), INITIAL_BALANCE + 10);
+    assert_eq!(details(&app, &pool).total_protocol_earnings.u128(), 0);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+
+    // nothing is left to collect
+    app.execute_contract(Addr::unchecked(ADMIN), pool.clone(), &collect_msg(KEEPER), &[])
+        .unwrap();
+    assert_eq!(balance(&app, KEEPER, ASSET), INITIAL_BALANCE + 10);
+}
+
+#[test]
+fn earnings_are_not_lent_out_or_withdrawn() {
+    let mut app = native_app();
+    let (pool, _) = pool_with_fees(&mut app, fees()).unwrap();
+    deposit(&mut app, &pool, LENDER, 1_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+
+    // the 10 kept from the loan sits in the pool but is not available
+    assert_eq!(balance(&app, pool.as_str(), ASSET), 10);
+    let err = app
+        .execute_contract(Addr::unchecked(KEEPER), pool.clone(), &loan_msg(5), &coins(10, COLLATERAL))
+        .unwrap_err();
+    assert_eq!(ContractError::InsufficientFunds {}, err.downcast().unwrap());
+}
+
+#[test]
+fn invalid_fee_config_is_rejected() {
+    let mut app = native_app();
+
+    // above the 10% - 5% spread
+    let err = pool_with_fees(&mut app, FeeConfig { reserve_factor: Decimal::percent(51), ..fees() }).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidConfig { .. }));
+
+    let err = pool_with_fees(&mut app, FeeConfig { origination_fee: Decimal::one(), ..fees() }).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidConfig { .. }));
+
+    // raising the lend interest rate has to leave room for the reserve factor
+    let (pool, _) = pool_with_fees(&mut app, fees()).unwrap();
+    let msg = ExecuteMsg::Admin(AdminMsg::UpdateConfig(Box::new(UpdateConfigMsg {
+        debtinterestrate: None,
+        lendinterestrate: Some(Decimal::percent(6)),
+        overcollateralizationfactor: None,
+        strikeprice: None,
+        lock_in_period: None,
+        maturationdate: None,
+        oracle: None,
+        liquidation: None,
+        fees: None,
+    })));
+    let err = app.execute_contract(Addr::unchecked(ADMIN), pool, &msg, &[]).unwrap_err();
+    assert!(matches!(err.downcast::<ContractError>().unwrap(), ContractError::InvalidConfig { .. }));
+}
@@ -0,0 +1,53 @@
+use std::str::FromStr;
+
+use cosmwasm_std::{coins, Addr, Decimal, Uint128};
+use cw_multi_test::Executor;
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{HealthFactorResponse, QueryMsg};
+
+#[test]
+fn health_factor_follows_oracle_price_and_accrued_interest() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let (pool, oracle) = instantiate_pool_with_oracle(&mut app, pool_config(ASSET, COLLATERAL, now));
+
+    deposit(&mut app, &pool, LENDER, 10_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+
+    let query = QueryMsg::HealthFactor { loan_id: 1 };
+
+    // at the strike price the position is worth its over-collateralization factor
+    let health: HealthFactorResponse = app.wrap().query_wasm_smart(&pool, &query).unwrap();
+    assert_eq!(
+        health,
+        HealthFactorResponse {
+            collateral_value: Uint128::new(2_000),
+            debt: Uint128::new(1_000),
+            health_factor: Decimal::percent(200),
+        }
+    );
+
+    set_price(&mut app, &oracle, Decimal::percent(60));
+    advance(&mut app, YEAR / 2);
+
+    let health: HealthFactorResponse = app.wrap().query_wasm_smart(&pool, &query).unwrap();
+    assert_eq!(health.collateral_value.u128(), 1_200);
+    assert_eq!(health.debt.u128(), 1_050);
+    assert_eq!(health.health_factor, Decimal::from_str("1.142857142857142857").unwrap());
+}
+
+#[test]
+fn health_factor_needs_a_loan() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+
+    let err = app
+        .wrap()
+        .query_wasm_smart::<HealthFactorResponse>(&pool, &QueryMsg::HealthFactor { loan_id: 1 })
+        .unwrap_err();
+    assert!(err.to_string().contains(&ContractError::PositionNotAvailable {}.to_string()));
+}
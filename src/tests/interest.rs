@@ -0,0 +1,127 @@
+use std::str::FromStr;
+
+use cosmwasm_std::{coins, from_json, Addr, Decimal, Uint128};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+use crate::msg::{
+    AdminMsg, ExecuteMsg, LoanInfo, LoanQuoteResponse, PoolStateResponse, QueryMsg, RepayQuoteResponse, UpdateConfigMsg,
+};
+use crate::types::PoolState;
+
+fn pool_state(app: &App, pool: &Addr) -> PoolStateResponse {
+    app.wrap().query_wasm_smart(pool, &QueryMsg::PoolState {}).unwrap()
+}
+
+#[test]
+fn pool_totals_follow_the_indices() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+
+    deposit(&mut app, &pool, LENDER, 6_000);
+    deposit(&mut app, &pool, LENDER2, 4_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+    advance(&mut app, YEAR / 2);
+
+    let state = pool_state(&app, &pool);
+    assert_eq!(state.supply_index, Decimal::from_str("1.025").unwrap());
+    assert_eq!(state.borrow_index, Decimal::from_str("1.05").unwrap());
+    assert_eq!(state.total_supplied.u128(), 10_000);
+    assert_eq!(state.total_supply_interest.u128(), 250);
+    assert_eq!(state.total_borrowed.u128(), 1_000);
+    assert_eq!(state.total_debt_interest.u128(), 50);
+    assert_eq!(state.utilization, Decimal::percent(10));
+
+    // nothing accrues past maturity
+    advance(&mut app, YEAR);
+    let state = pool_state(&app, &pool);
+    assert_eq!(state.total_supply_interest.u128(), 500);
+    assert_eq!(state.total_debt_interest.u128(), 100);
+}
+
+#[test]
+fn rate_changes_apply_to_the_loans_opened_after() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+
+    deposit(&mut app, &pool, LENDER, 10_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+    advance(&mut app, YEAR / 2);
+
+    let msg = ExecuteMsg::Admin(AdminMsg::UpdateConfig(Box::new(UpdateConfigMsg {
+        debtinterestrate: Some(Decimal::percent(20)),
+        lendinterestrate: None,
+        overcollateralizationfactor: None,
+        strikeprice: None,
+        lock_in_period: None,
+        maturationdate: None,
+        oracle: None,
+        liquidation: None,
+        fees: None,
+    })));
+    app.execute_contract(Addr::unchecked(ADMIN), pool.clone(), &msg, &[])
+        .unwrap();
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+    advance(&mut app, YEAR / 4);
+
+    // the first loan keeps its 10%, 5% for the first half year then 2.5% on top of that for the quarter
+    let quote: RepayQuoteResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::GetRepayQuote { loan_id: 1 })
+        .unwrap();
+    assert_eq!(quote.principal.u128(), 1_000);
+    assert_eq!(quote.interest.u128(), 77);
+
+    // the second one was opened at 20%, 5% for the quarter
+    let quote: RepayQuoteResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::GetRepayQuote { loan_id: 2 })
+        .unwrap();
+    assert_eq!(quote.interest.u128(), 50);
+    let loan: LoanInfo = app.wrap().query_wasm_smart(&pool, &QueryMsg::Loan { loan_id: 2 }).unwrap();
+    assert_eq!(loan.rate, Decimal::percent(20));
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+
+    // the index of the old rate is dropped at the next accrual once its last loan is repaid
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 1_000), &coins(1_077, ASSET))
+        .unwrap();
+    deposit(&mut app, &pool, LENDER2, 1_000);
+    let raw = app.wrap().query_wasm_raw(&pool, b"pool_state".as_slice()).unwrap().unwrap();
+    let state: PoolState = from_json(raw).unwrap();
+    assert_eq!(state.borrow_indices.iter().map(|index| index.rate).collect::<Vec<_>>(), vec![Decimal::percent(20)]);
+    assert_eq!(pool_state(&app, &pool).borrow_index, Decimal::percent(105));
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn interest_compounds_at_every_accrual() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+    deposit(&mut app, &pool, LENDER, 10_000);
+
+    // the quote is the index grown once until maturity
+    let quote: LoanQuoteResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::GetLoanQuote { amount: Uint128::new(1_000) })
+        .unwrap();
+    assert_eq!(quote.interest.u128(), 100);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+
+    // a deposit half way accrues 5%, the second half year's 5% is on top of it
+    advance(&mut app, YEAR / 2);
+    deposit(&mut app, &pool, LENDER2, 1_000);
+    advance(&mut app, YEAR / 2);
+    assert_eq!(pool_state(&app, &pool).borrow_index, Decimal::from_str("1.1025").unwrap());
+    let quote: RepayQuoteResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::GetRepayQuote { loan_id: 1 })
+        .unwrap();
+    assert_eq!(quote.interest.u128(), 103);
+}
@@ -0,0 +1,153 @@
+use cosmwasm_std::{coins, Addr, Decimal, Uint128};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{ExecuteMsg, LoanInfo, QueryMsg, RepayQuoteResponse, TransactMsg};
+
+fn liquidate_msg(repay_amount: u128) -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::Liquidate {
+        loan_id: 1,
+        repay_amount: Uint128::new(repay_amount),
+    })
+}
+
+// The borrower takes 1_000 against 2_000 collateral at a price of 1
+fn borrowed_pool() -> (App, Addr, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let (pool, oracle) = instantiate_pool_with_oracle(&mut app, pool_config(ASSET, COLLATERAL, now));
+
+    deposit(&mut app, &pool, LENDER, 10_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+    (app, pool, oracle)
+}
+
+#[test]
+fn healthy_position_cannot_be_liquidated() {
+    let (mut app, pool, oracle) = borrowed_pool();
+
+    // 2_000 * 0.7 / 1_000 is still above the 125% threshold
+    set_price(&mut app, &oracle, Decimal::percent(70));
+
+    let err = app
+        .execute_contract(Addr::unchecked(KEEPER), pool, &liquidate_msg(500), &coins(500, ASSET))
+        .unwrap_err();
+    assert_eq!(ContractError::PositionHealthy {}, err.downcast().unwrap());
+}
+
+#[test]
+fn keeper_repays_up_to_close_factor_and_takes_collateral_with_bonus() {
+    let (mut app, pool, oracle) = borrowed_pool();
+    set_price(&mut app, &oracle, Decimal::percent(60));
+
+    // asking for more than half the debt only repays half, the rest is refunded
+    app.execute_contract(Addr::unchecked(KEEPER), pool.clone(), &liquidate_msg(800), &coins(800, ASSET))
+        .unwrap();
+
+    // 500 repaid buys 500 * 1.05 / 0.6 collateral
+    assert_eq!(balance(&app, KEEPER, ASSET), INITIAL_BALANCE - 500);
+    assert_eq!(balance(&app, KEEPER, COLLATERAL), INITIAL_BALANCE + 875);
+    assert_eq!(balance(&app, pool.as_str(), COLLATERAL), 1_125);
+
+    let quote: RepayQuoteResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::GetRepayQuote { loan_id: 1 })
+        .unwrap();
+    assert_eq!((quote.principal.u128(), quote.interest.u128(), quote.collateral.u128()), (500, 0, 1_125));
+
+    // the borrower gets back what is left of the collateral by repaying the rest
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 500), &coins(500, ASSET))
+        .unwrap();
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE - 875);
+    assert_eq!(balance(&app, pool.as_str(), COLLATERAL), 0);
+}
+
+#[test]
+fn underwater_position_gives_up_all_collateral() {
+    let (mut app, pool, oracle) = borrowed_pool();
+    set_price(&mut app, &oracle, Decimal::percent(20));
+
+    // 500 would buy 2_625 collateral, only 2_000 is there so it's sold for 2_000 * 0.2 / 1.05
+    app.execute_contract(Addr::unchecked(KEEPER), pool.clone(), &liquidate_msg(500), &coins(500, ASSET))
+        .unwrap();
+
+    assert_eq!(balance(&app, KEEPER, ASSET), INITIAL_BALANCE - 380);
+    assert_eq!(balance(&app, KEEPER, COLLATERAL), INITIAL_BALANCE + 2_000);
+    assert_eq!(balance(&app, pool.as_str(), COLLATERAL), 0);
+}
+
+#[test]
+fn collateral_left_after_the_debt_goes_back_to_the_holder() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let oracle = instantiate_oracle(&mut app, Decimal::one());
+    let code_id = app.store_code(pool_contract());
+    let mut msg = instantiate_pool_msg(pool_config(ASSET, COLLATERAL, now), &oracle);
+    msg.liquidation.close_factor = Decimal::one();
+    let pool = app
+        .instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "pool", None)
+        .unwrap();
+    deposit(&mut app, &pool, LENDER, 10_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+    set_price(&mut app, &oracle, Decimal::percent(60));
+
+    // the whole 1_000 buys 1_750 collateral, the 250 left is the borrower's and the loan is closed
+    app.execute_contract(Addr::unchecked(KEEPER), pool.clone(), &liquidate_msg(1_000), &coins(1_000, ASSET))
+        .unwrap();
+    assert_eq!(balance(&app, KEEPER, COLLATERAL), INITIAL_BALANCE + 1_750);
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE - 1_750);
+    assert_eq!(balance(&app, pool.as_str(), COLLATERAL), 0);
+    assert!(app
+        .wrap()
+        .query_wasm_smart::<LoanInfo>(&pool, &QueryMsg::Loan { loan_id: 1 })
+        .is_err());
+
+    // so none of it is forfeited to the lenders at settlement
+    advance(&mut app, YEAR + 1);
+    let res = app
+        .execute_contract(Addr::unchecked(KEEPER), pool.clone(), &ExecuteMsg::Transact(TransactMsg::Settle { limit: None }), &[])
+        .unwrap();
+    let forfeited = res
+        .events
+        .iter()
+        .flat_map(|event| &event.attributes)
+        .find(|attr| attr.key == "collateral_forfeited")
+        .unwrap();
+    assert_eq!(forfeited.value, "0");
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn loan_cannot_start_undercollateralized() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let (pool, oracle) = instantiate_pool_with_oracle(&mut app, pool_config(ASSET, COLLATERAL, now));
+    deposit(&mut app, &pool, LENDER, 10_000);
+
+    // the strike price asks for 2_000 collateral, which the oracle values at 1_200
+    set_price(&mut app, &oracle, Decimal::percent(60));
+
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool, &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap_err();
+    assert_eq!(ContractError::Undercollateralized {}, err.downcast().unwrap());
+}
+
+#[test]
+fn instantiate_validates_liquidation_config() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let oracle = instantiate_oracle(&mut app, Decimal::one());
+    let code_id = app.store_code(pool_contract());
+
+    let mut msg = instantiate_pool_msg(pool_config(ASSET, COLLATERAL, now), &oracle);
+    msg.liquidation.close_factor = Decimal::zero();
+
+    let err = app
+        .instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "pool", None)
+        .unwrap_err();
+    assert!(matches!(err.downcast().unwrap(), ContractError::InvalidConfig { .. }));
+}
@@ -0,0 +1,156 @@
+use cosmwasm_std::{coins, Addr, Timestamp, Uint128};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+use crate::msg::{
+    BorrowerInfo, LenderInfo, ListBorrowersResponse, ListLendersResponse, ListLoansResponse, LoanInfo, QueryMsg,
+};
+
+fn list_lenders(app: &App, pool: &Addr, start_after: Option<&str>, limit: Option<u32>) -> Vec<LenderInfo> {
+    let msg = QueryMsg::ListLenders { start_after: start_after.map(Addr::unchecked), limit };
+    let res: ListLendersResponse = app.wrap().query_wasm_smart(pool, &msg).unwrap();
+    res.lenders
+}
+
+fn list_borrowers(app: &App, pool: &Addr, start_after: Option<&str>, limit: Option<u32>) -> Vec<BorrowerInfo> {
+    let msg = QueryMsg::ListBorrowers { start_after: start_after.map(Addr::unchecked), limit };
+    let res: ListBorrowersResponse = app.wrap().query_wasm_smart(pool, &msg).unwrap();
+    res.borrowers
+}
+
+fn list_loans(app: &App, pool: &Addr, start_after: Option<u64>, limit: Option<u32>) -> Vec<LoanInfo> {
+    let msg = QueryMsg::ListLoans { start_after, limit };
+    let res: ListLoansResponse = app.wrap().query_wasm_smart(pool, &msg).unwrap();
+    res.loans
+}
+
+#[test]
+fn lenders_are_listed_in_pages_with_accrued_interest() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+
+    for lender in [LENDER, LENDER2, KEEPER] {
+        deposit(&mut app, &pool, lender, 10_000);
+    }
+    advance(&mut app, YEAR / 2);
+
+    let page = list_lenders(&app, &pool, None, Some(2));
+    assert_eq!(
+        page[0],
+        LenderInfo {
+            lender: Addr::unchecked(KEEPER),
+            principal: Uint128::new(10_000),
+            interest: Uint128::new(250),
+            deposited_at: Timestamp::from_seconds(now),
+        }
+    );
+    let names: Vec<_> = page.iter().map(|info| info.lender.to_string()).collect();
+    assert_eq!(names, vec![KEEPER, LENDER]);
+
+    let page = list_lenders(&app, &pool, Some(LENDER), Some(2));
+    let names: Vec<_> = page.iter().map(|info| info.lender.to_string()).collect();
+    assert_eq!(names, vec![LENDER2]);
+
+    assert!(list_lenders(&app, &pool, Some(LENDER2), None).is_empty());
+}
+
+#[test]
+fn loans_are_listed_with_interest_owed_until_now() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+    deposit(&mut app, &pool, LENDER, 10_000);
+
+    for (borrower, amount) in [(BORROWER, 1_000), (KEEPER, 2_000)] {
+        app.execute_contract(Addr::unchecked(borrower), pool.clone(), &loan_msg(amount), &coins(2 * amount, COLLATERAL))
+            .unwrap();
+    }
+    advance(&mut app, YEAR / 2);
+
+    assert_eq!(
+        list_loans(&app, &pool, None, None),
+        vec![
+            LoanInfo {
+                id: 1,
+                borrower: Addr::unchecked(BORROWER),
+                principal: Uint128::new(1_000),
+                interest: Uint128::new(50),
+                collateral: Uint128::new(2_000),
+                opened_at: Timestamp::from_seconds(now),
+                rate: DEBT_RATE,
+            },
+            LoanInfo {
+                id: 2,
+                borrower: Addr::unchecked(KEEPER),
+                principal: Uint128::new(2_000),
+                interest: Uint128::new(100),
+                collateral: Uint128::new(4_000),
+                opened_at: Timestamp::from_seconds(now),
+                rate: DEBT_RATE,
+            },
+        ]
+    );
+    let page = list_loans(&app, &pool, Some(1), Some(1));
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].id, 2);
+}
+
+#[test]
+fn borrowers_are_listed_with_their_loans_added_up() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+    deposit(&mut app, &pool, LENDER, 10_000);
+
+    for (borrower, amount) in [(KEEPER, 2_000), (BORROWER, 1_000), (BORROWER, 500)] {
+        app.execute_contract(Addr::unchecked(borrower), pool.clone(), &loan_msg(amount), &coins(2 * amount, COLLATERAL))
+            .unwrap();
+    }
+    advance(&mut app, YEAR / 2);
+
+    assert_eq!(
+        list_borrowers(&app, &pool, None, None),
+        // the index orders the shorter address first
+        vec![
+            BorrowerInfo {
+                borrower: Addr::unchecked(KEEPER),
+                principal: Uint128::new(2_000),
+                interest: Uint128::new(100),
+                collateral: Uint128::new(4_000),
+                loans: 1,
+            },
+            BorrowerInfo {
+                borrower: Addr::unchecked(BORROWER),
+                principal: Uint128::new(1_500),
+                interest: Uint128::new(75),
+                collateral: Uint128::new(3_000),
+                loans: 2,
+            },
+        ]
+    );
+
+    // a page holds whole borrowers, whatever the number of their loans
+    let page = list_borrowers(&app, &pool, Some(KEEPER), Some(1));
+    assert_eq!(page.len(), 1);
+    assert_eq!((page[0].borrower.as_str(), page[0].loans), (BORROWER, 2));
+    assert!(list_borrowers(&app, &pool, Some(BORROWER), None).is_empty());
+}
+
+#[test]
+fn list_limit_is_capped() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+
+    // more lenders than a page holds
+    for i in 0..35 {
+        let lender = format!("lender{:02}", i);
+        app.send_tokens(Addr::unchecked(LENDER), Addr::unchecked(&lender), &coins(100, ASSET))
+            .unwrap();
+        deposit(&mut app, &pool, &lender, 100);
+    }
+
+    assert_eq!(list_lenders(&app, &pool, None, None).len(), 10);
+    assert_eq!(list_lenders(&app, &pool, None, Some(100)).len(), 30);
+}
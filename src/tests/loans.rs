@@ -0,0 +1,88 @@
+use cosmwasm_std::{coins, Addr};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{ListLoansResponse, LoanInfo, QueryMsg, UserDetailsResponse};
+
+fn loans_by_borrower(app: &App, pool: &Addr, borrower: &str) -> Vec<LoanInfo> {
+    let msg = QueryMsg::LoansByBorrower { borrower: Addr::unchecked(borrower), start_after: None, limit: None };
+    let res: ListLoansResponse = app.wrap().query_wasm_smart(pool, &msg).unwrap();
+    res.loans
+}
+
+// The borrower takes 1_000 and half a year later another 500, the keeper 2_000 in between
+fn pool_with_loans() -> (App, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+    deposit(&mut app, &pool, LENDER, 10_000);
+
+    for (borrower, amount) in [(BORROWER, 1_000), (KEEPER, 2_000)] {
+        app.execute_contract(Addr::unchecked(borrower), pool.clone(), &loan_msg(amount), &coins(2 * amount, COLLATERAL))
+            .unwrap();
+    }
+    advance(&mut app, YEAR / 2);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(500), &coins(1_000, COLLATERAL))
+        .unwrap();
+    (app, pool)
+}
+
+#[test]
+fn a_borrower_holds_several_loans() {
+    let (app, pool) = pool_with_loans();
+
+    let loans = loans_by_borrower(&app, &pool, BORROWER);
+    let ids: Vec<_> = loans.iter().map(|loan| loan.id).collect();
+    assert_eq!(ids, vec![1, 3]);
+    assert_eq!(loans[0].interest.u128(), 50);
+    assert!(loans[1].opened_at > loans[0].opened_at);
+
+    let loan: LoanInfo = app.wrap().query_wasm_smart(&pool, &QueryMsg::Loan { loan_id: 3 }).unwrap();
+    assert_eq!(loan, loans[1]);
+
+    // the user details add up every loan of the borrower
+    let details: UserDetailsResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::UserDetails { user: Addr::unchecked(BORROWER) })
+        .unwrap();
+    assert_eq!(details.borrower.principal.u128(), 1_500);
+    assert_eq!(details.borrower.collateral.u128(), 3_000);
+}
+
+#[test]
+fn each_loan_is_repaid_on_its_own() {
+    let (mut app, pool) = pool_with_loans();
+
+    // only the collateral of the first loan is unlocked
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 1_000), &coins(1_100, ASSET))
+        .unwrap();
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE - 1_000);
+
+    let loans = loans_by_borrower(&app, &pool, BORROWER);
+    let ids: Vec<_> = loans.iter().map(|loan| loan.id).collect();
+    assert_eq!(ids, vec![3]);
+    assert_eq!(loans[0].principal.u128(), 500);
+
+    let err = app
+        .wrap()
+        .query_wasm_smart::<LoanInfo>(&pool, &QueryMsg::Loan { loan_id: 1 })
+        .unwrap_err();
+    assert!(err.to_string().contains(&ContractError::PositionNotAvailable {}.to_string()));
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn only_the_borrower_repays_a_loan() {
+    let (mut app, pool) = pool_with_loans();
+
+    let err = app
+        .execute_contract(Addr::unchecked(KEEPER), pool.clone(), &repay_msg(1, 1_000), &coins(1_100, ASSET))
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool, &repay_msg(9, 1_000), &coins(1_100, ASSET))
+        .unwrap_err();
+    assert_eq!(ContractError::PositionNotAvailable {}, err.downcast().unwrap());
+}
@@ -0,0 +1,241 @@
+use cosmwasm_std::testing::{mock_dependencies, mock_env};
+use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::Item;
+
+use super::*;
+use crate::contract::migrate;
+use crate::error::ContractError;
+use crate::migrations::{
+    LegacyBorrowerPosition, LegacyPoolState, LEGACY_BORROWERS, LEGACY_POOL_STATE, LEGACY_COLLATERAL_SUBMITTED, LEGACY_INTEREST_EARNED, LEGACY_PRINCIPLE_DEPLOYED, LEGACY_PRINCIPLE_TO_REPAY,
+};
+use crate::msg::MigrateMsg;
+use crate::state::{
+    loans, ASSET_CONFIG, COLLATERAL_CONFIG, LENDERS, LIQUIDATION_CONFIG, LOAN_COUNT, ORACLE, POOL_CONFIG, POOL_STATE,
+};
+use crate::types::{BorrowIndex, CoinConfig, LenderPosition, Loan};
+
+const NAME: &str = "crates.io:hashirwa_contracts";
+
+fn migrate_msg() -> MigrateMsg {
+    MigrateMsg {
+        oracle: Some("oracle".to_string()),
+        liquidation: Some(liquidation_config()),
+    }
+}
+
+/// PoolConfig as pools before 0.4.0 stored it, with rates in billionths per year
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LegacyPoolConfig {
+    name: String,
+    symbol: String,
+    maturationdate: u64,
+    debtinterestrate: Uint128,
+    strikeprice: Uint128,
+    lendinterestrate: Uint128,
+    overcollateralizationfactor: Uint128,
+    asset: String,
+    collateral: String,
+    lock_in_period: Uint128,
+}
+
+/// State as the cw20-only pools before 0.2.0 stored it, without a cw2 version
+fn legacy_pool(storage: &mut dyn cosmwasm_std::Storage, maturationdate: u64) {
+    let config = LegacyPoolConfig {
+        name: "Gold backed USDC".to_string(),
+        symbol: "gUSDC".to_string(),
+        maturationdate,
+        debtinterestrate: Uint128::new(100_000_000),
+        strikeprice: Uint128::new(1),
+        lendinterestrate: Uint128::new(50_000_000),
+        overcollateralizationfactor: Uint128::new(2),
+        asset: "asset_token".to_string(),
+        collateral: "collateral_token".to_string(),
+        lock_in_period: Uint128::new(10),
+    };
+    Item::new("pool_config").save(storage, &config).unwrap();
+    for (item, denom) in [(ASSET_CONFIG, "asset_token"), (COLLATERAL_CONFIG, "collateral_token")] {
+        item.save(storage, &CoinConfig { denom: denom.to_string(), decimals: 6 })
+            .unwrap();
+    }
+}
+
+#[test]
+fn migrate_legacy_pool() {
+    let mut deps = mock_dependencies();
+    legacy_pool(deps.as_mut().storage, YEAR);
+
+    let res = migrate(deps.as_mut(), mock_env(), migrate_msg()).unwrap();
+    assert!(res.attributes.iter().any(|a| a.key == "from_version" && a.value == "0.1.0"));
+
+    let config = POOL_CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(config.asset, "cw20:asset_token");
+    assert_eq!(config.collateral, "cw20:collateral_token");
+    assert_eq!(config.debtinterestrate, DEBT_RATE);
+    assert_eq!(config.lendinterestrate, LEND_RATE);
+    assert_eq!(config.overcollateralizationfactor, Decimal::percent(200));
+    assert_eq!(config.strikeprice, Decimal::one());
+    assert_eq!(ASSET_CONFIG.load(&deps.storage).unwrap().denom, "cw20:asset_token");
+    assert_eq!(COLLATERAL_CONFIG.load(&deps.storage).unwrap().denom, "cw20:collateral_token");
+    assert_eq!(ORACLE.load(&deps.storage).unwrap(), Addr::unchecked("oracle"));
+    assert_eq!(LIQUIDATION_CONFIG.load(&deps.storage).unwrap(), liquidation_config());
+
+    let version = get_contract_version(&deps.storage).unwrap();
+    assert_eq!(version.contract, NAME);
+    assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn migrate_folds_accrued_interest_into_scaled_balances() {
+    let mut deps = mock_dependencies();
+    let now = mock_env().block.time.seconds();
+    legacy_pool(deps.as_mut().storage, now + YEAR);
+
+    // half a year since the lender's last deposit and the borrower's loan
+    let half_year_ago = Timestamp::from_seconds(now - YEAR / 2);
+    let lender = Addr::unchecked(LENDER);
+    let borrower = Addr::unchecked(BORROWER);
+    LEGACY_PRINCIPLE_DEPLOYED
+        .save(deps.as_mut().storage, &lender, &(Uint128::new(10_000), half_year_ago))
+        .unwrap();
+    LEGACY_INTEREST_EARNED
+        .save(deps.as_mut().storage, &lender, &Uint128::new(100))
+        .unwrap();
+    LEGACY_PRINCIPLE_TO_REPAY
+        .save(deps.as_mut().storage, &borrower, &(Uint128::new(1_000), half_year_ago))
+        .unwrap();
+    LEGACY_COLLATERAL_SUBMITTED
+        .save(deps.as_mut().storage, &borrower, &(Uint128::new(2_000), half_year_ago))
+        .unwrap();
+
+    migrate(deps.as_mut(), mock_env(), migrate_msg()).unwrap();
+
+    assert_eq!(
+        LENDERS.load(&deps.storage, &lender).unwrap(),
+        LenderPosition { principal: Uint128::new(10_000), scaled: Uint128::new(10_350), deposited_at: half_year_ago }
+    );
+    let loan = loans().load(&deps.storage, 1).unwrap();
+    assert_eq!(loan.borrower, borrower);
+    assert_eq!(
+        (loan.principal, loan.scaled_debt, loan.collateral),
+        (Uint128::new(1_000), Uint128::new(1_050), Uint128::new(2_000))
+    );
+    assert!(LEGACY_PRINCIPLE_DEPLOYED.may_load(&deps.storage, &lender).unwrap().is_none());
+    assert!(LEGACY_PRINCIPLE_TO_REPAY.may_load(&deps.storage, &borrower).unwrap().is_none());
+
+    let pool_state = POOL_STATE.load(&deps.storage).unwrap();
+    assert_eq!(pool_state.total_scaled_supply.u128(), 10_350);
+    assert_eq!(pool_state.total_scaled_debt().unwrap().u128(), 1_050);
+    assert_eq!(pool_state.last_accrued, now);
+}
+
+#[test]
+fn migrate_legacy_pool_needs_oracle() {
+    let mut deps = mock_dependencies();
+    legacy_pool(deps.as_mut().storage, YEAR);
+
+    let msg = MigrateMsg { oracle: None, ..migrate_msg() };
+    let err = migrate(deps.as_mut(), mock_env(), msg).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidConfig { .. }));
+}
+
+#[test]
+fn migrate_splits_borrower_positions_into_loans() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    set_contract_version(deps.as_mut().storage, NAME, "0.4.0").unwrap();
+    POOL_CONFIG
+        .save(deps.as_mut().storage, &pool_config(ASSET, COLLATERAL, env.block.time.seconds()))
+        .unwrap();
+    let legacy_state = LegacyPoolState {
+        supply_index: Decimal::percent(102),
+        borrow_index: Decimal::percent(104),
+        last_accrued: env.block.time.seconds(),
+        total_scaled_supply: Uint128::new(10_000),
+        total_scaled_debt: Uint128::new(3_150),
+        total_supplied: Uint128::new(10_000),
+        total_borrowed: Uint128::new(3_000),
+    };
+    LEGACY_POOL_STATE.save(deps.as_mut().storage, &legacy_state).unwrap();
+
+    let position = |principal: u128, scaled_debt: u128, collateral: u128| LegacyBorrowerPosition {
+        principal: Uint128::new(principal),
+        scaled_debt: Uint128::new(scaled_debt),
+        collateral: Uint128::new(collateral),
+    };
+    LEGACY_BORROWERS
+        .save(deps.as_mut().storage, &Addr::unchecked(KEEPER), &position(2_000, 2_100, 4_000))
+        .unwrap();
+    LEGACY_BORROWERS
+        .save(deps.as_mut().storage, &Addr::unchecked(BORROWER), &position(1_000, 1_050, 2_000))
+        .unwrap();
+    // fully repaid, nothing left to carry over
+    LEGACY_BORROWERS
+        .save(deps.as_mut().storage, &Addr::unchecked(LENDER), &position(0, 0, 0))
+        .unwrap();
+
+    let res = migrate(deps.as_mut(), env.clone(), migrate_msg()).unwrap();
+    assert!(res.attributes.iter().any(|a| a.key == "migrations" && a.value == "0.5.0"));
+
+    // ids follow the address order
+    assert_eq!(
+        loans().load(&deps.storage, 1).unwrap(),
+        Loan {
+            id: 1,
+            borrower: Addr::unchecked(BORROWER),
+            principal: Uint128::new(1_000),
+            scaled_debt: Uint128::new(1_050),
+            collateral: Uint128::new(2_000),
+            opened_at: env.block.time,
+            rate: DEBT_RATE,
+        }
+    );
+    assert_eq!(loans().load(&deps.storage, 2).unwrap().borrower, Addr::unchecked(KEEPER));
+    assert!(loans().may_load(&deps.storage, 3).unwrap().is_none());
+    assert_eq!(LOAN_COUNT.load(&deps.storage).unwrap(), 2);
+    assert!(LEGACY_BORROWERS.is_empty(&deps.storage));
+
+    // the single borrow index carries on as the index of the debt rate
+    let pool_state = POOL_STATE.load(&deps.storage).unwrap();
+    assert_eq!(pool_state.supply_index, Decimal::percent(102));
+    assert_eq!(
+        pool_state.borrow_indices,
+        vec![BorrowIndex { rate: DEBT_RATE, index: Decimal::percent(104), total_scaled_debt: Uint128::new(3_150) }]
+    );
+    assert_eq!(pool_state.total_borrowed.u128(), 3_000);
+}
+
+#[test]
+fn migrate_current_version_is_noop() {
+    let mut deps = mock_dependencies();
+    set_contract_version(deps.as_mut().storage, NAME, env!("CARGO_PKG_VERSION")).unwrap();
+
+    let msg = MigrateMsg { oracle: None, liquidation: None };
+    let res = migrate(deps.as_mut(), mock_env(), msg).unwrap();
+    assert!(res.attributes.iter().any(|a| a.key == "migrations" && a.value.is_empty()));
+}
+
+#[test]
+fn migrate_refuses_downgrade() {
+    let mut deps = mock_dependencies();
+    set_contract_version(deps.as_mut().storage, NAME, "99.0.0").unwrap();
+
+    let err = migrate(deps.as_mut(), mock_env(), migrate_msg()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidMigration { contract: NAME.to_string(), version: "99.0.0".to_string() }
+    );
+}
+
+#[test]
+fn migrate_refuses_other_contract() {
+    let mut deps = mock_dependencies();
+    set_contract_version(deps.as_mut().storage, "crates.io:cw20-base", "0.1.0").unwrap();
+
+    let err = migrate(deps.as_mut(), mock_env(), migrate_msg()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidMigration { contract: "crates.io:cw20-base".to_string(), version: "0.1.0".to_string() }
+    );
+}
@@ -0,0 +1,45 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    to_json_binary, Binary, Decimal, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdResult,
+};
+use cw_multi_test::{Contract, ContractWrapper};
+use cw_storage_plus::Item;
+
+use crate::external::{OracleQueryMsg, PriceResponse};
+
+const PRICE: Item<Decimal> = Item::new("price");
+
+/// Quotes the same price for any pair until told otherwise
+#[cw_serde]
+pub struct InstantiateMsg {
+    pub price: Decimal,
+}
+
+#[cw_serde]
+pub enum ExecuteMsg {
+    SetPrice { price: Decimal },
+}
+
+fn instantiate(deps: DepsMut, _env: Env, _info: MessageInfo, msg: InstantiateMsg) -> StdResult<Response> {
+    PRICE.save(deps.storage, &msg.price)?;
+    Ok(Response::default())
+}
+
+fn execute(deps: DepsMut, _env: Env, _info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+    match msg {
+        ExecuteMsg::SetPrice { price } => PRICE.save(deps.storage, &price)?,
+    }
+    Ok(Response::default())
+}
+
+fn query(deps: Deps, _env: Env, msg: OracleQueryMsg) -> StdResult<Binary> {
+    match msg {
+        OracleQueryMsg::Price { .. } => to_json_binary(&PriceResponse {
+            price: PRICE.load(deps.storage)?,
+        }),
+    }
+}
+
+pub fn contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(execute, instantiate, query))
+}
@@ -0,0 +1,50 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    coins, to_json_binary, Addr, Binary, CosmosMsg, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdResult,
+    WasmMsg,
+};
+use cw_multi_test::{Contract, ContractWrapper};
+use cw_storage_plus::Item;
+
+use crate::msg::{DepositMsg, ExecuteMsg, PoolDetailsResponse, QueryMsg, TransactMsg};
+
+const MIRROR: Item<Addr> = Item::new("mirror");
+const DENOM: Item<String> = Item::new("denom");
+
+/// Answers `AllDetails` with the details of `mirror` and "repays" any call
+/// by depositing `amount` of `denom` it holds into the calling pool
+#[cw_serde]
+pub struct InstantiateMsg {
+    pub mirror: String,
+    pub denom: String,
+}
+
+fn instantiate(deps: DepsMut, _env: Env, _info: MessageInfo, msg: InstantiateMsg) -> StdResult<Response> {
+    MIRROR.save(deps.storage, &deps.api.addr_validate(&msg.mirror)?)?;
+    DENOM.save(deps.storage, &msg.denom)?;
+    Ok(Response::default())
+}
+
+fn execute(deps: DepsMut, _env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+    let amount = match msg {
+        ExecuteMsg::Transact(TransactMsg::AcceptRollover { asset_amount, .. }) => asset_amount,
+        _ => return Ok(Response::default()),
+    };
+    let denom = DENOM.load(deps.storage)?;
+    let deposit = ExecuteMsg::Transact(TransactMsg::Deposit(DepositMsg { denom: denom.clone(), amount }));
+    Ok(Response::new().add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: info.sender.to_string(),
+        msg: to_json_binary(&deposit)?,
+        funds: coins(amount.u128(), denom),
+    })))
+}
+
+fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    let mirror = MIRROR.load(deps.storage)?;
+    let details: PoolDetailsResponse = deps.querier.query_wasm_smart(mirror, &msg)?;
+    to_json_binary(&details)
+}
+
+pub fn contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(execute, instantiate, query))
+}
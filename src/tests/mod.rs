@@ -0,0 +1,286 @@
+mod admin;
+mod amount;
+mod collateral;
+mod cw20_pool;
+mod debt_token;
+mod errors;
+mod fees;
+mod health;
+mod interest;
+mod invariants;
+mod liquidation;
+mod listing;
+mod loans;
+mod migrate;
+mod mock_oracle;
+mod mock_successor;
+mod native;
+mod nft;
+mod overflow;
+mod pause;
+mod queue;
+mod receipt;
+mod repay;
+mod rollover;
+mod settlement;
+
+use cosmwasm_std::{coin, to_json_binary, Addr, Decimal, Empty, Uint128};
+use cw20::{BalanceResponse, Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg, Expiration};
+use cw_multi_test::{App, AppBuilder, AppResponse, Contract, ContractWrapper, Executor};
+
+use crate::contract::{execute, instantiate, query, reply};
+use crate::error::ContractError;
+use crate::msg::{DepositMsg, ExecuteMsg, InstantiateMsg, InvariantsResponse, LoanMsg, QueryMsg, ReceiveMsg, RepayMsg, TransactMsg};
+use crate::types::{LiquidationConfig, PoolConfig};
+
+pub const ADMIN: &str = "admin";
+pub const LENDER: &str = "lender";
+pub const LENDER2: &str = "lender2";
+pub const BORROWER: &str = "borrower";
+pub const KEEPER: &str = "keeper";
+
+pub const ASSET: &str = "uusdc";
+pub const COLLATERAL: &str = "ugold";
+
+pub const YEAR: u64 = 365 * 24 * 60 * 60;
+pub const INITIAL_BALANCE: u128 = 1_000_000;
+
+// 10% and 5% a year
+pub const DEBT_RATE: Decimal = Decimal::percent(10);
+pub const LEND_RATE: Decimal = Decimal::percent(5);
+
+pub fn pool_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(execute, instantiate, query).with_reply(reply))
+}
+
+/// A pool maturing a year after `now`, lending at 5%, borrowing at 10%
+/// with 2x collateral at a strike price of 1 and a 10% lock in
+pub fn pool_config(asset: &str, collateral: &str, now: u64) -> PoolConfig {
+    PoolConfig {
+        name: "Gold backed USDC".to_string(),
+        symbol: "gUSDC".to_string(),
+        maturationdate: now + YEAR,
+        debtinterestrate: DEBT_RATE,
+        strikeprice: Decimal::one(),
+        lendinterestrate: LEND_RATE,
+        overcollateralizationfactor: Decimal::percent(200),
+        asset: asset.to_string(),
+        collateral: collateral.to_string(),
+        lock_in_period: Uint128::new(10),
+    }
+}
+
+/// An app where the lender and borrower hold both native coins
+pub fn native_app() -> App {
+    AppBuilder::new().build(|router, _, storage| {
+        for user in [LENDER, LENDER2, BORROWER, KEEPER] {
+            let balance = vec![coin(INITIAL_BALANCE, ASSET), coin(INITIAL_BALANCE, COLLATERAL)];
+            router
+                .bank
+                .init_balance(storage, &Addr::unchecked(user), balance)
+                .unwrap();
+        }
+    })
+}
+
+pub fn instantiate_oracle(app: &mut App, price: Decimal) -> Addr {
+    let code_id = app.store_code(mock_oracle::contract());
+    let msg = mock_oracle::InstantiateMsg { price };
+    app.instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "oracle", None)
+        .unwrap()
+}
+
+pub fn set_price(app: &mut App, oracle: &Addr, price: Decimal) {
+    let msg = mock_oracle::ExecuteMsg::SetPrice { price };
+    app.execute_contract(Addr::unchecked(ADMIN), oracle.clone(), &msg, &[])
+        .unwrap();
+}
+
+/// Liquidatable below 125% collateral, 5% bonus, half the debt at a time
+pub fn liquidation_config() -> LiquidationConfig {
+    LiquidationConfig {
+        threshold: Decimal::percent(125),
+        bonus: Decimal::percent(5),
+        close_factor: Decimal::percent(50),
+    }
+}
+
+pub fn instantiate_pool_msg(config: PoolConfig, oracle: &Addr) -> InstantiateMsg {
+    InstantiateMsg {
+        config,
+        oracle: oracle.to_string(),
+        admin: ADMIN.to_string(),
+        liquidation: liquidation_config(),
+        fees: None,
+        receipt_token_code_id: None,
+    }
+}
+
+/// Instantiates the pool along with a mock oracle quoting the collateral at the strike price,
+/// returns the pool and the oracle
+pub fn instantiate_pool_with_oracle(app: &mut App, config: PoolConfig) -> (Addr, Addr) {
+    let oracle = instantiate_oracle(app, Decimal::one());
+    let code_id = app.store_code(pool_contract());
+    let msg = instantiate_pool_msg(config, &oracle);
+    let pool = app
+        .instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "pool", None)
+        .unwrap();
+    (pool, oracle)
+}
+
+pub fn instantiate_pool(app: &mut App, config: PoolConfig) -> Addr {
+    instantiate_pool_with_oracle(app, config).0
+}
+
+pub fn balance(app: &App, user: &str, denom: &str) -> u128 {
+    app.wrap().query_balance(user, denom).unwrap().amount.u128()
+}
+
+pub fn advance(app: &mut App, seconds: u64) {
+    app.update_block(|block| {
+        block.time = block.time.plus_seconds(seconds);
+        block.height += seconds / 5;
+    });
+}
+
+pub fn deposit(app: &mut App, pool: &Addr, lender: &str, amount: u128) {
+    let msg = ExecuteMsg::Transact(TransactMsg::Deposit(DepositMsg {
+        denom: ASSET.to_string(),
+        amount: Uint128::new(amount),
+    }));
+    app.execute_contract(Addr::unchecked(lender), pool.clone(), &msg, &[coin(amount, ASSET)])
+        .unwrap();
+}
+
+pub fn loan_msg(amount: u128) -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::Loan(LoanMsg {
+        asset_denom: ASSET.to_string(),
+        asset_amount: Uint128::new(amount),
+        collateral_denom: COLLATERAL.to_string(),
+    }))
+}
+
+pub fn repay_msg(loan_id: u64, principle: u128) -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::Repay(RepayMsg {
+        loan_id,
+        asset_denom: ASSET.to_string(),
+        asset_principle: Uint128::new(principle),
+        collateral_denom: COLLATERAL.to_string(),
+    }))
+}
+
+pub fn cw20_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(
+        cw20_base::contract::execute,
+        cw20_base::contract::instantiate,
+        cw20_base::contract::query,
+    ))
+}
+
+/// A cw20-base token of which every user holds the initial balance
+pub fn instantiate_token(app: &mut App, name: &str, symbol: &str) -> Addr {
+    let code_id = app.store_code(cw20_contract());
+    let msg = cw20_base::msg::InstantiateMsg {
+        name: name.to_string(),
+        symbol: symbol.to_string(),
+        decimals: 6,
+        initial_balances: [LENDER, LENDER2, BORROWER, KEEPER]
+            .iter()
+            .map(|user| Cw20Coin { address: user.to_string(), amount: Uint128::new(INITIAL_BALANCE) })
+            .collect(),
+        mint: None,
+        marketing: None,
+    };
+    app.instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], symbol, None)
+        .unwrap()
+}
+
+/// A pool lending a cw20 asset against a cw20 collateral
+pub struct Cw20Pool {
+    pub pool: Addr,
+    pub oracle: Addr,
+    pub asset: Addr,
+    pub collateral: Addr,
+}
+
+impl Cw20Pool {
+    pub fn asset_denom(&self) -> String {
+        format!("cw20:{}", self.asset)
+    }
+
+    pub fn collateral_denom(&self) -> String {
+        format!("cw20:{}", self.collateral)
+    }
+}
+
+/// Instantiates both tokens and a pool of them configured like `pool_config`
+pub fn instantiate_cw20_pool(app: &mut App) -> Cw20Pool {
+    let asset = instantiate_token(app, "USD Coin", "USDC");
+    let collateral = instantiate_token(app, "Gold", "GOLD");
+
+    let now = app.block_info().time.seconds();
+    let config = pool_config(&format!("cw20:{}", asset), &format!("cw20:{}", collateral), now);
+    let (pool, oracle) = instantiate_pool_with_oracle(app, config);
+
+    Cw20Pool { pool, oracle, asset, collateral }
+}
+
+pub fn token_balance(app: &App, token: &Addr, user: &str) -> u128 {
+    let res: BalanceResponse = app
+        .wrap()
+        .query_wasm_smart(token, &Cw20QueryMsg::Balance { address: user.to_string() })
+        .unwrap();
+    res.balance.u128()
+}
+
+/// Lets `spender` pull `amount` of `token` from `owner` until `expires`
+pub fn approve(app: &mut App, token: &Addr, owner: &str, spender: &Addr, amount: u128, expires: Option<Expiration>) {
+    let msg = Cw20ExecuteMsg::IncreaseAllowance {
+        spender: spender.to_string(),
+        amount: Uint128::new(amount),
+        expires,
+    };
+    app.execute_contract(Addr::unchecked(owner), token.clone(), &msg, &[])
+        .unwrap();
+}
+
+/// Sends `amount` of `token` to the pool with `msg` as the ReceiveMsg hook,
+/// failures are the pool's
+pub fn send(app: &mut App, token: &Addr, sender: &str, pool: &Addr, amount: u128, msg: &ReceiveMsg) -> Result<AppResponse, ContractError> {
+    let msg = Cw20ExecuteMsg::Send {
+        contract: pool.to_string(),
+        amount: Uint128::new(amount),
+        msg: to_json_binary(msg).unwrap(),
+    };
+    app.execute_contract(Addr::unchecked(sender), token.clone(), &msg, &[])
+        .map_err(|err| err.downcast().unwrap())
+}
+
+/// Sends a TransactMsg to the cw20 pool, failures are the pool's
+pub fn transact(app: &mut App, pool: &Cw20Pool, sender: &str, msg: TransactMsg) -> Result<(), ContractError> {
+    app.execute_contract(Addr::unchecked(sender), pool.pool.clone(), &ExecuteMsg::Transact(msg), &[])
+        .map(|_| ())
+        .map_err(|err| err.downcast().unwrap())
+}
+
+/// Approves and deposits `amount` of the asset for `lender`
+pub fn deposit_cw20(app: &mut App, pool: &Cw20Pool, lender: &str, amount: u128) {
+    approve(app, &pool.asset, lender, &pool.pool, amount, None);
+    let msg = TransactMsg::Deposit(DepositMsg { denom: pool.asset_denom(), amount: Uint128::new(amount) });
+    transact(app, pool, lender, msg).unwrap();
+}
+
+/// Approves the collateral for and takes a loan of `amount` at twice the collateral
+pub fn loan_cw20(app: &mut App, pool: &Cw20Pool, borrower: &str, amount: u128) {
+    approve(app, &pool.collateral, borrower, &pool.pool, 2 * amount, None);
+    let msg = TransactMsg::Loan(LoanMsg {
+        asset_denom: pool.asset_denom(),
+        asset_amount: Uint128::new(amount),
+        collateral_denom: pool.collateral_denom(),
+    });
+    transact(app, pool, borrower, msg).unwrap();
+}
+
+pub fn invariants(app: &App, pool: &Addr) -> InvariantsResponse {
+    app.wrap().query_wasm_smart(pool, &QueryMsg::Invariants {}).unwrap()
+}
@@ -0,0 +1,265 @@
+use cosmwasm_std::{coin, coins, Addr, Decimal, Uint128};
+use cw_multi_test::{App, Executor};
+use cw_utils::PaymentError;
+
+use super::*;
+use crate::error::ContractError;
+use crate::types::PoolConfig;
+use crate::msg::{
+    BorrowerPositionResponse, DepositMsg, ExecuteMsg, LenderPositionResponse, QueryMsg, RepayQuoteResponse, TransactMsg,
+    UserDetailsResponse, WithdrawMsg,
+};
+
+fn setup() -> (App, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+    (app, pool)
+}
+
+#[test]
+fn deposit_and_withdraw_native_asset() {
+    let (mut app, pool) = setup();
+
+    deposit(&mut app, &pool, LENDER, 10_000);
+    assert_eq!(balance(&app, pool.as_str(), ASSET), 10_000);
+    assert_eq!(balance(&app, LENDER, ASSET), INITIAL_BALANCE - 10_000);
+
+    // the lock in is 10% of the time left to maturity
+    advance(&mut app, YEAR / 10);
+
+    let msg = ExecuteMsg::Transact(TransactMsg::Withdraw(WithdrawMsg {
+        denom: ASSET.to_string(),
+        amount: Uint128::new(4_000),
+    }));
+    app.execute_contract(Addr::unchecked(LENDER), pool.clone(), &msg, &[])
+        .unwrap();
+
+    assert_eq!(balance(&app, pool.as_str(), ASSET), 6_000);
+    assert_eq!(balance(&app, LENDER, ASSET), INITIAL_BALANCE - 6_000);
+}
+
+#[test]
+fn deposit_refunds_coins_above_the_amount() {
+    let (mut app, pool) = setup();
+
+    let msg = ExecuteMsg::Transact(TransactMsg::Deposit(DepositMsg {
+        denom: ASSET.to_string(),
+        amount: Uint128::new(1_000),
+    }));
+    app.execute_contract(Addr::unchecked(LENDER), pool.clone(), &msg, &coins(1_500, ASSET))
+        .unwrap();
+
+    assert_eq!(balance(&app, pool.as_str(), ASSET), 1_000);
+    assert_eq!(balance(&app, LENDER, ASSET), INITIAL_BALANCE - 1_000);
+}
+
+#[test]
+fn deposit_requires_the_asset_coins() {
+    let (mut app, pool) = setup();
+
+    let msg = ExecuteMsg::Transact(TransactMsg::Deposit(DepositMsg {
+        denom: ASSET.to_string(),
+        amount: Uint128::new(1_000),
+    }));
+
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER), pool.clone(), &msg, &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Payment(PaymentError::NoFunds {}), err.downcast().unwrap());
+
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER), pool.clone(), &msg, &coins(1_000, COLLATERAL))
+        .unwrap_err();
+    assert_eq!(
+        ContractError::Payment(PaymentError::MissingDenom(ASSET.to_string())),
+        err.downcast().unwrap()
+    );
+
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER), pool, &msg, &coins(999, ASSET))
+        .unwrap_err();
+    assert_eq!(ContractError::InsufficientFunds {}, err.downcast().unwrap());
+}
+
+#[test]
+fn loan_and_repay_with_native_collateral() {
+    let (mut app, pool) = setup();
+    deposit(&mut app, &pool, LENDER, 10_000);
+
+    // 2x collateral at a strike price of 1, the extra collateral is sent back
+    app.execute_contract(
+        Addr::unchecked(BORROWER),
+        pool.clone(),
+        &loan_msg(1_000),
+        &coins(2_500, COLLATERAL),
+    )
+    .unwrap();
+
+    assert_eq!(balance(&app, BORROWER, ASSET), INITIAL_BALANCE + 1_000);
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE - 2_000);
+    assert_eq!(balance(&app, pool.as_str(), COLLATERAL), 2_000);
+
+    advance(&mut app, YEAR / 2);
+
+    let quote: RepayQuoteResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::GetRepayQuote { loan_id: 1 })
+        .unwrap();
+    assert_eq!(quote.principal.u128(), 1_000);
+    assert_eq!(quote.interest.u128(), 50);
+    assert_eq!(quote.collateral.u128(), 2_000);
+
+    // paying more than is due gets the difference refunded
+    app.execute_contract(
+        Addr::unchecked(BORROWER),
+        pool.clone(),
+        &repay_msg(1, 1_000),
+        &coins(1_100, ASSET),
+    )
+    .unwrap();
+
+    assert_eq!(balance(&app, BORROWER, ASSET), INITIAL_BALANCE - 50);
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE);
+    assert_eq!(balance(&app, pool.as_str(), ASSET), 10_050);
+    assert_eq!(balance(&app, pool.as_str(), COLLATERAL), 0);
+}
+
+#[test]
+fn user_details_name_lender_and_borrower_positions() {
+    let (mut app, pool) = setup();
+    deposit(&mut app, &pool, BORROWER, 10_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+
+    // both positions are quoted at maturity, a year away
+    let details: UserDetailsResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::UserDetails { user: Addr::unchecked(BORROWER) })
+        .unwrap();
+    assert_eq!(
+        details,
+        UserDetailsResponse {
+            lender: LenderPositionResponse { principal: Uint128::new(10_000), interest: Uint128::new(500) },
+            borrower: BorrowerPositionResponse {
+                principal: Uint128::new(1_000),
+                interest: Uint128::new(100),
+                collateral: Uint128::new(2_000),
+            },
+        }
+    );
+}
+
+#[test]
+fn loan_rejects_wrong_or_missing_collateral() {
+    let (mut app, pool) = setup();
+    deposit(&mut app, &pool, LENDER, 10_000);
+
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(1_999, COLLATERAL))
+        .unwrap_err();
+    assert_eq!(ContractError::InsufficientFunds {}, err.downcast().unwrap());
+
+    let err = app
+        .execute_contract(
+            Addr::unchecked(BORROWER),
+            pool,
+            &loan_msg(1_000),
+            &[coin(2_000, COLLATERAL), coin(1, ASSET)],
+        )
+        .unwrap_err();
+    assert_eq!(ContractError::Payment(PaymentError::MultipleDenoms {}), err.downcast().unwrap());
+}
+
+#[test]
+fn lender_withdraws_interest_paid_by_borrower() {
+    let (mut app, pool) = setup();
+    deposit(&mut app, &pool, LENDER, 1_000);
+
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+    advance(&mut app, YEAR / 2);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 1_000), &coins(1_050, ASSET))
+        .unwrap();
+
+    // the lender's interest is booked when the position is touched
+    let msg = ExecuteMsg::Transact(TransactMsg::Withdraw(WithdrawMsg {
+        denom: ASSET.to_string(),
+        amount: Uint128::new(1_000),
+    }));
+    app.execute_contract(Addr::unchecked(LENDER), pool.clone(), &msg, &[])
+        .unwrap();
+    app.execute_contract(
+        Addr::unchecked(LENDER),
+        pool.clone(),
+        &ExecuteMsg::Transact(TransactMsg::WithdrawInterest {}),
+        &[],
+    )
+    .unwrap();
+
+    assert_eq!(balance(&app, LENDER, ASSET), INITIAL_BALANCE + 25);
+    assert_eq!(balance(&app, pool.as_str(), ASSET), 25);
+}
+
+#[test]
+fn instantiate_rejects_same_asset_and_collateral() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let oracle = instantiate_oracle(&mut app, Decimal::one());
+    let code_id = app.store_code(pool_contract());
+    let msg = instantiate_pool_msg(pool_config(ASSET, ASSET, now), &oracle);
+
+    let err = app
+        .instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "pool", None)
+        .unwrap_err();
+    assert_eq!(ContractError::InvalidCollateral {}, err.downcast().unwrap());
+}
+
+#[test]
+fn instantiate_validates_rates_and_factors() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let oracle = instantiate_oracle(&mut app, Decimal::one());
+    let code_id = app.store_code(pool_contract());
+
+    let invalid_configs = [
+        PoolConfig { debtinterestrate: Decimal::percent(101), ..pool_config(ASSET, COLLATERAL, now) },
+        PoolConfig { lendinterestrate: Decimal::percent(11), ..pool_config(ASSET, COLLATERAL, now) },
+        PoolConfig { strikeprice: Decimal::zero(), ..pool_config(ASSET, COLLATERAL, now) },
+        PoolConfig { lock_in_period: Uint128::new(101), ..pool_config(ASSET, COLLATERAL, now) },
+        PoolConfig { maturationdate: now, ..pool_config(ASSET, COLLATERAL, now) },
+    ];
+    for config in invalid_configs {
+        let msg = instantiate_pool_msg(config, &oracle);
+        let err = app
+            .instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "pool", None)
+            .unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InvalidConfig { .. }));
+    }
+
+    let config = PoolConfig { overcollateralizationfactor: Decimal::percent(99), ..pool_config(ASSET, COLLATERAL, now) };
+    let msg = instantiate_pool_msg(config, &oracle);
+    let err = app
+        .instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "pool", None)
+        .unwrap_err();
+    assert_eq!(ContractError::InsufficientOCF {}, err.downcast().unwrap());
+}
+
+#[test]
+fn loan_with_fractional_overcollateralization() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let config = PoolConfig {
+        overcollateralizationfactor: Decimal::percent(150),
+        strikeprice: Decimal::percent(80),
+        ..pool_config(ASSET, COLLATERAL, now)
+    };
+    let pool = instantiate_pool(&mut app, config);
+    deposit(&mut app, &pool, LENDER, 10_000);
+
+    // 1_000 at 150% is worth 1_500 of asset, that is 1_875 collateral at 0.8 each
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+    assert_eq!(balance(&app, pool.as_str(), COLLATERAL), 1_875);
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE - 1_875);
+}
@@ -0,0 +1,174 @@
+use cosmwasm_std::{
+    coins, to_json_binary, Addr, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdResult, Uint128, WasmMsg,
+};
+use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{
+    Cw721ReceiverExecuteMsg, ExecuteMsg, NftContractInfoResponse, NftInfoResponse, NumTokensResponse, OwnerOfResponse,
+    PendingLoanTransferResponse, QueryMsg, TokensResponse, TransactMsg,
+};
+
+fn transfer_msg(recipient: &str, token_id: &str) -> ExecuteMsg {
+    ExecuteMsg::TransferNft { recipient: recipient.to_string(), token_id: token_id.to_string() }
+}
+
+fn accept_msg(loan_id: u64) -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::AcceptLoan { loan_id })
+}
+
+fn owner_of(app: &App, pool: &Addr, token_id: &str) -> String {
+    let msg = QueryMsg::OwnerOf { token_id: token_id.to_string(), include_expired: None };
+    let res: OwnerOfResponse = app.wrap().query_wasm_smart(pool, &msg).unwrap();
+    res.owner
+}
+
+/// Accepts any loan it is sent and tags the response with its id
+fn receiver_contract() -> Box<dyn Contract<Empty>> {
+    fn execute(_deps: DepsMut, _env: Env, info: MessageInfo, msg: Cw721ReceiverExecuteMsg) -> StdResult<Response> {
+        let Cw721ReceiverExecuteMsg::ReceiveNft(msg) = msg;
+        let loan_id = msg.token_id.parse().unwrap();
+        Ok(Response::new()
+            .add_attribute("received", msg.token_id)
+            .add_message(WasmMsg::Execute {
+                contract_addr: info.sender.to_string(),
+                msg: to_json_binary(&accept_msg(loan_id))?,
+                funds: vec![],
+            }))
+    }
+    fn instantiate(_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty) -> StdResult<Response> {
+        Ok(Response::default())
+    }
+    fn query(_deps: Deps, _env: Env, _msg: Empty) -> StdResult<Binary> {
+        Ok(Binary::default())
+    }
+    Box::new(ContractWrapper::new(execute, instantiate, query))
+}
+
+// The borrower takes 1_000 against 2_000 collateral and the keeper 2_000 against 4_000
+fn borrowed_pool() -> (App, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+    deposit(&mut app, &pool, LENDER, 10_000);
+    for (borrower, amount) in [(BORROWER, 1_000), (KEEPER, 2_000)] {
+        app.execute_contract(Addr::unchecked(borrower), pool.clone(), &loan_msg(amount), &coins(2 * amount, COLLATERAL))
+            .unwrap();
+    }
+    (app, pool)
+}
+
+#[test]
+fn the_holder_repays_and_gets_the_collateral() {
+    let (mut app, pool) = borrowed_pool();
+
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &transfer_msg(LENDER2, "1"), &[])
+        .unwrap();
+    app.execute_contract(Addr::unchecked(LENDER2), pool.clone(), &accept_msg(1), &[])
+        .unwrap();
+    assert_eq!(owner_of(&app, &pool, "1"), LENDER2);
+
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 1_000), &coins(1_000, ASSET))
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+    app.execute_contract(Addr::unchecked(LENDER2), pool.clone(), &repay_msg(1, 1_000), &coins(1_000, ASSET))
+        .unwrap();
+    assert_eq!(balance(&app, LENDER2, COLLATERAL), INITIAL_BALANCE + 2_000);
+    assert_eq!(balance(&app, BORROWER, ASSET), INITIAL_BALANCE + 1_000);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn only_the_holder_transfers_a_loan() {
+    let (mut app, pool) = borrowed_pool();
+
+    let err = app
+        .execute_contract(Addr::unchecked(KEEPER), pool.clone(), &transfer_msg(KEEPER, "1"), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+    for token_id in ["9", "one"] {
+        let err = app
+            .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &transfer_msg(KEEPER, token_id), &[])
+            .unwrap_err();
+        assert_eq!(ContractError::PositionNotAvailable {}, err.downcast().unwrap());
+    }
+    assert_eq!(owner_of(&app, &pool, "1"), BORROWER);
+}
+
+#[test]
+fn nobody_gets_a_loan_without_accepting_it() {
+    let (mut app, pool) = borrowed_pool();
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &transfer_msg(LENDER2, "1"), &[])
+        .unwrap();
+
+    // the loan and its debt stay with the holder until the recipient takes them
+    let pending = |app: &App| -> PendingLoanTransferResponse {
+        app.wrap().query_wasm_smart(&pool, &QueryMsg::PendingLoanTransfer { loan_id: 1 }).unwrap()
+    };
+    assert_eq!(pending(&app).recipient, Some(Addr::unchecked(LENDER2)));
+    assert_eq!(owner_of(&app, &pool, "1"), BORROWER);
+    assert_eq!(token_balance(&app, &pool, LENDER2), 0);
+
+    let err = app
+        .execute_contract(Addr::unchecked(KEEPER), pool.clone(), &accept_msg(1), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+    app.execute_contract(Addr::unchecked(LENDER2), pool.clone(), &accept_msg(1), &[])
+        .unwrap();
+    assert_eq!(owner_of(&app, &pool, "1"), LENDER2);
+    assert_eq!(token_balance(&app, &pool, LENDER2), 1_000);
+    assert_eq!(token_balance(&app, &pool, BORROWER), 0);
+    assert_eq!(pending(&app).recipient, None);
+
+    // the offer is gone once taken
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER2), pool.clone(), &accept_msg(1), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+}
+
+#[test]
+fn loans_answer_the_cw721_queries() {
+    let (app, pool) = borrowed_pool();
+
+    let info: NftContractInfoResponse = app.wrap().query_wasm_smart(&pool, &QueryMsg::ContractInfo {}).unwrap();
+    assert_eq!(info, NftContractInfoResponse { name: "Gold backed USDC loans".to_string(), symbol: "lgUSDC".to_string() });
+
+    let count: NumTokensResponse = app.wrap().query_wasm_smart(&pool, &QueryMsg::NumTokens {}).unwrap();
+    assert_eq!(count.count, 2);
+
+    let msg = QueryMsg::Tokens { owner: KEEPER.to_string(), start_after: None, limit: None };
+    let tokens: TokensResponse = app.wrap().query_wasm_smart(&pool, &msg).unwrap();
+    assert_eq!(tokens.tokens, vec!["2"]);
+
+    let msg = QueryMsg::AllTokens { start_after: Some("1".to_string()), limit: None };
+    let tokens: TokensResponse = app.wrap().query_wasm_smart(&pool, &msg).unwrap();
+    assert_eq!(tokens.tokens, vec!["2"]);
+
+    let msg = QueryMsg::NftInfo { token_id: "2".to_string() };
+    let nft: NftInfoResponse = app.wrap().query_wasm_smart(&pool, &msg).unwrap();
+    assert_eq!(nft.token_uri, None);
+    assert_eq!(nft.extension.principal, Uint128::new(2_000));
+    assert_eq!(nft.extension.collateral, Uint128::new(4_000));
+    assert_eq!(nft.extension.rate, DEBT_RATE);
+}
+
+#[test]
+fn send_nft_calls_the_receiving_contract() {
+    let (mut app, pool) = borrowed_pool();
+    let code_id = app.store_code(receiver_contract());
+    let desk = app
+        .instantiate_contract(code_id, Addr::unchecked(ADMIN), &Empty {}, &[], "desk", None)
+        .unwrap();
+
+    let msg = ExecuteMsg::SendNft { contract: desk.to_string(), token_id: "1".to_string(), msg: Binary::default() };
+    let res = app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &msg, &[])
+        .unwrap();
+    assert!(res.events.iter().any(|event| event.attributes.iter().any(|a| a.key == "received" && a.value == "1")));
+    assert_eq!(owner_of(&app, &pool, "1"), desk.as_str());
+}
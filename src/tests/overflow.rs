@@ -0,0 +1,198 @@
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{
+    to_json_binary, Addr, ContractResult as QuerierResult, Decimal, Env, OwnedDeps, SystemResult, Uint128,
+};
+use cw20::Cw20ReceiveMsg;
+
+use super::*;
+use crate::contract::{execute, instantiate, query};
+use crate::error::ContractError;
+use crate::external::PriceResponse;
+use crate::msg::{ExecuteMsg, QueryMsg, ReceiveMsg, TransactMsg, WithdrawMsg};
+use crate::state::{loans, LENDERS, POOL_STATE};
+use crate::types::{LenderPosition, Loan};
+
+// Every handler is pushed past the bounds of Uint128 and Decimal,
+// each one has to fail with Overflow instead of panicking
+
+const ASSET_TOKEN: &str = "asset";
+const COLLATERAL_TOKEN: &str = "collateral";
+
+type Deps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+/// A pool of cw20 tokens driven through the entry points directly,
+/// so the state can be set up at the bounds without moving that many tokens
+fn setup() -> (Deps, Env) {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    set_oracle_price(&mut deps, Decimal::one());
+
+    let config = pool_config(
+        &format!("cw20:{}", ASSET_TOKEN),
+        &format!("cw20:{}", COLLATERAL_TOKEN),
+        env.block.time.seconds(),
+    );
+    let msg = instantiate_pool_msg(config, &Addr::unchecked("oracle"));
+    instantiate(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), msg).unwrap();
+    (deps, env)
+}
+
+fn set_oracle_price(deps: &mut Deps, price: Decimal) {
+    deps.querier.update_wasm(move |_| {
+        SystemResult::Ok(QuerierResult::Ok(to_json_binary(&PriceResponse { price }).unwrap()))
+    });
+}
+
+fn receive(deps: &mut Deps, env: &Env, token: &str, sender: &str, amount: u128, msg: ReceiveMsg) -> Result<(), ContractError> {
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: sender.to_string(),
+        amount: Uint128::new(amount),
+        msg: to_json_binary(&msg).unwrap(),
+    });
+    execute(deps.as_mut(), env.clone(), mock_info(token, &[]), msg).map(|_| ())
+}
+
+fn transact(deps: &mut Deps, env: &Env, sender: &str, msg: TransactMsg) -> Result<(), ContractError> {
+    execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), ExecuteMsg::Transact(msg)).map(|_| ())
+}
+
+fn later(env: &Env, seconds: u64) -> Env {
+    let mut env = env.clone();
+    env.block.time = env.block.time.plus_seconds(seconds);
+    env
+}
+
+/// Indices this far into the Decimal range turn any real balance into an overflow
+fn inflate_indices(deps: &mut Deps) {
+    let mut pool_state = POOL_STATE.load(&deps.storage).unwrap();
+    pool_state.supply_index = Decimal::MAX;
+    for borrow_index in pool_state.borrow_indices.iter_mut() {
+        borrow_index.index = Decimal::MAX;
+    }
+    POOL_STATE.save(&mut deps.storage, &pool_state).unwrap();
+}
+
+#[test]
+fn deposit_past_the_pool_totals() {
+    let (mut deps, env) = setup();
+
+    receive(&mut deps, &env, ASSET_TOKEN, LENDER, u128::MAX, ReceiveMsg::Deposit {}).unwrap();
+
+    let err = receive(&mut deps, &env, ASSET_TOKEN, LENDER2, 1, ReceiveMsg::Deposit {}).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+
+    let msg = QueryMsg::GetDepositQuote { user: Addr::unchecked(LENDER), amount: Uint128::one() };
+    let err = query(deps.as_ref(), env, msg).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+}
+
+#[test]
+fn accrual_past_the_indices() {
+    let (mut deps, env) = setup();
+    receive(&mut deps, &env, ASSET_TOKEN, LENDER, 1_000, ReceiveMsg::Deposit {}).unwrap();
+    inflate_indices(&mut deps);
+
+    let env = later(&env, YEAR / 2);
+    let err = receive(&mut deps, &env, ASSET_TOKEN, LENDER2, 1_000, ReceiveMsg::Deposit {}).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+
+    let err = query(deps.as_ref(), env.clone(), QueryMsg::PoolState {}).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+
+    // the positions are reported at maturity
+    let err = query(deps.as_ref(), env, QueryMsg::UserDetails { user: Addr::unchecked(LENDER) }).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+}
+
+#[test]
+fn withdraw_at_the_bounds() {
+    let (mut deps, env) = setup();
+    receive(&mut deps, &env, ASSET_TOKEN, LENDER, 1_000, ReceiveMsg::Deposit {}).unwrap();
+    let env = later(&env, YEAR / 10);
+
+    // asking for more than the principal is refused before any arithmetic
+    let msg = TransactMsg::Withdraw(WithdrawMsg {
+        denom: format!("cw20:{}", ASSET_TOKEN),
+        amount: Uint128::MAX,
+    });
+    let err = transact(&mut deps, &env, LENDER, msg).unwrap_err();
+    assert_eq!(ContractError::InsufficientFunds {}, err);
+
+    // a balance beyond Uint128 can't be paid out
+    LENDERS
+        .save(
+            &mut deps.storage,
+            &Addr::unchecked(LENDER),
+            &LenderPosition { principal: Uint128::new(1_000), scaled: Uint128::MAX, deposited_at: env.block.time },
+        )
+        .unwrap();
+    let err = transact(&mut deps, &env, LENDER, TransactMsg::WithdrawInterest {}).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+}
+
+#[test]
+fn loan_past_the_collateral_bounds() {
+    let (mut deps, env) = setup();
+    receive(&mut deps, &env, ASSET_TOKEN, LENDER, 1_000, ReceiveMsg::Deposit {}).unwrap();
+
+    // twice the loan is needed as collateral
+    let msg = ReceiveMsg::PostCollateral { asset_amount: Uint128::MAX };
+    let err = receive(&mut deps, &env, COLLATERAL_TOKEN, BORROWER, u128::MAX, msg).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+
+    let err = query(deps.as_ref(), env, QueryMsg::GetLoanQuote { amount: Uint128::MAX }).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+}
+
+#[test]
+fn repay_and_settle_past_the_debt_bounds() {
+    let (mut deps, env) = setup();
+    receive(&mut deps, &env, ASSET_TOKEN, LENDER, 1_000, ReceiveMsg::Deposit {}).unwrap();
+    let msg = ReceiveMsg::PostCollateral { asset_amount: Uint128::new(500) };
+    receive(&mut deps, &env, COLLATERAL_TOKEN, BORROWER, 1_000, msg).unwrap();
+
+    // a debt beyond Uint128 once the borrow index is applied
+    loans()
+        .update(&mut deps.storage, 1, |loan| -> Result<_, ContractError> {
+            Ok(Loan { scaled_debt: Uint128::MAX, ..loan.unwrap() })
+        })
+        .unwrap();
+    let mut pool_state = POOL_STATE.load(&deps.storage).unwrap();
+    let borrow_index = pool_state.borrow_index_mut(DEBT_RATE);
+    borrow_index.index = Decimal::percent(200);
+    borrow_index.total_scaled_debt = Uint128::MAX;
+    POOL_STATE.save(&mut deps.storage, &pool_state).unwrap();
+
+    let err = query(deps.as_ref(), env.clone(), QueryMsg::GetRepayQuote { loan_id: 1 }).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+
+    let msg = ReceiveMsg::Repay { loan_id: 1, asset_principle: Uint128::new(500) };
+    let err = receive(&mut deps, &env, ASSET_TOKEN, BORROWER, 1_000, msg).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+
+    let err = transact(&mut deps, &later(&env, YEAR + 1), KEEPER, TransactMsg::Settle { limit: None }).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+}
+
+#[test]
+fn health_and_liquidation_past_the_price_bounds() {
+    let (mut deps, env) = setup();
+    receive(&mut deps, &env, ASSET_TOKEN, LENDER, 1_000, ReceiveMsg::Deposit {}).unwrap();
+    let msg = ReceiveMsg::PostCollateral { asset_amount: Uint128::new(500) };
+    receive(&mut deps, &env, COLLATERAL_TOKEN, BORROWER, 1_000, msg).unwrap();
+
+    // the collateral is worth more than Uint128 holds
+    loans()
+        .update(&mut deps.storage, 1, |loan| -> Result<_, ContractError> {
+            Ok(Loan { collateral: Uint128::new(u128::MAX / 2), ..loan.unwrap() })
+        })
+        .unwrap();
+    set_oracle_price(&mut deps, Decimal::percent(300));
+
+    let err = query(deps.as_ref(), env.clone(), QueryMsg::HealthFactor { loan_id: 1 }).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+
+    let msg = ReceiveMsg::Liquidate { loan_id: 1 };
+    let err = receive(&mut deps, &env, ASSET_TOKEN, KEEPER, 100, msg).unwrap_err();
+    assert_eq!(ContractError::Overflow {}, err);
+}
@@ -0,0 +1,102 @@
+use cosmwasm_std::{coins, Addr, Uint128};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{AdminMsg, ExecuteMsg, PauseStateResponse, QueryMsg, TransactMsg};
+use crate::types::Operation;
+
+const GUARDIAN: &str = "guardian";
+
+fn setup() -> (App, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+
+    let msg = ExecuteMsg::Admin(AdminMsg::SetGuardian { guardian: Some(GUARDIAN.to_string()) });
+    app.execute_contract(Addr::unchecked(ADMIN), pool.clone(), &msg, &[])
+        .unwrap();
+    (app, pool)
+}
+
+fn update_pause(app: &mut App, pool: &Addr, sender: &str, operations: Vec<Operation>, paused: bool) -> Result<(), ContractError> {
+    let msg = ExecuteMsg::Admin(AdminMsg::UpdatePause { operations, paused });
+    app.execute_contract(Addr::unchecked(sender), pool.clone(), &msg, &[])
+        .map(|_| ())
+        .map_err(|err| err.downcast().unwrap())
+}
+
+#[test]
+fn guardian_pauses_operations_independently() {
+    let (mut app, pool) = setup();
+    deposit(&mut app, &pool, LENDER, 10_000);
+
+    update_pause(&mut app, &pool, GUARDIAN, vec![Operation::Loan], true).unwrap();
+
+    let res: PauseStateResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::PauseState {})
+        .unwrap();
+    assert!(res.pause_state.loan);
+    assert!(!res.pause_state.deposit);
+    assert_eq!(res.guardian, Some(Addr::unchecked(GUARDIAN)));
+
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap_err();
+    assert_eq!(
+        ContractError::Paused { operation: "loan".to_string() },
+        err.downcast().unwrap()
+    );
+
+    // everything else keeps working
+    deposit(&mut app, &pool, LENDER2, 1_000);
+
+    update_pause(&mut app, &pool, GUARDIAN, vec![Operation::Loan], false).unwrap();
+    app.execute_contract(Addr::unchecked(BORROWER), pool, &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+}
+
+#[test]
+fn borrowers_can_repay_through_any_pause() {
+    let (mut app, pool) = setup();
+    deposit(&mut app, &pool, LENDER, 10_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+
+    let err = update_pause(&mut app, &pool, LENDER, vec![Operation::Deposit], true).unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err);
+
+    let everything = vec![
+        Operation::Deposit,
+        Operation::Withdraw,
+        Operation::Loan,
+        Operation::WithdrawInterest,
+        Operation::Liquidate,
+    ];
+    update_pause(&mut app, &pool, ADMIN, everything, true).unwrap();
+
+    let top_up = ExecuteMsg::Transact(TransactMsg::TopUpCollateral { loan_id: 1, amount: Uint128::new(100) });
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &top_up, &coins(100, COLLATERAL))
+        .unwrap();
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 1_000), &coins(1_000, ASSET))
+        .unwrap();
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE);
+}
+
+#[test]
+fn removed_guardian_cannot_pause() {
+    let (mut app, pool) = setup();
+
+    let msg = ExecuteMsg::Admin(AdminMsg::SetGuardian { guardian: None });
+    let err = app
+        .execute_contract(Addr::unchecked(GUARDIAN), pool.clone(), &msg, &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+    app.execute_contract(Addr::unchecked(ADMIN), pool.clone(), &msg, &[])
+        .unwrap();
+
+    let err = update_pause(&mut app, &pool, GUARDIAN, vec![Operation::Deposit], true).unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err);
+}
@@ -0,0 +1,162 @@
+use cosmwasm_std::{coins, Addr, Uint128};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{AdminMsg, ExecuteMsg, QueryMsg, TransactMsg, WithdrawMsg, WithdrawalQueueResponse};
+use crate::types::Operation;
+
+fn withdraw_msg(amount: u128) -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::Withdraw(WithdrawMsg {
+        denom: ASSET.to_string(),
+        amount: Uint128::new(amount),
+    }))
+}
+
+fn process_queue_msg() -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::ProcessQueue { limit: None })
+}
+
+fn queue(app: &App, pool: &Addr) -> WithdrawalQueueResponse {
+    app.wrap()
+        .query_wasm_smart(pool, &QueryMsg::WithdrawalQueue { limit: None })
+        .unwrap()
+}
+
+// Two lenders put in 1_000 each and the borrower takes 1_800 of it,
+// the lenders then ask for 1_000 and 500 back past the lock in
+fn queued_pool() -> (App, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+
+    deposit(&mut app, &pool, LENDER, 1_000);
+    deposit(&mut app, &pool, LENDER2, 1_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_800), &coins(3_600, COLLATERAL))
+        .unwrap();
+    advance(&mut app, YEAR / 10);
+
+    app.execute_contract(Addr::unchecked(LENDER), pool.clone(), &withdraw_msg(1_000), &[])
+        .unwrap();
+    app.execute_contract(Addr::unchecked(LENDER2), pool.clone(), &withdraw_msg(500), &[])
+        .unwrap();
+    (app, pool)
+}
+
+#[test]
+fn withdrawal_beyond_the_liquidity_waits_in_the_queue() {
+    let (app, pool) = queued_pool();
+
+    // the 200 left is paid to the first lender, the rest of both withdrawals waits
+    assert_eq!(balance(&app, LENDER, ASSET), INITIAL_BALANCE - 800);
+    assert_eq!(balance(&app, LENDER2, ASSET), INITIAL_BALANCE - 1_000);
+    assert_eq!(balance(&app, pool.as_str(), ASSET), 0);
+
+    let pending = queue(&app, &pool);
+    assert_eq!(pending.total_queued.u128(), 1_300);
+    assert_eq!(pending.length, 2);
+    let queued: Vec<_> = pending.withdrawals.iter().map(|w| (w.lender.as_str(), w.amount.u128())).collect();
+    assert_eq!(queued, vec![(LENDER, 800), (LENDER2, 500)]);
+
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn repayments_pay_the_queue_in_order() {
+    let (mut app, pool) = queued_pool();
+
+    // 1% interest on the 1_000 repaid, the first lender is paid in full and the second in part
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 1_000), &coins(1_100, ASSET))
+        .unwrap();
+    assert_eq!(balance(&app, LENDER, ASSET), INITIAL_BALANCE);
+    assert_eq!(balance(&app, LENDER2, ASSET), INITIAL_BALANCE - 1_000 + 210);
+
+    let pending = queue(&app, &pool);
+    assert_eq!(pending.total_queued.u128(), 290);
+    assert_eq!(pending.length, 1);
+    assert_eq!(pending.withdrawals[0].lender.as_str(), LENDER2);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+
+    // new liquidity goes to the queue before anything else
+    deposit(&mut app, &pool, KEEPER, 1_000);
+    assert_eq!(balance(&app, LENDER2, ASSET), INITIAL_BALANCE - 500);
+    assert_eq!(queue(&app, &pool).length, 0);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn anyone_processes_the_queue_once_withdrawals_resume() {
+    let (mut app, pool) = queued_pool();
+
+    let msg = ExecuteMsg::Admin(AdminMsg::UpdatePause { operations: vec![Operation::Withdraw], paused: true });
+    app.execute_contract(Addr::unchecked(ADMIN), pool.clone(), &msg, &[])
+        .unwrap();
+
+    // repayments go through the pause but leave the queue alone
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 1_000), &coins(1_100, ASSET))
+        .unwrap();
+    assert_eq!(queue(&app, &pool).total_queued.u128(), 1_300);
+    let err = app
+        .execute_contract(Addr::unchecked(KEEPER), pool.clone(), &process_queue_msg(), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Paused { operation: "withdraw".to_string() }, err.downcast().unwrap());
+
+    let msg = ExecuteMsg::Admin(AdminMsg::UpdatePause { operations: vec![Operation::Withdraw], paused: false });
+    app.execute_contract(Addr::unchecked(ADMIN), pool.clone(), &msg, &[])
+        .unwrap();
+    app.execute_contract(Addr::unchecked(KEEPER), pool.clone(), &process_queue_msg(), &[])
+        .unwrap();
+
+    assert_eq!(balance(&app, LENDER, ASSET), INITIAL_BALANCE);
+    assert_eq!(queue(&app, &pool).total_queued.u128(), 290);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn interest_waits_for_the_queue() {
+    let (mut app, pool) = queued_pool();
+    let msg = ExecuteMsg::Transact(TransactMsg::WithdrawInterest {});
+
+    // the second lender's 500 still earns interest, but the queue comes first
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER2), pool.clone(), &msg, &[])
+        .unwrap_err();
+    assert_eq!(ContractError::WithdrawalsQueued {}, err.downcast().unwrap());
+
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 1_800), &coins(1_900, ASSET))
+        .unwrap();
+    assert_eq!(queue(&app, &pool).length, 0);
+    app.execute_contract(Addr::unchecked(LENDER2), pool.clone(), &msg, &[])
+        .unwrap();
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn queued_liquidity_is_not_lent_out_again() {
+    let (mut app, pool) = queued_pool();
+
+    let err = app
+        .execute_contract(Addr::unchecked(KEEPER), pool.clone(), &loan_msg(10), &coins(20, COLLATERAL))
+        .unwrap_err();
+    assert_eq!(ContractError::InsufficientFunds {}, err.downcast().unwrap());
+}
+
+#[test]
+fn settlement_returns_the_queue_to_the_positions() {
+    let (mut app, pool) = queued_pool();
+    advance(&mut app, YEAR);
+
+    app.execute_contract(Addr::unchecked(KEEPER), pool.clone(), &ExecuteMsg::Transact(TransactMsg::Settle { limit: None }), &[])
+        .unwrap();
+    assert_eq!(queue(&app, &pool).total_queued.u128(), 0);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+
+    // nothing was repaid, the lenders share the forfeited collateral by what they are owed
+    for lender in [LENDER, LENDER2] {
+        app.execute_contract(Addr::unchecked(lender), pool.clone(), &ExecuteMsg::Transact(TransactMsg::Redeem {}), &[])
+            .unwrap();
+    }
+    let (lender, lender2) = (balance(&app, LENDER, COLLATERAL), balance(&app, LENDER2, COLLATERAL));
+    assert!(lender < lender2);
+    assert_eq!(lender + lender2, 2 * INITIAL_BALANCE + 3_600 - 1);
+}
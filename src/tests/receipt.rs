@@ -0,0 +1,161 @@
+use cosmwasm_std::{Addr, Decimal, Uint128};
+use cw20::{Cw20ExecuteMsg, TokenInfoResponse};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{
+    ExecuteMsg, InstantiateMsg, ListLendersResponse, PoolDetailsResponse, PoolStateResponse, QueryMsg, TransactMsg, WithdrawMsg,
+};
+
+// A native pool whose lenders get receipt tokens, returns the pool and the token
+fn receipt_pool() -> (App, Addr, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let oracle = instantiate_oracle(&mut app, Decimal::one());
+    let token_code_id = app.store_code(cw20_contract());
+    let code_id = app.store_code(pool_contract());
+    let msg = InstantiateMsg {
+        receipt_token_code_id: Some(token_code_id),
+        ..instantiate_pool_msg(pool_config(ASSET, COLLATERAL, now), &oracle)
+    };
+    let pool = app
+        .instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "pool", None)
+        .unwrap();
+
+    let details: PoolDetailsResponse = app.wrap().query_wasm_smart(&pool, &QueryMsg::AllDetails {}).unwrap();
+    let token = details.receipt_token.unwrap();
+    (app, pool, token)
+}
+
+fn withdraw(app: &mut App, pool: &Addr, lender: &str, amount: u128) -> Result<(), ContractError> {
+    let msg = ExecuteMsg::Transact(TransactMsg::Withdraw(WithdrawMsg {
+        denom: ASSET.to_string(),
+        amount: Uint128::new(amount),
+    }));
+    app.execute_contract(Addr::unchecked(lender), pool.clone(), &msg, &[])
+        .map(|_| ())
+        .map_err(|err| err.downcast().unwrap())
+}
+
+fn transfer(app: &mut App, token: &Addr, from: &str, to: &str, amount: u128) {
+    let msg = Cw20ExecuteMsg::Transfer { recipient: to.to_string(), amount: Uint128::new(amount) };
+    app.execute_contract(Addr::unchecked(from), token.clone(), &msg, &[])
+        .unwrap();
+}
+
+fn sync(app: &mut App, pool: &Addr, lender: &str) {
+    let msg = ExecuteMsg::Transact(TransactMsg::SyncLender { lender: lender.to_string() });
+    app.execute_contract(Addr::unchecked(KEEPER), pool.clone(), &msg, &[])
+        .unwrap();
+}
+
+#[test]
+fn deposits_mint_shares_of_the_supply() {
+    let (mut app, pool, token) = receipt_pool();
+
+    let info: TokenInfoResponse = app.wrap().query_wasm_smart(&token, &cw20::Cw20QueryMsg::TokenInfo {}).unwrap();
+    assert_eq!((info.name.as_str(), info.symbol.as_str()), ("Gold backed USDC", "gUSDC"));
+
+    deposit(&mut app, &pool, LENDER, 1_000);
+    assert_eq!(token_balance(&app, &token, LENDER), 1_000);
+
+    // later deposits get fewer shares for the interest accrued
+    advance(&mut app, YEAR / 2);
+    deposit(&mut app, &pool, LENDER2, 1_025);
+    assert_eq!(token_balance(&app, &token, LENDER2), 1_000);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn withdrawals_burn_the_shares_with_the_allowance() {
+    let (mut app, pool, token) = receipt_pool();
+    deposit(&mut app, &pool, LENDER, 1_000);
+    advance(&mut app, YEAR / 2);
+
+    // the pool can't burn the shares without the lender's allowance
+    assert!(app
+        .execute_contract(
+            Addr::unchecked(LENDER),
+            pool.clone(),
+            &ExecuteMsg::Transact(TransactMsg::Withdraw(WithdrawMsg { denom: ASSET.to_string(), amount: Uint128::new(500) })),
+            &[],
+        )
+        .is_err());
+
+    approve(&mut app, &token, LENDER, &pool, 1_000, None);
+    withdraw(&mut app, &pool, LENDER, 500).unwrap();
+    assert_eq!(token_balance(&app, &token, LENDER), 513);
+    assert_eq!(balance(&app, LENDER, ASSET), INITIAL_BALANCE - 500);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn interest_follows_the_token_holder() {
+    let (mut app, pool, token) = receipt_pool();
+    deposit(&mut app, &pool, LENDER, 1_000);
+    transfer(&mut app, &token, LENDER, KEEPER, 400);
+    sync(&mut app, &pool, KEEPER);
+    advance(&mut app, YEAR / 2);
+
+    // 400 shares are worth 410 half a year on, 400 of it is the principal that came with them
+    approve(&mut app, &token, KEEPER, &pool, 400, None);
+    assert_eq!(ContractError::InsufficientFunds {}, withdraw(&mut app, &pool, KEEPER, 401).unwrap_err());
+    withdraw(&mut app, &pool, KEEPER, 400).unwrap();
+    app.execute_contract(Addr::unchecked(KEEPER), pool.clone(), &ExecuteMsg::Transact(TransactMsg::WithdrawInterest {}), &[])
+        .unwrap();
+    assert_eq!(balance(&app, KEEPER, ASSET), INITIAL_BALANCE + 410);
+    assert_eq!(token_balance(&app, &token, KEEPER), 0);
+
+    // the lender is left with what the other 600 shares are worth
+    approve(&mut app, &token, LENDER, &pool, 600, None);
+    assert_eq!(ContractError::InsufficientFunds {}, withdraw(&mut app, &pool, LENDER, 616).unwrap_err());
+    withdraw(&mut app, &pool, LENDER, 500).unwrap();
+    assert_eq!(balance(&app, LENDER, ASSET), INITIAL_BALANCE - 500);
+    assert_eq!(token_balance(&app, &token, LENDER), 113);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn transferred_shares_stay_locked_in() {
+    let (mut app, pool, token) = receipt_pool();
+    deposit(&mut app, &pool, LENDER, 1_000);
+    transfer(&mut app, &token, LENDER, KEEPER, 400);
+    approve(&mut app, &token, KEEPER, &pool, 400, None);
+
+    // the keeper never deposited, the shares are locked in from when they are booked
+    assert_eq!(ContractError::LockinTimePeriodActive {}, withdraw(&mut app, &pool, KEEPER, 400).unwrap_err());
+    sync(&mut app, &pool, KEEPER);
+
+    // the principal went with the shares, the pool's total catches up once the lender is booked too
+    let res: ListLendersResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::ListLenders { start_after: None, limit: None })
+        .unwrap();
+    let principals: Vec<_> = res.lenders.iter().map(|lender| (lender.lender.as_str(), lender.principal.u128())).collect();
+    assert_eq!(principals, vec![(KEEPER, 400), (LENDER, 600)]);
+    sync(&mut app, &pool, LENDER);
+    let state: PoolStateResponse = app.wrap().query_wasm_smart(&pool, &QueryMsg::PoolState {}).unwrap();
+    assert_eq!(state.total_supplied.u128(), 1_000);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+
+    assert_eq!(ContractError::LockinTimePeriodActive {}, withdraw(&mut app, &pool, KEEPER, 400).unwrap_err());
+    advance(&mut app, YEAR / 10 + 1);
+    withdraw(&mut app, &pool, KEEPER, 400).unwrap();
+    assert_eq!(balance(&app, KEEPER, ASSET), INITIAL_BALANCE + 400);
+}
+
+#[test]
+fn a_few_shares_got_dont_lock_the_position() {
+    let (mut app, pool, token) = receipt_pool();
+    deposit(&mut app, &pool, LENDER2, 1_000);
+    advance(&mut app, YEAR / 2);
+
+    // the lock in of the 5 shares got is averaged over the 1_005 held
+    deposit(&mut app, &pool, LENDER, 10);
+    transfer(&mut app, &token, LENDER, LENDER2, 5);
+    approve(&mut app, &token, LENDER2, &pool, 1_005, None);
+    withdraw(&mut app, &pool, LENDER2, 1_000).unwrap();
+    assert_eq!(balance(&app, LENDER2, ASSET), INITIAL_BALANCE);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
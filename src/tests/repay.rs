@@ -0,0 +1,112 @@
+use cosmwasm_std::{coins, Addr, Uint128};
+use cw_multi_test::{App, AppResponse, Executor};
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{ExecuteMsg, LoanInfo, QueryMsg, ReceiveMsg, TransactMsg};
+
+fn repay_amount_msg(amount: u128) -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::RepayAmount { loan_id: 1, amount: Uint128::new(amount) })
+}
+
+fn attribute(res: &AppResponse, key: &str) -> String {
+    res.events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == key)
+        .map(|attr| attr.value.clone())
+        .unwrap()
+}
+
+// The borrower takes 1_000 against 2_000 collateral and owes 1_050 half a year later
+fn borrowed_pool() -> (App, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+    deposit(&mut app, &pool, LENDER, 10_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+    advance(&mut app, YEAR / 2);
+    (app, pool)
+}
+
+#[test]
+fn interest_only_payment_keeps_the_collateral() {
+    let (mut app, pool) = borrowed_pool();
+
+    let res = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_amount_msg(50), &coins(50, ASSET))
+        .unwrap();
+    assert_eq!(attribute(&res, "interest_paid"), "50");
+    assert_eq!(attribute(&res, "principal_paid"), "0");
+    assert_eq!(attribute(&res, "collateral_unlocked"), "0");
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE - 2_000);
+
+    // the debt left rounds up in favour of the pool
+    let loan: LoanInfo = app.wrap().query_wasm_smart(&pool, &QueryMsg::Loan { loan_id: 1 }).unwrap();
+    assert_eq!((loan.principal.u128(), loan.interest.u128()), (1_000, 1));
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn payment_goes_to_interest_then_principal() {
+    let (mut app, pool) = borrowed_pool();
+
+    let res = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_amount_msg(550), &coins(550, ASSET))
+        .unwrap();
+    assert_eq!(attribute(&res, "interest_paid"), "50");
+    assert_eq!(attribute(&res, "principal_paid"), "500");
+    assert_eq!(attribute(&res, "collateral_unlocked"), "1000");
+
+    // paying more than the debt closes the loan and the rest is refunded
+    let res = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_amount_msg(1_000), &coins(1_000, ASSET))
+        .unwrap();
+    assert_eq!(attribute(&res, "paid"), "501");
+    assert_eq!(balance(&app, BORROWER, ASSET), INITIAL_BALANCE + 1_000 - 550 - 501);
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE);
+
+    let err = app
+        .wrap()
+        .query_wasm_smart::<LoanInfo>(&pool, &QueryMsg::Loan { loan_id: 1 })
+        .unwrap_err();
+    assert!(err.to_string().contains(&ContractError::PositionNotAvailable {}.to_string()));
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn payment_needs_the_holder_and_the_funds() {
+    let (mut app, pool) = borrowed_pool();
+
+    let err = app
+        .execute_contract(Addr::unchecked(KEEPER), pool.clone(), &repay_amount_msg(100), &coins(100, ASSET))
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_amount_msg(100), &coins(99, ASSET))
+        .unwrap_err();
+    assert_eq!(ContractError::InsufficientFunds {}, err.downcast().unwrap());
+
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool, &repay_amount_msg(0), &coins(1, ASSET))
+        .unwrap_err();
+    assert_eq!(ContractError::InsufficientFunds {}, err.downcast().unwrap());
+}
+
+#[test]
+fn cw20_payment_refunds_the_excess() {
+    let mut app = native_app();
+    let pool = instantiate_cw20_pool(&mut app);
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+    loan_cw20(&mut app, &pool, BORROWER, 1_000);
+    advance(&mut app, YEAR / 2);
+
+    let msg = ReceiveMsg::RepayAmount { loan_id: 1 };
+    let res = send(&mut app, &pool.asset, BORROWER, &pool.pool, 1_200, &msg).unwrap();
+    assert_eq!(attribute(&res, "paid"), "1050");
+    assert_eq!(token_balance(&app, &pool.asset, BORROWER), INITIAL_BALANCE + 1_000 - 1_050);
+    assert_eq!(token_balance(&app, &pool.collateral, BORROWER), INITIAL_BALANCE);
+    assert!(invariants(&app, &pool.pool).discrepancies.is_empty());
+}
@@ -0,0 +1,235 @@
+use cosmwasm_std::{coins, Addr, Decimal, Uint128};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{ExecuteMsg, InstantiateMsg, LoanInfo, QueryMsg, RolloverSourceResponse, TransactMsg};
+use crate::types::{FeeConfig, PoolConfig};
+
+fn rollover_msg(target_pool: &Addr) -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::Rollover { loan_id: 1, target_pool: target_pool.to_string() })
+}
+
+fn allow_rollover_msg(from_pool: Option<&Addr>) -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::AllowRollover { from_pool: from_pool.map(Addr::to_string) })
+}
+
+fn top_up_msg(amount: u128) -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::TopUpCollateral { loan_id: 1, amount: Uint128::new(amount) })
+}
+
+fn loan(app: &App, pool: &Addr, loan_id: u64) -> Result<LoanInfo, cosmwasm_std::StdError> {
+    app.wrap().query_wasm_smart(pool, &QueryMsg::Loan { loan_id })
+}
+
+/// A pool of the same code as `source` with the given config and fees
+fn same_code_pool(app: &mut App, source: &Addr, config: PoolConfig, fees: Option<FeeConfig>) -> Addr {
+    let oracle = instantiate_oracle(app, Decimal::one());
+    let code_id = app.wrap().query_wasm_contract_info(source).unwrap().code_id;
+    let msg = InstantiateMsg { fees, ..instantiate_pool_msg(config, &oracle) };
+    app.instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "successor", None)
+        .unwrap()
+}
+
+/// A pool like `pool_config` of the same code as `source` maturing a year after the first one, with the given fees
+fn successor_pool(app: &mut App, source: &Addr, asset: &str, collateral: &str, fees: Option<FeeConfig>) -> Addr {
+    let now = app.block_info().time.seconds();
+    let config = PoolConfig { maturationdate: now + 2 * YEAR, ..pool_config(asset, collateral, now) };
+    same_code_pool(app, source, config, fees)
+}
+
+// The borrower takes 1_000 against 2_000 collateral from the first pool and owes 1_050 half a year later,
+// the successor has 10_000 to lend and takes rollovers of the borrower's loans from the first pool
+fn pools(successor_fees: Option<FeeConfig>) -> (App, Addr, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+    let successor = successor_pool(&mut app, &pool, ASSET, COLLATERAL, successor_fees);
+
+    deposit(&mut app, &pool, LENDER, 10_000);
+    deposit(&mut app, &successor, LENDER2, 10_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+        .unwrap();
+    app.execute_contract(Addr::unchecked(BORROWER), successor.clone(), &allow_rollover_msg(Some(&pool)), &[])
+        .unwrap();
+    advance(&mut app, YEAR / 2);
+    (app, pool, successor)
+}
+
+#[test]
+fn rollover_moves_the_loan_into_the_successor() {
+    let (mut app, pool, successor) = pools(None);
+    // the successor asks for 2x the 1_050 of debt as well
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &top_up_msg(100), &coins(100, COLLATERAL))
+        .unwrap();
+
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &rollover_msg(&successor), &[])
+        .unwrap();
+
+    assert!(loan(&app, &pool, 1).is_err());
+    assert_eq!(balance(&app, pool.as_str(), ASSET), 10_050);
+    assert_eq!(balance(&app, pool.as_str(), COLLATERAL), 0);
+
+    let rolled = loan(&app, &successor, 1).unwrap();
+    assert_eq!(rolled.borrower, Addr::unchecked(BORROWER));
+    assert_eq!((rolled.principal.u128(), rolled.collateral.u128()), (1_050, 2_100));
+    assert_eq!(balance(&app, successor.as_str(), ASSET), 8_950);
+    assert_eq!(balance(&app, successor.as_str(), COLLATERAL), 2_100);
+
+    // the borrower neither paid nor got anything
+    assert_eq!(balance(&app, BORROWER, ASSET), INITIAL_BALANCE + 1_000);
+    assert_eq!(balance(&app, BORROWER, COLLATERAL), INITIAL_BALANCE - 2_100);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+    assert!(invariants(&app, &successor).discrepancies.is_empty());
+}
+
+#[test]
+fn rollover_happens_whole_or_not_at_all() {
+    let (mut app, pool, successor) = pools(None);
+
+    // 2_000 of collateral is short of what the successor asks for 1_050
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &rollover_msg(&successor), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::InsufficientCollateral {}, err.downcast().unwrap());
+
+    assert_eq!(loan(&app, &pool, 1).unwrap().collateral.u128(), 2_000);
+    assert!(loan(&app, &successor, 1).is_err());
+    assert_eq!(balance(&app, pool.as_str(), COLLATERAL), 2_000);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn successor_fee_comes_on_top_of_the_debt() {
+    let fees = FeeConfig { reserve_factor: Decimal::zero(), origination_fee: Decimal::percent(1) };
+    let (mut app, pool, successor) = pools(Some(fees));
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &top_up_msg(200), &coins(200, COLLATERAL))
+        .unwrap();
+
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &rollover_msg(&successor), &[])
+        .unwrap();
+
+    // 1_061 less the 10 fee covers the 1_050, the 1 above it goes to the borrower
+    assert_eq!(loan(&app, &successor, 1).unwrap().principal.u128(), 1_061);
+    assert_eq!(balance(&app, BORROWER, ASSET), INITIAL_BALANCE + 1_000 + 1);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+    assert!(invariants(&app, &successor).discrepancies.is_empty());
+}
+
+#[test]
+fn rollover_needs_the_holder_and_a_matching_successor() {
+    let (mut app, pool, successor) = pools(None);
+    let now = app.block_info().time.seconds();
+    // the successor has to mature later
+    let same_maturity = same_code_pool(&mut app, &pool, pool_config(ASSET, COLLATERAL, now - YEAR / 2), None);
+    let other_collateral = successor_pool(&mut app, &pool, ASSET, "usilver", None);
+    // and run the same code
+    let other_code = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now + YEAR));
+
+    for target in [&pool, &same_maturity, &other_collateral, &other_code] {
+        let err = app
+            .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &rollover_msg(target), &[])
+            .unwrap_err();
+        assert_eq!(ContractError::InvalidRolloverTarget {}, err.downcast().unwrap());
+    }
+
+    let err = app
+        .execute_contract(Addr::unchecked(KEEPER), pool.clone(), &rollover_msg(&successor), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+}
+
+#[test]
+fn lookalike_successor_cant_repay_with_a_deposit() {
+    let (mut app, pool, successor) = pools(None);
+    // answers queries like the real successor and deposits into the pool instead of repaying
+    let code_id = app.store_code(mock_successor::contract());
+    let msg = mock_successor::InstantiateMsg { mirror: successor.to_string(), denom: ASSET.to_string() };
+    let fake = app
+        .instantiate_contract(code_id, Addr::unchecked(ADMIN), &msg, &[], "fake", None)
+        .unwrap();
+    app.send_tokens(Addr::unchecked(KEEPER), fake.clone(), &coins(2_000, ASSET)).unwrap();
+    app.execute_contract(Addr::unchecked(BORROWER), fake.clone(), &allow_rollover_msg(Some(&pool)), &[])
+        .unwrap();
+
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &rollover_msg(&fake), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::InvalidRolloverTarget {}, err.downcast().unwrap());
+
+    // nor can anyone book a repayment without a rollover waiting for it
+    let err = app
+        .execute_contract(
+            Addr::unchecked(KEEPER),
+            pool.clone(),
+            &ExecuteMsg::Transact(TransactMsg::RepayRollover {}),
+            &coins(1_050, ASSET),
+        )
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+    assert_eq!(loan(&app, &pool, 1).unwrap().collateral.u128(), 2_000);
+    assert_eq!(balance(&app, pool.as_str(), COLLATERAL), 2_000);
+    assert_eq!(balance(&app, fake.as_str(), ASSET), 2_000);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
+
+#[test]
+fn successor_only_takes_loans_the_borrower_allowed() {
+    let (mut app, pool, successor) = pools(None);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &top_up_msg(100), &coins(100, COLLATERAL))
+        .unwrap();
+
+    // nobody else can open a loan held by the borrower, even bringing the collateral
+    let accept = ExecuteMsg::Transact(TransactMsg::AcceptRollover {
+        borrower: BORROWER.to_string(),
+        asset_amount: Uint128::new(1_000),
+    });
+    let err = app
+        .execute_contract(Addr::unchecked(KEEPER), successor.clone(), &accept, &coins(2_000, COLLATERAL))
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+    let source: RolloverSourceResponse = app
+        .wrap()
+        .query_wasm_smart(&successor, &QueryMsg::RolloverSource { borrower: Addr::unchecked(BORROWER) })
+        .unwrap();
+    assert_eq!(source.from_pool, Some(pool.clone()));
+
+    // once the borrower takes it back the loan stays where it is
+    app.execute_contract(Addr::unchecked(BORROWER), successor.clone(), &allow_rollover_msg(None), &[])
+        .unwrap();
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &rollover_msg(&successor), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+    assert_eq!(loan(&app, &pool, 1).unwrap().collateral.u128(), 2_100);
+    assert!(loan(&app, &successor, 1).is_err());
+}
+
+#[test]
+fn cw20_collateral_is_sent_to_the_successor() {
+    let mut app = native_app();
+    let pool = instantiate_cw20_pool(&mut app);
+    let successor = Cw20Pool {
+        pool: successor_pool(&mut app, &pool.pool, &pool.asset_denom(), &pool.collateral_denom(), None),
+        oracle: pool.oracle.clone(),
+        asset: pool.asset.clone(),
+        collateral: pool.collateral.clone(),
+    };
+
+    deposit_cw20(&mut app, &pool, LENDER, 10_000);
+    deposit_cw20(&mut app, &successor, LENDER2, 10_000);
+    loan_cw20(&mut app, &pool, BORROWER, 1_000);
+    transact(&mut app, &successor, BORROWER, TransactMsg::AllowRollover { from_pool: Some(pool.pool.to_string()) })
+        .unwrap();
+
+    transact(&mut app, &pool, BORROWER, TransactMsg::Rollover { loan_id: 1, target_pool: successor.pool.to_string() })
+        .unwrap();
+
+    assert_eq!(token_balance(&app, &pool.collateral, pool.pool.as_str()), 0);
+    assert_eq!(token_balance(&app, &pool.collateral, successor.pool.as_str()), 2_000);
+    assert_eq!(loan(&app, &successor.pool, 1).unwrap().principal.u128(), 1_000);
+    assert!(invariants(&app, &pool.pool).discrepancies.is_empty());
+    assert!(invariants(&app, &successor.pool).discrepancies.is_empty());
+}
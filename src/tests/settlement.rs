@@ -0,0 +1,149 @@
+use cosmwasm_std::{coins, Addr, Uint128};
+use cw_multi_test::{App, Executor};
+
+use super::*;
+use crate::error::ContractError;
+use crate::msg::{ExecuteMsg, ListDefaultedLoansResponse, QueryMsg, TransactMsg, WithdrawMsg};
+use crate::types::Settlement;
+
+fn settle_msg() -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::Settle { limit: None })
+}
+
+fn settlement(app: &App, pool: &Addr) -> Option<Settlement> {
+    app.wrap().query_wasm_smart(pool, &QueryMsg::Settlement {}).unwrap()
+}
+
+fn redeem_msg() -> ExecuteMsg {
+    ExecuteMsg::Transact(TransactMsg::Redeem {})
+}
+
+// Two lenders put in 6_000 and 4_000, the borrower takes 5_000 against 10_000 collateral
+// and never repays it
+fn defaulted_pool() -> (App, Addr) {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+
+    deposit(&mut app, &pool, LENDER, 6_000);
+    deposit(&mut app, &pool, LENDER2, 4_000);
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(5_000), &coins(10_000, COLLATERAL))
+        .unwrap();
+
+    advance(&mut app, YEAR + 1);
+    (app, pool)
+}
+
+#[test]
+fn settle_requires_maturity() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER), pool, &settle_msg(), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::PoolNotMatured {}, err.downcast().unwrap());
+}
+
+#[test]
+fn matured_pool_only_pays_out_through_redeem() {
+    let (mut app, pool) = defaulted_pool();
+
+    let err = app
+        .execute_contract(Addr::unchecked(BORROWER), pool.clone(), &repay_msg(1, 5_000), &coins(5_500, ASSET))
+        .unwrap_err();
+    assert_eq!(ContractError::CollateralForfeited {}, err.downcast().unwrap());
+
+    let withdraw = ExecuteMsg::Transact(TransactMsg::Withdraw(WithdrawMsg {
+        denom: ASSET.to_string(),
+        amount: Uint128::new(5_000),
+    }));
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER), pool.clone(), &withdraw, &[])
+        .unwrap_err();
+    assert_eq!(ContractError::PoolMatured {}, err.downcast().unwrap());
+
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER), pool, &redeem_msg(), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::PoolNotSettled {}, err.downcast().unwrap());
+}
+
+#[test]
+fn lenders_redeem_pro_rata_share_of_asset_and_forfeited_collateral() {
+    let (mut app, pool) = defaulted_pool();
+
+    app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &settle_msg(), &[])
+        .unwrap();
+
+    // claims are principal plus 5% interest for the year
+    let settlement: Option<Settlement> = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::Settlement {})
+        .unwrap();
+    let settlement = settlement.unwrap();
+    assert_eq!(settlement.asset_available.u128(), 5_000);
+    assert_eq!(settlement.collateral_forfeited.u128(), 10_000);
+    assert_eq!(settlement.total_lender_claims.u128(), 10_500);
+
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER), pool.clone(), &settle_msg(), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::PoolSettled {}, err.downcast().unwrap());
+
+    app.execute_contract(Addr::unchecked(LENDER), pool.clone(), &redeem_msg(), &[])
+        .unwrap();
+    app.execute_contract(Addr::unchecked(LENDER2), pool.clone(), &redeem_msg(), &[])
+        .unwrap();
+
+    assert_eq!(balance(&app, LENDER, ASSET), INITIAL_BALANCE - 6_000 + 3_000);
+    assert_eq!(balance(&app, LENDER, COLLATERAL), INITIAL_BALANCE + 6_000);
+    assert_eq!(balance(&app, LENDER2, ASSET), INITIAL_BALANCE - 4_000 + 2_000);
+    assert_eq!(balance(&app, LENDER2, COLLATERAL), INITIAL_BALANCE + 4_000);
+    assert_eq!(balance(&app, pool.as_str(), ASSET), 0);
+    assert_eq!(balance(&app, pool.as_str(), COLLATERAL), 0);
+
+    // the position is closed once redeemed
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER), pool, &redeem_msg(), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::PositionNotAvailable {}, err.downcast().unwrap());
+}
+
+#[test]
+fn settlement_goes_through_the_loans_a_page_at_a_time() {
+    let mut app = native_app();
+    let now = app.block_info().time.seconds();
+    let pool = instantiate_pool(&mut app, pool_config(ASSET, COLLATERAL, now));
+    deposit(&mut app, &pool, LENDER, 10_000);
+    for _ in 0..3 {
+        app.execute_contract(Addr::unchecked(BORROWER), pool.clone(), &loan_msg(1_000), &coins(2_000, COLLATERAL))
+            .unwrap();
+    }
+    advance(&mut app, YEAR + 1);
+
+    let settle = ExecuteMsg::Transact(TransactMsg::Settle { limit: Some(2) });
+    app.execute_contract(Addr::unchecked(KEEPER), pool.clone(), &settle, &[])
+        .unwrap();
+    assert_eq!(settlement(&app, &pool), None);
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+    let err = app
+        .execute_contract(Addr::unchecked(LENDER), pool.clone(), &redeem_msg(), &[])
+        .unwrap_err();
+    assert_eq!(ContractError::PoolNotSettled {}, err.downcast().unwrap());
+
+    app.execute_contract(Addr::unchecked(KEEPER), pool.clone(), &settle, &[])
+        .unwrap();
+    assert_eq!(settlement(&app, &pool).unwrap().collateral_forfeited.u128(), 6_000);
+
+    // each loan is owed with a year of 10% interest
+    let res: ListDefaultedLoansResponse = app
+        .wrap()
+        .query_wasm_smart(&pool, &QueryMsg::ListDefaultedLoans { start_after: Some(1), limit: None })
+        .unwrap();
+    let defaulted: Vec<_> = res.loans.iter().map(|loan| (loan.loan_id, loan.debt.u128(), loan.collateral.u128())).collect();
+    assert_eq!(defaulted, vec![(2, 1_100, 2_000), (3, 1_100, 2_000)]);
+    assert_eq!(res.loans[0].borrower, Addr::unchecked(BORROWER));
+    assert!(invariants(&app, &pool).discrepancies.is_empty());
+}
@@ -1,7 +1,13 @@
+use std::fmt;
+
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use cosmwasm_std::{Addr, Timestamp, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{
+    Addr, CheckedMultiplyFractionError, Decimal, OverflowError, QuerierWrapper, StdResult, Timestamp, Uint128,
+};
+use cw20::{BalanceResponse, Cw20QueryMsg};
+
+use crate::amount::Amount;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -9,19 +15,324 @@ pub struct PoolConfig {
     pub name: String,
     pub symbol: String,
     pub maturationdate: u64, 
-    pub debtinterestrate: Uint128, 
-    pub strikeprice: Uint128,
-    pub lendinterestrate: Uint128,
-    pub overcollateralizationfactor: Uint128, 
-    pub asset: Addr,
-    pub collateral: Addr,
+    /// interest per year paid by borrowers on the loans opened at it, 0.1 is 10% a year, at most 1.
+    /// Compounded each time the pool accrues interest, at most once per block
+    pub debtinterestrate: Decimal, 
+    /// asset paid per unit of collateral, collateral is posted at this price
+    pub strikeprice: Decimal,
+    /// interest per year earned by lenders, at most the debt interest rate,
+    /// compounded like the debt interest rate
+    pub lendinterestrate: Decimal,
+    /// value of the collateral posted over the value lent, 1.5 posts 150% of the loan, at least 1
+    pub overcollateralizationfactor: Decimal, 
+    /// native denom or `cw20:<address>`
+    pub asset: String,
+    /// native denom or `cw20:<address>`
+    pub collateral: String,
     pub lock_in_period : Uint128 , 
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CoinConfig {
-    pub denom: Addr,
+    /// native denom or `cw20:<address>`, the same format as `Amount::denom`
+    pub denom: String,
     pub decimals: u16,
 }
 
+impl CoinConfig {
+    /// `amount` of this coin, moved in and out of the pool through `Amount`
+    pub fn amount(&self, amount: Uint128) -> Amount {
+        Amount::from_parts(self.denom.clone(), amount)
+    }
+
+    /// The token contract if this coin is a cw20, `None` for native coins
+    pub fn cw20_address(&self) -> Option<Addr> {
+        self.denom.strip_prefix("cw20:").map(Addr::unchecked)
+    }
+
+    /// What `address` holds of this coin, its bank balance or its cw20 balance
+    pub fn balance_of(&self, querier: &QuerierWrapper, address: &Addr) -> StdResult<Uint128> {
+        match self.cw20_address() {
+            None => Ok(querier.query_balance(address, &self.denom)?.amount),
+            Some(cw20_addr) => {
+                let res: BalanceResponse = querier.query_wasm_smart(
+                    cw20_addr,
+                    &Cw20QueryMsg::Balance { address: address.to_string() },
+                )?;
+                Ok(res.balance)
+            }
+        }
+    }
+}
+
+
+/// Pool wide interest accounting. All indices start at 1 and grow with their rate
+/// every block the pool is touched, until maturity. Balances are stored scaled down
+/// by the index when they are booked, so `scaled * index` is the balance with all the
+/// interest accrued since, and the totals below are known without visiting every user.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolState {
+    pub supply_index: Decimal,
+    /// one for the pool's debt interest rate and one for every other rate loans are still open at
+    pub borrow_indices: Vec<BorrowIndex>,
+    /// seconds, the indices include all interest up to here
+    pub last_accrued: u64,
+    pub total_scaled_supply: Uint128,
+    /// principal deposited by all lenders, without interest
+    pub total_supplied: Uint128,
+    /// principal lent out to all borrowers, without interest
+    pub total_borrowed: Uint128,
+}
+
+/// The borrow index of the loans opened at `rate`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BorrowIndex {
+    pub rate: Decimal,
+    pub index: Decimal,
+    pub total_scaled_debt: Uint128,
+}
+
+impl BorrowIndex {
+    pub fn new(rate: Decimal) -> Self {
+        BorrowIndex { rate, index: Decimal::one(), total_scaled_debt: Uint128::zero() }
+    }
+}
+
+impl PoolState {
+    pub fn new(now: u64, debt_rate: Decimal) -> Self {
+        PoolState {
+            supply_index: Decimal::one(),
+            borrow_indices: vec![BorrowIndex::new(debt_rate)],
+            last_accrued: now,
+            total_scaled_supply: Uint128::zero(),
+            total_supplied: Uint128::zero(),
+            total_borrowed: Uint128::zero(),
+        }
+    }
+
+    /// Principal plus interest owed to all lenders
+    pub fn total_supply(&self) -> Result<Uint128, CheckedMultiplyFractionError> {
+        self.total_scaled_supply.checked_mul_floor(self.supply_index)
+    }
+
+    /// Principal plus interest owed by all borrowers
+    pub fn total_debt(&self) -> Result<Uint128, CheckedMultiplyFractionError> {
+        let mut total_debt = Uint128::zero();
+        for borrow_index in &self.borrow_indices {
+            total_debt = total_debt.checked_add(borrow_index.total_scaled_debt.checked_mul_ceil(borrow_index.index)?)?;
+        }
+        Ok(total_debt)
+    }
+
+    /// Scaled debt of all borrowers, over every rate
+    pub fn total_scaled_debt(&self) -> Result<Uint128, OverflowError> {
+        self.borrow_indices
+            .iter()
+            .try_fold(Uint128::zero(), |total, borrow_index| total.checked_add(borrow_index.total_scaled_debt))
+    }
+
+    /// The borrow index of the loans at `rate`, 1 until a loan is opened at it
+    pub fn borrow_index(&self, rate: Decimal) -> Decimal {
+        self.borrow_indices
+            .iter()
+            .find(|borrow_index| borrow_index.rate == rate)
+            .map_or(Decimal::one(), |borrow_index| borrow_index.index)
+    }
+
+    /// The borrow index at `rate`, started at 1 if no loan is open at it
+    pub fn borrow_index_mut(&mut self, rate: Decimal) -> &mut BorrowIndex {
+        let position = match self.borrow_indices.iter().position(|borrow_index| borrow_index.rate == rate) {
+            Some(position) => position,
+            None => {
+                self.borrow_indices.push(BorrowIndex::new(rate));
+                self.borrow_indices.len() - 1
+            }
+        };
+        &mut self.borrow_indices[position]
+    }
+
+    /// Books `scaled` more debt at `rate`
+    pub fn add_scaled_debt(&mut self, rate: Decimal, scaled: Uint128) -> Result<(), OverflowError> {
+        let borrow_index = self.borrow_index_mut(rate);
+        borrow_index.total_scaled_debt = borrow_index.total_scaled_debt.checked_add(scaled)?;
+        Ok(())
+    }
+
+    /// Takes `scaled` debt at `rate` off the books
+    pub fn sub_scaled_debt(&mut self, rate: Decimal, scaled: Uint128) -> Result<(), OverflowError> {
+        let borrow_index = self.borrow_index_mut(rate);
+        borrow_index.total_scaled_debt = borrow_index.total_scaled_debt.checked_sub(scaled)?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LenderPosition {
+    /// deposited and not withdrawn yet, without interest
+    pub principal: Uint128,
+    /// principal plus interest not withdrawn yet, scaled by the supply index.
+    /// With a receipt token, the receipt tokens the pool last booked for the lender
+    pub scaled: Uint128,
+    /// the lock in runs from the last deposit, or from the receipt tokens got averaged over the shares
+    pub deposited_at: Timestamp,
+}
+
+impl LenderPosition {
+    pub fn balance(&self, state: &PoolState) -> Result<Uint128, CheckedMultiplyFractionError> {
+        self.scaled.checked_mul_floor(state.supply_index)
+    }
+}
+
+/// A loan drawn from the pool, a borrower can hold any number of them
+/// and each one is repaid, liquidated and settled on its own
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Loan {
+    pub id: u64,
+    /// holds the loan, the borrower until it's transferred as a cw721 token
+    pub borrower: Addr,
+    /// lent out and not repaid yet, without interest
+    pub principal: Uint128,
+    /// principal plus interest not repaid yet, scaled by the borrow index
+    pub scaled_debt: Uint128,
+    pub collateral: Uint128,
+    pub opened_at: Timestamp,
+    /// debt interest rate of the pool when the loan was opened,
+    /// the loan owes interest at it until it's repaid whatever the pool's rate becomes
+    pub rate: Decimal,
+}
+
+impl Loan {
+    pub fn debt(&self, state: &PoolState) -> Result<Uint128, CheckedMultiplyFractionError> {
+        self.scaled_debt.checked_mul_ceil(state.borrow_index(self.rate))
+    }
+}
+
+/// Principal a lender asked to withdraw while the asset was lent out,
+/// paid out of the asset coming back into the pool in the order it was asked for.
+/// It no longer earns interest once queued.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedWithdrawal {
+    pub lender: Addr,
+    /// not paid out yet
+    pub amount: Uint128,
+    pub queued_at: Timestamp,
+}
+
+/// A loan repaid into a successor pool, held while the successor pays its debt back to this pool.
+/// Only the successor's RepayRollover counts towards `repaid`, which the reply checks against `amount_due`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingRollover {
+    pub loan_id: u64,
+    pub holder: Addr,
+    pub target_pool: Addr,
+    pub amount_due: Uint128,
+    pub repaid: Uint128,
+}
+
+/// Snapshot taken when a matured pool is settled,
+/// every lender redeems `claim / total_lender_claims` of the asset and the forfeited collateral
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Settlement {
+    pub settled_at: u64,
+    pub asset_available: Uint128,
+    pub collateral_forfeited: Uint128,
+    /// principal plus interest owed to all lenders at maturity
+    pub total_lender_claims: Uint128,
+}
+
+/// A loan left unpaid at maturity, its collateral is forfeited to the lenders
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DefaultedLoan {
+    pub borrower: Addr,
+    /// principal plus interest owed at maturity
+    pub debt: Uint128,
+    pub collateral: Uint128,
+}
+
+/// A position whose health factor (collateral value / debt) drops below `threshold`
+/// can be liquidated: a keeper repays up to `close_factor` of the debt
+/// and receives the matching collateral at the oracle price plus `bonus`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LiquidationConfig {
+    pub threshold: Decimal,
+    pub bonus: Decimal,
+    pub close_factor: Decimal,
+}
+
+/// Fees the protocol takes into its earnings, a pool without a fee config charges none
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeConfig {
+    /// share of the interest repaid by borrowers, only out of the spread above the lend interest rate
+    pub reserve_factor: Decimal,
+    /// share of every loan kept when it is paid out, the borrower owes the full amount
+    pub origination_fee: Decimal,
+}
+
+/// Operations that can be halted independently, repaying a loan never is
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Operation {
+    Deposit,
+    Withdraw,
+    Loan,
+    WithdrawInterest,
+    Liquidate,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Operation::Deposit => "deposit",
+            Operation::Withdraw => "withdraw",
+            Operation::Loan => "loan",
+            Operation::WithdrawInterest => "withdraw_interest",
+            Operation::Liquidate => "liquidate",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Which operations are currently halted, nothing is by default
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PauseState {
+    pub deposit: bool,
+    pub withdraw: bool,
+    pub loan: bool,
+    pub withdraw_interest: bool,
+    pub liquidate: bool,
+}
+
+impl PauseState {
+    pub fn is_paused(&self, operation: Operation) -> bool {
+        match operation {
+            Operation::Deposit => self.deposit,
+            Operation::Withdraw => self.withdraw,
+            Operation::Loan => self.loan,
+            Operation::WithdrawInterest => self.withdraw_interest,
+            Operation::Liquidate => self.liquidate,
+        }
+    }
+
+    pub fn set(&mut self, operation: Operation, paused: bool) {
+        let flag = match operation {
+            Operation::Deposit => &mut self.deposit,
+            Operation::Withdraw => &mut self.withdraw,
+            Operation::Loan => &mut self.loan,
+            Operation::WithdrawInterest => &mut self.withdraw_interest,
+            Operation::Liquidate => &mut self.liquidate,
+        };
+        *flag = paused;
+    }
+}